@@ -0,0 +1,180 @@
+//! AES-GCM record throughput, wolfCrypt against aws-lc-rs.
+//!
+//! Run it once as is and once with wolfSSL's hardware acceleration,
+//! the difference between the two is the speedup:
+//!
+//!     cargo bench --bench aes_gcm
+//!     cargo bench --bench aes_gcm --features intelasm
+//!
+//! (each set of features gets its own wolfSSL build, see wolfcrypt-rs/build.rs)
+use rustls::crypto::cipher::{AeadKey, InboundOpaqueMessage, Iv, OutboundPlainMessage};
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::PrivatePkcs8KeyDer;
+use rustls::{ConnectionTrafficSecrets, ContentType, ProtocolVersion};
+use std::hint::black_box;
+use std::sync::Arc;
+use std::time::Instant;
+
+const RECORD_LEN: usize = 16 * 1024;
+const TOTAL_LEN: usize = 64 * 1024 * 1024;
+
+fn main() {
+    println!(
+        "wolfCrypt built with: {}",
+        match (cfg!(feature = "aesni"), cfg!(feature = "intelasm")) {
+            (_, true) => "AES-NI, AVX1/AVX2",
+            (true, false) => "AES-NI",
+            _ => "no acceleration",
+        }
+    );
+
+    for (name, wolfcrypt, aws_lc_rs) in [
+        (
+            "TLS13_AES_128_GCM_SHA256",
+            rustls_wolfcrypt_provider::TLS13_AES_128_GCM_SHA256,
+            rustls::crypto::aws_lc_rs::cipher_suite::TLS13_AES_128_GCM_SHA256,
+        ),
+        (
+            "TLS13_AES_256_GCM_SHA384",
+            rustls_wolfcrypt_provider::TLS13_AES_256_GCM_SHA384,
+            rustls::crypto::aws_lc_rs::cipher_suite::TLS13_AES_256_GCM_SHA384,
+        ),
+    ] {
+        for (provider, suite) in [
+            (
+                "wolfcrypt",
+                CryptoProvider {
+                    cipher_suites: vec![wolfcrypt],
+                    ..rustls_wolfcrypt_provider::provider()
+                },
+            ),
+            (
+                "aws-lc-rs",
+                CryptoProvider {
+                    cipher_suites: vec![aws_lc_rs],
+                    ..rustls::crypto::aws_lc_rs::default_provider()
+                },
+            ),
+        ] {
+            let (encrypt, decrypt) = throughput(suite);
+            println!(
+                "{:<26} {:<10} encrypt {:>8.1} MB/s   decrypt {:>8.1} MB/s",
+                name, provider, encrypt, decrypt
+            );
+        }
+    }
+}
+
+/// Returns the encryption and decryption throughput, in MB/s,
+/// of full size records through the TLS 1.3 AEAD of the provider's
+/// only suite.
+fn throughput(provider: CryptoProvider) -> (f64, f64) {
+    let suite = provider.cipher_suites[0].tls13().unwrap();
+    let ((key, iv), (peer_key, peer_iv)) = traffic_keys(provider);
+    let mut encrypter = suite.aead_alg.encrypter(key, iv);
+    let mut decrypter = suite.aead_alg.decrypter(peer_key, peer_iv);
+
+    let plain = vec![0xa5u8; RECORD_LEN];
+    let records = TOTAL_LEN / RECORD_LEN;
+
+    let start = Instant::now();
+    let mut encrypted = Vec::with_capacity(records);
+    for seq in 0..records as u64 {
+        let m = OutboundPlainMessage {
+            typ: ContentType::ApplicationData,
+            version: ProtocolVersion::TLSv1_2,
+            payload: plain[..].into(),
+        };
+        encrypted.push(black_box(encrypter.encrypt(m, seq).unwrap().encode()));
+    }
+    let encrypt = mb_per_second(start);
+
+    let start = Instant::now();
+    for (seq, record) in encrypted.iter_mut().enumerate() {
+        // Skip the 5 byte record header.
+        let m = InboundOpaqueMessage::new(
+            ContentType::ApplicationData,
+            ProtocolVersion::TLSv1_2,
+            &mut record[5..],
+        );
+        black_box(decrypter.decrypt(m, seq as u64).unwrap());
+    }
+    let decrypt = mb_per_second(start);
+
+    (encrypt, decrypt)
+}
+
+fn mb_per_second(start: Instant) -> f64 {
+    TOTAL_LEN as f64 / start.elapsed().as_secs_f64() / 1_000_000.0
+}
+
+/// Runs a TLS 1.3 handshake in memory with the provider on both sides,
+/// and returns the client's write key along with the server's read key,
+/// which are the same, as extracted from the connections.
+fn traffic_keys(provider: CryptoProvider) -> ((AeadKey, Iv), (AeadKey, Iv)) {
+    let provider = Arc::new(provider);
+
+    let ca_key = rcgen::KeyPair::generate().unwrap();
+    let mut ca_params = rcgen::CertificateParams::new(Vec::new()).unwrap();
+    ca_params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+    let ca_cert = ca_params.self_signed(&ca_key).unwrap();
+    let server_key = rcgen::KeyPair::generate().unwrap();
+    let server_cert = rcgen::CertificateParams::new(vec!["localhost".to_string()])
+        .unwrap()
+        .signed_by(&server_key, &ca_cert, &ca_key)
+        .unwrap();
+
+    let mut root_store = rustls::RootCertStore::empty();
+    root_store.add(ca_cert.into()).unwrap();
+    let mut client_config = rustls::ClientConfig::builder_with_provider(provider.clone())
+        .with_protocol_versions(&[&rustls::version::TLS13])
+        .unwrap()
+        .with_root_certificates(root_store)
+        .with_no_client_auth();
+    client_config.enable_secret_extraction = true;
+    let mut server_config = rustls::ServerConfig::builder_with_provider(provider)
+        .with_protocol_versions(&[&rustls::version::TLS13])
+        .unwrap()
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![server_cert.into()],
+            PrivatePkcs8KeyDer::from(server_key.serialize_der()).into(),
+        )
+        .unwrap();
+    server_config.enable_secret_extraction = true;
+
+    let mut client: rustls::Connection =
+        rustls::ClientConnection::new(Arc::new(client_config), "localhost".try_into().unwrap())
+            .unwrap()
+            .into();
+    let mut server: rustls::Connection = rustls::ServerConnection::new(Arc::new(server_config))
+        .unwrap()
+        .into();
+    while client.is_handshaking() || server.is_handshaking() {
+        transfer(&mut client, &mut server);
+        transfer(&mut server, &mut client);
+    }
+
+    let key_and_iv = |secrets: ConnectionTrafficSecrets| match secrets {
+        ConnectionTrafficSecrets::Aes128Gcm { key, iv }
+        | ConnectionTrafficSecrets::Aes256Gcm { key, iv } => (key, iv),
+        _ => unreachable!("not an AES-GCM suite"),
+    };
+    (
+        key_and_iv(client.dangerous_extract_secrets().unwrap().tx.1),
+        key_and_iv(server.dangerous_extract_secrets().unwrap().rx.1),
+    )
+}
+
+fn transfer(from: &mut rustls::Connection, to: &mut rustls::Connection) {
+    let mut buf = Vec::new();
+    while from.wants_write() {
+        from.write_tls(&mut buf).unwrap();
+    }
+
+    let mut rd = buf.as_slice();
+    while !rd.is_empty() {
+        to.read_tls(&mut rd).unwrap();
+        to.process_new_packets().unwrap();
+    }
+}
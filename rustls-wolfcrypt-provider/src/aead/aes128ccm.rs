@@ -0,0 +1,498 @@
+use crate::aead::context::AesCcmContext;
+use alloc::boxed::Box;
+use rustls::crypto::cipher::{
+    make_tls12_aad, make_tls13_aad, AeadKey, InboundOpaqueMessage, InboundPlainMessage, Iv,
+    KeyBlockShape, MessageDecrypter, MessageEncrypter, Nonce, OutboundOpaqueMessage,
+    OutboundPlainMessage, PrefixedPayload, Tls12AeadAlgorithm, Tls13AeadAlgorithm,
+    UnsupportedOperationError,
+};
+use rustls::{ConnectionTrafficSecrets, ContentType, ProtocolVersion};
+
+const CCM_NONCE_LENGTH: usize = 12;
+pub(crate) const CCM_TAG_LENGTH: usize = 16;
+pub(crate) const CCM_8_TAG_LENGTH: usize = 8;
+
+/// AES-128-CCM with a 16 byte tag (TLS13_AES_128_CCM_SHA256,
+/// TLS_ECDHE_ECDSA_WITH_AES_128_CCM).
+pub struct Aes128Ccm;
+
+/// AES-128-CCM with an 8 byte tag (TLS13_AES_128_CCM_8_SHA256,
+/// TLS_ECDHE_ECDSA_WITH_AES_128_CCM_8).
+pub struct Aes128Ccm8;
+
+impl Tls12AeadAlgorithm for Aes128Ccm {
+    fn encrypter(&self, key: AeadKey, iv: &[u8], extra: &[u8]) -> Box<dyn MessageEncrypter> {
+        Box::new(WCTls12Encrypter::new(
+            key.as_ref(),
+            iv,
+            extra,
+            CCM_TAG_LENGTH,
+        ))
+    }
+
+    fn decrypter(&self, key: AeadKey, iv: &[u8]) -> Box<dyn MessageDecrypter> {
+        Box::new(WCTls12Decrypter::new(key.as_ref(), iv, CCM_TAG_LENGTH))
+    }
+
+    fn key_block_shape(&self) -> KeyBlockShape {
+        KeyBlockShape {
+            enc_key_len: 16,
+            fixed_iv_len: 4,
+            explicit_nonce_len: 8,
+        }
+    }
+
+    fn extract_keys(
+        &self,
+        _key: AeadKey,
+        _iv: &[u8],
+        _explicit: &[u8],
+    ) -> Result<ConnectionTrafficSecrets, UnsupportedOperationError> {
+        Err(UnsupportedOperationError)
+    }
+}
+
+impl Tls12AeadAlgorithm for Aes128Ccm8 {
+    fn encrypter(&self, key: AeadKey, iv: &[u8], extra: &[u8]) -> Box<dyn MessageEncrypter> {
+        Box::new(WCTls12Encrypter::new(
+            key.as_ref(),
+            iv,
+            extra,
+            CCM_8_TAG_LENGTH,
+        ))
+    }
+
+    fn decrypter(&self, key: AeadKey, iv: &[u8]) -> Box<dyn MessageDecrypter> {
+        Box::new(WCTls12Decrypter::new(key.as_ref(), iv, CCM_8_TAG_LENGTH))
+    }
+
+    fn key_block_shape(&self) -> KeyBlockShape {
+        KeyBlockShape {
+            enc_key_len: 16,
+            fixed_iv_len: 4,
+            explicit_nonce_len: 8,
+        }
+    }
+
+    fn extract_keys(
+        &self,
+        _key: AeadKey,
+        _iv: &[u8],
+        _explicit: &[u8],
+    ) -> Result<ConnectionTrafficSecrets, UnsupportedOperationError> {
+        Err(UnsupportedOperationError)
+    }
+}
+
+// Same split as for GCM (RFC 6655 uses the same nonce construction):
+// the encrypter keeps the full nonce, the decrypter only the implicit part
+// since the explicit one travels with every record.
+// These don't care about the key size, so the AES-256 suites use them too.
+// Both keep their AES context keyed for as long as the traffic key lives.
+pub(crate) struct WCTls12Encrypter {
+    iv: Iv,
+    context: AesCcmContext,
+    tag_len: usize,
+}
+
+pub(crate) struct WCTls12Decrypter {
+    implicit_iv: [u8; 4],
+    context: AesCcmContext,
+    tag_len: usize,
+}
+
+impl WCTls12Encrypter {
+    pub(crate) fn new(key: &[u8], iv: &[u8], extra: &[u8], tag_len: usize) -> Self {
+        let mut iv_as_array = [0u8; CCM_NONCE_LENGTH];
+        iv_as_array[..(CCM_NONCE_LENGTH - 8)].copy_from_slice(iv); // implicit
+        iv_as_array[(CCM_NONCE_LENGTH - 8)..].copy_from_slice(extra); // explicit
+
+        WCTls12Encrypter {
+            iv: iv_as_array.into(),
+            context: AesCcmContext::new(key),
+            tag_len,
+        }
+    }
+}
+
+impl WCTls12Decrypter {
+    pub(crate) fn new(key: &[u8], iv: &[u8], tag_len: usize) -> Self {
+        let mut iv_implicit_as_array = [0u8; CCM_NONCE_LENGTH - 8];
+        iv_implicit_as_array.copy_from_slice(iv);
+
+        WCTls12Decrypter {
+            implicit_iv: iv_implicit_as_array,
+            context: AesCcmContext::new(key),
+            tag_len,
+        }
+    }
+}
+
+impl MessageEncrypter for WCTls12Encrypter {
+    fn encrypt(
+        &mut self,
+        m: OutboundPlainMessage,
+        seq: u64,
+    ) -> Result<OutboundOpaqueMessage, rustls::Error> {
+        let total_len = self.encrypted_payload_len(m.payload.len());
+        let mut payload = PrefixedPayload::with_capacity(total_len);
+
+        // The record starts with the explicit part of the nonce (8 bytes),
+        // followed by the cipher text and the tag.
+        let nonce = Nonce::new(&self.iv, seq).0;
+        payload.extend_from_slice(&nonce[(CCM_NONCE_LENGTH - 8)..]);
+        payload.extend_from_chunks(&m.payload);
+        payload.extend_from_slice(&[0u8; CCM_TAG_LENGTH][..self.tag_len]);
+
+        let aad = make_tls12_aad(seq, m.typ, m.version, m.payload.len());
+
+        // Encrypted in place, with the tag written into the room left for it.
+        let payload_start = CCM_NONCE_LENGTH - 4;
+        let (message, auth_tag) = payload.as_mut()[payload_start..].split_at_mut(m.payload.len());
+        self.context
+            .seal_in_place(&nonce, &aad, message, auth_tag)
+            .map_err(|_| rustls::Error::EncryptError)?;
+
+        Ok(OutboundOpaqueMessage::new(m.typ, m.version, payload))
+    }
+
+    fn encrypted_payload_len(&self, payload_len: usize) -> usize {
+        payload_len + (CCM_NONCE_LENGTH - 4) + self.tag_len
+    }
+}
+
+impl MessageDecrypter for WCTls12Decrypter {
+    fn decrypt<'a>(
+        &mut self,
+        mut m: InboundOpaqueMessage<'a>,
+        seq: u64,
+    ) -> Result<InboundPlainMessage<'a>, rustls::Error> {
+        let payload = &mut m.payload;
+        let payload_len = payload.len();
+        let payload_start = CCM_NONCE_LENGTH - 4;
+
+        if payload_len < payload_start + self.tag_len {
+            return Err(rustls::Error::DecryptError);
+        }
+
+        let payload_end = payload_len - self.tag_len;
+
+        let mut nonce = [0u8; CCM_NONCE_LENGTH];
+        nonce[..(CCM_NONCE_LENGTH - 8)].copy_from_slice(self.implicit_iv.as_ref());
+        nonce[(CCM_NONCE_LENGTH - 8)..].copy_from_slice(&payload[..payload_start]);
+
+        let aad = make_tls12_aad(seq, m.typ, m.version, payload_end - payload_start);
+
+        let (message, auth_tag) =
+            payload[payload_start..].split_at_mut(payload_end - payload_start);
+        self.context
+            .open_in_place(&nonce, &aad, message, auth_tag)
+            .map_err(|_| rustls::Error::DecryptError)?;
+
+        payload.copy_within(payload_start..payload_end, 0);
+        payload.truncate(payload_end - payload_start);
+
+        Ok(m.into_plain_message())
+    }
+}
+
+impl Tls13AeadAlgorithm for Aes128Ccm {
+    fn encrypter(&self, key: AeadKey, iv: Iv) -> Box<dyn MessageEncrypter> {
+        Box::new(WCTls13Cipher {
+            context: AesCcmContext::new(key.as_ref()),
+            iv,
+            tag_len: CCM_TAG_LENGTH,
+        })
+    }
+
+    fn decrypter(&self, key: AeadKey, iv: Iv) -> Box<dyn MessageDecrypter> {
+        Box::new(WCTls13Cipher {
+            context: AesCcmContext::new(key.as_ref()),
+            iv,
+            tag_len: CCM_TAG_LENGTH,
+        })
+    }
+
+    fn key_len(&self) -> usize {
+        16_usize
+    }
+
+    // rustls has no ConnectionTrafficSecrets variant for CCM.
+    fn extract_keys(
+        &self,
+        _key: AeadKey,
+        _iv: Iv,
+    ) -> Result<ConnectionTrafficSecrets, UnsupportedOperationError> {
+        Err(UnsupportedOperationError)
+    }
+}
+
+impl Tls13AeadAlgorithm for Aes128Ccm8 {
+    fn encrypter(&self, key: AeadKey, iv: Iv) -> Box<dyn MessageEncrypter> {
+        Box::new(WCTls13Cipher {
+            context: AesCcmContext::new(key.as_ref()),
+            iv,
+            tag_len: CCM_8_TAG_LENGTH,
+        })
+    }
+
+    fn decrypter(&self, key: AeadKey, iv: Iv) -> Box<dyn MessageDecrypter> {
+        Box::new(WCTls13Cipher {
+            context: AesCcmContext::new(key.as_ref()),
+            iv,
+            tag_len: CCM_8_TAG_LENGTH,
+        })
+    }
+
+    fn key_len(&self) -> usize {
+        16_usize
+    }
+
+    fn extract_keys(
+        &self,
+        _key: AeadKey,
+        _iv: Iv,
+    ) -> Result<ConnectionTrafficSecrets, UnsupportedOperationError> {
+        Err(UnsupportedOperationError)
+    }
+}
+
+pub struct WCTls13Cipher {
+    context: AesCcmContext,
+    iv: Iv,
+    tag_len: usize,
+}
+
+impl MessageEncrypter for WCTls13Cipher {
+    fn encrypt(
+        &mut self,
+        m: OutboundPlainMessage,
+        seq: u64,
+    ) -> Result<OutboundOpaqueMessage, rustls::Error> {
+        let payload_len = m.payload.len();
+        let total_len = self.encrypted_payload_len(payload_len);
+        let mut payload = PrefixedPayload::with_capacity(total_len);
+
+        // Same layout as the other TLS 1.3 ciphers: the plaintext
+        // followed by the ContentType, which is also encrypted.
+        payload.extend_from_chunks(&m.payload);
+        payload.extend_from_slice(&m.typ.to_array());
+        payload.extend_from_slice(&[0u8; CCM_TAG_LENGTH][..self.tag_len]);
+
+        let nonce = Nonce::new(&self.iv, seq);
+        let aad = make_tls13_aad(total_len);
+
+        // This encrypts the payload (and the ContentType) in place using CCM,
+        // and generates the authorization tag from the additional data;
+        // the tag length selects between CCM and CCM_8.
+        let (message, auth_tag) = payload.as_mut().split_at_mut(payload_len + 1);
+        self.context
+            .seal_in_place(&nonce.0, &aad, message, auth_tag)
+            .map_err(|_| rustls::Error::EncryptError)?;
+
+        Ok(OutboundOpaqueMessage::new(
+            ContentType::ApplicationData,
+            ProtocolVersion::TLSv1_2,
+            payload,
+        ))
+    }
+
+    fn encrypted_payload_len(&self, payload_len: usize) -> usize {
+        // the + 1 refers to the encoded type (included in the encrypted payload).
+        payload_len + 1 + self.tag_len
+    }
+}
+
+impl MessageDecrypter for WCTls13Cipher {
+    fn decrypt<'a>(
+        &mut self,
+        mut m: InboundOpaqueMessage<'a>,
+        seq: u64,
+    ) -> Result<InboundPlainMessage<'a>, rustls::Error> {
+        let payload = &mut m.payload;
+        if payload.len() < self.tag_len {
+            return Err(rustls::Error::DecryptError);
+        }
+
+        let nonce = Nonce::new(&self.iv, seq);
+        let aad = make_tls13_aad(payload.len());
+        let message_len = payload.len() - self.tag_len;
+
+        // This decrypts the cipher text in place, and checks the authentication
+        // tag against the additional data.
+        // A record that doesn't authenticate is the peer's problem, not ours,
+        // so it's reported as a decrypt error rather than a panic.
+        let (message, auth_tag) = payload.split_at_mut(message_len);
+        self.context
+            .open_in_place(&nonce.0, &aad, message, auth_tag)
+            .map_err(|_| rustls::Error::DecryptError)?;
+
+        payload.truncate(message_len);
+
+        m.into_tls13_unpadded_message()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    const KEY: [u8; 16] = [
+        0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e,
+        0x0f,
+    ];
+    const IV: [u8; 12] = [
+        0xa0, 0xa1, 0xa2, 0xa3, 0xa4, 0xa5, 0xa6, 0xa7, 0xa8, 0xa9, 0xaa, 0xab,
+    ];
+
+    // Outside of rustls an AeadKey can only be built out of 32 bytes,
+    // so the ciphers are put together by hand with the 16 byte key.
+    fn encrypt_and_decrypt(tag_len: usize, expected: &[u8]) {
+        let mut encrypter = WCTls13Cipher {
+            context: AesCcmContext::new(&KEY),
+            iv: Iv::new(IV),
+            tag_len,
+        };
+        let mut decrypter = WCTls13Cipher {
+            context: AesCcmContext::new(&KEY),
+            iv: Iv::new(IV),
+            tag_len,
+        };
+
+        let plain = OutboundPlainMessage {
+            typ: ContentType::ApplicationData,
+            version: ProtocolVersion::TLSv1_2,
+            payload: b"hello CCM"[..].into(),
+        };
+        let encrypted = encrypter.encrypt(plain, 1).unwrap().encode();
+
+        // Skip the 5 byte record header.
+        assert_eq!(&encrypted[5..], expected);
+
+        let mut record = encrypted[5..].to_vec();
+        let decrypted = decrypter
+            .decrypt(
+                InboundOpaqueMessage::new(
+                    ContentType::ApplicationData,
+                    ProtocolVersion::TLSv1_2,
+                    &mut record,
+                ),
+                1,
+            )
+            .unwrap();
+        assert_eq!(decrypted.typ, ContentType::ApplicationData);
+        assert_eq!(decrypted.payload, b"hello CCM");
+
+        // Flipping a bit in the tag must be caught.
+        let mut tampered = encrypted[5..].to_vec();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(decrypter
+            .decrypt(
+                InboundOpaqueMessage::new(
+                    ContentType::ApplicationData,
+                    ProtocolVersion::TLSv1_2,
+                    &mut tampered,
+                ),
+                2,
+            )
+            .is_err());
+    }
+
+    fn encrypt_and_decrypt_tls12(tag_len: usize, expected: &[u8]) {
+        let implicit_iv = [0xa0, 0xa1, 0xa2, 0xa3];
+        let explicit = [0xb0, 0xb1, 0xb2, 0xb3, 0xb4, 0xb5, 0xb6, 0xb7];
+        let mut encrypter = WCTls12Encrypter::new(&KEY, &implicit_iv, &explicit, tag_len);
+        let mut decrypter = WCTls12Decrypter::new(&KEY, &implicit_iv, tag_len);
+
+        let plain = OutboundPlainMessage {
+            typ: ContentType::ApplicationData,
+            version: ProtocolVersion::TLSv1_2,
+            payload: b"hello CCM"[..].into(),
+        };
+        let encrypted = encrypter.encrypt(plain, 1).unwrap().encode();
+
+        // explicit nonce (explicit ^ seq) || cipher text || tag
+        assert_eq!(&encrypted[5..], expected);
+
+        let mut record = encrypted[5..].to_vec();
+        let decrypted = decrypter
+            .decrypt(
+                InboundOpaqueMessage::new(
+                    ContentType::ApplicationData,
+                    ProtocolVersion::TLSv1_2,
+                    &mut record,
+                ),
+                1,
+            )
+            .unwrap();
+        assert_eq!(decrypted.payload, b"hello CCM");
+
+        // The sequence number is part of the additional data.
+        let mut replayed = encrypted[5..].to_vec();
+        assert!(decrypter
+            .decrypt(
+                InboundOpaqueMessage::new(
+                    ContentType::ApplicationData,
+                    ProtocolVersion::TLSv1_2,
+                    &mut replayed,
+                ),
+                2,
+            )
+            .is_err());
+
+        // Too short to even hold the explicit nonce and the tag.
+        let mut truncated = encrypted[5..12].to_vec();
+        assert!(decrypter
+            .decrypt(
+                InboundOpaqueMessage::new(
+                    ContentType::ApplicationData,
+                    ProtocolVersion::TLSv1_2,
+                    &mut truncated,
+                ),
+                1,
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_aes128ccm_tls13_record() {
+        let expected = vec![
+            0x16, 0x05, 0x8e, 0xfe, 0xcf, 0x40, 0x55, 0xd2, 0xa8, 0x8f, 0x93, 0x2f, 0x5a, 0x60,
+            0xb2, 0x64, 0xbd, 0xa8, 0x28, 0x71, 0x35, 0x21, 0x96, 0x9e, 0x95, 0xc9,
+        ];
+
+        encrypt_and_decrypt(CCM_TAG_LENGTH, &expected);
+    }
+
+    #[test]
+    fn test_aes128ccm8_tls13_record() {
+        let expected = vec![
+            0x16, 0x05, 0x8e, 0xfe, 0xcf, 0x40, 0x55, 0xd2, 0xa8, 0x8f, 0x07, 0xd4, 0x5a, 0x60,
+            0x92, 0xbf, 0xb6, 0x88,
+        ];
+
+        encrypt_and_decrypt(CCM_8_TAG_LENGTH, &expected);
+    }
+
+    #[test]
+    fn test_aes128ccm_tls12_record() {
+        let expected = vec![
+            0xb0, 0xb1, 0xb2, 0xb3, 0xb4, 0xb5, 0xb6, 0xb6, 0x9b, 0x7f, 0xe5, 0x97, 0x6c, 0xf1,
+            0x77, 0x41, 0x8b, 0xe1, 0x8c, 0x97, 0x6a, 0x4d, 0xc5, 0x9c, 0x85, 0xd3, 0x05, 0x6c,
+            0x78, 0x9b, 0x14, 0x88, 0x74,
+        ];
+
+        encrypt_and_decrypt_tls12(CCM_TAG_LENGTH, &expected);
+    }
+
+    #[test]
+    fn test_aes128ccm8_tls12_record() {
+        let expected = vec![
+            0xb0, 0xb1, 0xb2, 0xb3, 0xb4, 0xb5, 0xb6, 0xb6, 0x9b, 0x7f, 0xe5, 0x97, 0x6c, 0xf1,
+            0x77, 0x41, 0x8b, 0x5f, 0x8a, 0x96, 0x8a, 0x35, 0x0b, 0x0c, 0xe5,
+        ];
+
+        encrypt_and_decrypt_tls12(CCM_8_TAG_LENGTH, &expected);
+    }
+}
@@ -1,9 +1,6 @@
-use crate::error::check_if_zero;
-use crate::types::*;
+use crate::aead::context::AesGcmContext;
 use alloc::boxed::Box;
 use alloc::vec;
-use core::mem;
-use foreign_types::ForeignType;
 use rustls::crypto::cipher::{
     make_tls12_aad, make_tls13_aad, AeadKey, InboundOpaqueMessage, InboundPlainMessage, Iv,
     KeyBlockShape, MessageDecrypter, MessageEncrypter, Nonce, OutboundOpaqueMessage,
@@ -12,10 +9,6 @@ use rustls::crypto::cipher::{
 };
 use rustls::{ConnectionTrafficSecrets, ContentType, ProtocolVersion};
 
-use alloc::vec::Vec;
-use core::ptr;
-use wolfcrypt_rs::*;
-
 const GCM_NONCE_LENGTH: usize = 12;
 const GCM_TAG_LENGTH: usize = 16;
 
@@ -26,11 +19,10 @@ impl Tls12AeadAlgorithm for Aes128Gcm {
         let mut iv_as_array = [0u8; GCM_NONCE_LENGTH];
         iv_as_array[..(GCM_NONCE_LENGTH - 8)].copy_from_slice(iv); // implicit
         iv_as_array[(GCM_NONCE_LENGTH - 8)..].copy_from_slice(extra); // explicit
-        let key_as_slice = key.as_ref();
 
         Box::new(WCTls12Encrypter {
             iv: iv_as_array.into(),
-            key: key_as_slice.to_vec(),
+            context: AesGcmContext::new(key.as_ref()),
         })
     }
 
@@ -41,11 +33,9 @@ impl Tls12AeadAlgorithm for Aes128Gcm {
         let mut iv_implicit_as_array = [0u8; GCM_NONCE_LENGTH - 8];
         iv_implicit_as_array.copy_from_slice(iv);
 
-        let key_as_slice = key.as_ref();
-
         Box::new(WCTls12Decrypter {
             implicit_iv: iv_implicit_as_array,
-            key: key_as_slice.to_vec(),
+            context: AesGcmContext::new(key.as_ref()),
         })
     }
 
@@ -63,10 +53,12 @@ impl Tls12AeadAlgorithm for Aes128Gcm {
         iv: &[u8],
         explicit: &[u8],
     ) -> Result<ConnectionTrafficSecrets, UnsupportedOperationError> {
+        // Same layout as the encrypter's nonce, the implicit part
+        // filling the first 4 bytes and the explicit one the other 8.
         let mut iv_as_vec = vec![0u8; GCM_NONCE_LENGTH];
 
-        iv_as_vec.copy_from_slice(iv);
-        iv_as_vec.copy_from_slice(explicit);
+        iv_as_vec[..(GCM_NONCE_LENGTH - 8)].copy_from_slice(iv); // implicit
+        iv_as_vec[(GCM_NONCE_LENGTH - 8)..].copy_from_slice(explicit); // explicit
 
         Ok(ConnectionTrafficSecrets::Aes128Gcm {
             key,
@@ -78,14 +70,15 @@ impl Tls12AeadAlgorithm for Aes128Gcm {
 // Since we use a different Iv (full_iv/implicit) based of
 // the process on what we are doing (encryption/decryption)
 // We separate the structs for the implementation.
+// Both keep their AES context keyed for as long as the traffic key lives.
 pub struct WCTls12Encrypter {
     iv: Iv,
-    key: Vec<u8>,
+    context: AesGcmContext,
 }
 
 pub struct WCTls12Decrypter {
     implicit_iv: [u8; 4],
-    key: Vec<u8>,
+    context: AesGcmContext,
 }
 
 impl MessageEncrypter for WCTls12Encrypter {
@@ -109,53 +102,18 @@ impl MessageEncrypter for WCTls12Encrypter {
         payload.extend_from_slice(&nonce[(GCM_NONCE_LENGTH - 8)..]);
         payload.extend_from_chunks(&m.payload);
 
+        // Room for the tag, which gets written in place.
+        payload.extend_from_slice(&[0u8; GCM_TAG_LENGTH]);
+
         let aad = make_tls12_aad(seq, m.typ, m.version, m.payload.len());
-        let mut auth_tag = vec![0u8; GCM_TAG_LENGTH];
-        let mut aes_c_type: Aes = unsafe { mem::zeroed() };
-        let aes_object = unsafe { AesObject::from_ptr(&mut aes_c_type) };
-        let mut ret;
-
-        // Initialize Aes structure.
-        ret = unsafe { wc_AesInit(aes_object.as_ptr(), ptr::null_mut(), INVALID_DEVID) };
-        check_if_zero(ret).unwrap();
-
-        // This function is used to set the key for AES GCM (Galois/Counter Mode).
-        // It initializes an AES object with the given key.
-        ret = unsafe {
-            wc_AesGcmSetKey(
-                aes_object.as_ptr(),
-                self.key.as_ptr(),
-                self.key.len() as word32,
-            )
-        };
-        check_if_zero(ret).unwrap();
-
-        // This function encrypts the input message, held in the buffer in,
-        // and stores the resulting cipher text in the output buffer out.
-        // It requires a new iv (initialization vector) for each call to encrypt.
-        // It also encodes the input authentication vector,
-        // authIn, into the authentication tag, authTag.
-        // We only care about the explicit IV, so we skip the first
-        // 8 bytes.
-        let payload_start = GCM_NONCE_LENGTH - 4;
-        let payload_end = m.payload.len() + (GCM_NONCE_LENGTH - 4);
-        ret = unsafe {
-            wc_AesGcmEncrypt(
-                aes_object.as_ptr(),
-                payload.as_mut()[payload_start..payload_end].as_mut_ptr(),
-                payload.as_ref()[payload_start..payload_end].as_ptr(),
-                payload.as_ref()[payload_start..payload_end].len() as word32,
-                nonce.as_ptr(),
-                nonce.len() as word32,
-                auth_tag.as_mut_ptr(),
-                auth_tag.len() as word32,
-                aad.as_ptr(),
-                aad.len() as word32,
-            )
-        };
-        check_if_zero(ret).unwrap();
 
-        payload.extend_from_slice(&auth_tag);
+        // We encrypt the message in place, right after the explicit nonce
+        // (8 bytes), using the whole nonce, and the tag goes right after it.
+        let payload_start = GCM_NONCE_LENGTH - 4;
+        let (message, auth_tag) = payload.as_mut()[payload_start..].split_at_mut(m.payload.len());
+        self.context
+            .seal_in_place(&nonce, &aad, message, auth_tag)
+            .map_err(|_| rustls::Error::EncryptError)?;
 
         Ok(OutboundOpaqueMessage::new(m.typ, m.version, payload))
     }
@@ -173,62 +131,32 @@ impl MessageDecrypter for WCTls12Decrypter {
     ) -> Result<InboundPlainMessage<'a>, rustls::Error> {
         let payload = &mut m.payload;
         let payload_len = payload.len();
+        let payload_start = GCM_NONCE_LENGTH - 4;
+
+        if payload_len < payload_start + GCM_TAG_LENGTH {
+            return Err(rustls::Error::DecryptError);
+        }
+
+        let payload_end = payload_len - GCM_TAG_LENGTH;
 
         // First we copy the implicit nonce followed by copying
         // the explicit, both from the slice.
         let mut nonce = [0u8; GCM_NONCE_LENGTH];
         nonce[..(GCM_NONCE_LENGTH - 8)].copy_from_slice(self.implicit_iv.as_ref());
-        nonce[(GCM_NONCE_LENGTH - 8)..].copy_from_slice(&payload[..(GCM_NONCE_LENGTH - 4)]);
-
-        let mut auth_tag = [0u8; GCM_TAG_LENGTH];
-        auth_tag.copy_from_slice(&payload[payload_len - GCM_TAG_LENGTH..]);
-        let aad = make_tls12_aad(
-            seq,
-            m.typ,
-            m.version,
-            payload_len - GCM_TAG_LENGTH - (GCM_NONCE_LENGTH - 4),
-        );
-        let mut aes_c_type: Aes = unsafe { mem::zeroed() };
-        let aes_object = unsafe { AesObject::from_ptr(&mut aes_c_type) };
-        let mut ret;
-
-        ret = unsafe { wc_AesInit(aes_object.as_ptr(), ptr::null_mut(), INVALID_DEVID) };
-        check_if_zero(ret).unwrap();
+        nonce[(GCM_NONCE_LENGTH - 8)..].copy_from_slice(&payload[..payload_start]);
 
-        ret = unsafe {
-            wc_AesGcmSetKey(
-                aes_object.as_ptr(),
-                self.key.as_ptr(),
-                self.key.len() as word32,
-            )
-        };
-        check_if_zero(ret).unwrap();
+        let aad = make_tls12_aad(seq, m.typ, m.version, payload_end - payload_start);
 
         // Finally, we have everything to decrypt the message
-        // from the payload.
-        let payload_start = GCM_NONCE_LENGTH - 4;
-        let payload_end = payload_len - GCM_TAG_LENGTH;
-        ret = unsafe {
-            wc_AesGcmDecrypt(
-                aes_object.as_ptr(),
-                payload[payload_start..payload_end].as_mut_ptr(),
-                payload[payload_start..payload_end].as_ptr(),
-                payload[payload_start..payload_end]
-                    .len()
-                    .try_into()
-                    .unwrap(),
-                nonce.as_ptr(),
-                nonce.len() as word32,
-                auth_tag.as_ptr(),
-                auth_tag.len() as word32,
-                aad.as_ptr(),
-                aad.len() as word32,
-            )
-        };
-        check_if_zero(ret).unwrap();
+        // from the payload, the tag being the last 16 bytes of it.
+        let (message, auth_tag) =
+            payload[payload_start..].split_at_mut(payload_end - payload_start);
+        self.context
+            .open_in_place(&nonce, &aad, message, auth_tag)
+            .map_err(|_| rustls::Error::DecryptError)?;
 
-        payload.copy_within(payload_start..(payload_len - GCM_TAG_LENGTH), 0);
-        payload.truncate(payload_len - ((payload_start) + GCM_TAG_LENGTH));
+        payload.copy_within(payload_start..payload_end, 0);
+        payload.truncate(payload_end - payload_start);
 
         Ok(m.into_plain_message())
     }
@@ -237,14 +165,14 @@ impl MessageDecrypter for WCTls12Decrypter {
 impl Tls13AeadAlgorithm for Aes128Gcm {
     fn encrypter(&self, key: AeadKey, iv: Iv) -> Box<dyn MessageEncrypter> {
         Box::new(WCTls13Cipher {
-            key: key.as_ref().into(),
+            context: AesGcmContext::new(key.as_ref()),
             iv,
         })
     }
 
     fn decrypter(&self, key: AeadKey, iv: Iv) -> Box<dyn MessageDecrypter> {
         Box::new(WCTls13Cipher {
-            key: key.as_ref().into(),
+            context: AesGcmContext::new(key.as_ref()),
             iv,
         })
     }
@@ -263,7 +191,7 @@ impl Tls13AeadAlgorithm for Aes128Gcm {
 }
 
 pub struct WCTls13Cipher {
-    key: Vec<u8>,
+    context: AesGcmContext,
     iv: Iv,
 }
 
@@ -284,54 +212,19 @@ impl MessageEncrypter for WCTls13Cipher {
         payload.extend_from_chunks(&m.payload);
         payload.extend_from_slice(&m.typ.to_array());
 
+        // Room for the authentication tag at the end of it,
+        // written in place by the encryption.
+        payload.extend_from_slice(&[0u8; GCM_TAG_LENGTH]);
+
         let nonce = Nonce::new(&self.iv, seq);
         let aad = make_tls13_aad(total_len);
-        let mut auth_tag: [u8; GCM_TAG_LENGTH] = unsafe { mem::zeroed() };
-        let mut aes_c_type: Aes = unsafe { mem::zeroed() };
-        let aes_object = unsafe { AesObject::from_ptr(&mut aes_c_type) };
-        let mut ret;
-
-        // Initialize Aes structure.
-        ret = unsafe { wc_AesInit(aes_object.as_ptr(), ptr::null_mut(), INVALID_DEVID) };
-        check_if_zero(ret).unwrap();
-
-        // This function is used to set the key for AES GCM (Galois/Counter Mode).
-        // It initializes an AES object with the given key.
-        ret = unsafe {
-            wc_AesGcmSetKey(
-                aes_object.as_ptr(),
-                self.key.as_ptr(),
-                self.key.len() as word32,
-            )
-        };
-        check_if_zero(ret).unwrap();
-
-        // This function encrypts the input message, held in the buffer in,
-        // and stores the resulting cipher text in the output buffer out.
-        // It requires a new iv (initialization vector) for each call to encrypt.
-        // It also encodes the input authentication vector,
-        // authIn, into the authentication tag, authTag.
-        // Apparently we need to also need to include for the encoding type into the encrypted
-        // payload, hence the + 1 otherwise the rustls returns EoF.
-        ret = unsafe {
-            wc_AesGcmEncrypt(
-                aes_object.as_ptr(),
-                payload.as_mut()[..payload_len + 1].as_mut_ptr(),
-                payload.as_ref()[..payload_len + 1].as_ptr(),
-                payload.as_ref()[..payload_len + 1].len() as word32,
-                nonce.0.as_ptr(),
-                nonce.0.len() as word32,
-                auth_tag.as_mut_ptr(),
-                auth_tag.len() as word32,
-                aad.as_ptr(),
-                aad.len() as word32,
-            )
-        };
-        check_if_zero(ret).unwrap();
 
-        // Finally, we add the authentication tag at the end of it
-        // after the process of encryption is done.
-        payload.extend_from_slice(&auth_tag);
+        // The encoded type is encrypted along with the payload,
+        // hence the + 1, otherwise rustls returns EoF.
+        let (message, auth_tag) = payload.as_mut().split_at_mut(payload_len + 1);
+        self.context
+            .seal_in_place(&nonce.0, &aad, message, auth_tag)
+            .map_err(|_| rustls::Error::EncryptError)?;
 
         Ok(OutboundOpaqueMessage::new(
             ContentType::ApplicationData,
@@ -353,44 +246,20 @@ impl MessageDecrypter for WCTls13Cipher {
         seq: u64,
     ) -> Result<InboundPlainMessage<'a>, rustls::Error> {
         let payload = &mut m.payload;
+        if payload.len() < GCM_TAG_LENGTH {
+            return Err(rustls::Error::DecryptError);
+        }
+
         let nonce = Nonce::new(&self.iv, seq);
         let aad = make_tls13_aad(payload.len());
-        let mut auth_tag = [0u8; GCM_TAG_LENGTH];
         let message_len = payload.len() - GCM_TAG_LENGTH;
-        auth_tag.copy_from_slice(&payload[message_len..]);
-        let mut aes_c_type: Aes = unsafe { mem::zeroed() };
-        let aes_object = unsafe { AesObject::from_ptr(&mut aes_c_type) };
-        let mut ret;
-
-        ret = unsafe { wc_AesInit(aes_object.as_ptr(), ptr::null_mut(), INVALID_DEVID) };
-        check_if_zero(ret).unwrap();
-
-        ret = unsafe {
-            wc_AesGcmSetKey(
-                aes_object.as_ptr(),
-                self.key.as_ptr(),
-                self.key.len() as word32,
-            )
-        };
-        check_if_zero(ret).unwrap();
 
         // Finally, we have everything to decrypt the message
         // from the payload.
-        ret = unsafe {
-            wc_AesGcmDecrypt(
-                aes_object.as_ptr(),
-                payload[..message_len].as_mut_ptr(),
-                payload[..message_len].as_ptr(),
-                payload[..message_len].len().try_into().unwrap(),
-                nonce.0.as_ptr(),
-                nonce.0.len() as word32,
-                auth_tag.as_ptr(),
-                auth_tag.len() as word32,
-                aad.as_ptr(),
-                aad.len() as word32,
-            )
-        };
-        check_if_zero(ret).unwrap();
+        let (message, auth_tag) = payload.split_at_mut(message_len);
+        self.context
+            .open_in_place(&nonce.0, &aad, message, auth_tag)
+            .map_err(|_| rustls::Error::DecryptError)?;
 
         payload.truncate(message_len);
 
@@ -401,6 +270,12 @@ impl MessageDecrypter for WCTls13Cipher {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::error::check_if_zero;
+    use crate::types::*;
+    use core::{mem, ptr};
+    use foreign_types::ForeignType;
+    use hex_literal::hex;
+    use wolfcrypt_rs::*;
     use wycheproof::{aead::TestFlag, TestResult};
 
     #[test]
@@ -622,4 +497,121 @@ mod tests {
             counter
         );
     }
+
+    // Outside of rustls an AeadKey can only be built out of 32 bytes,
+    // so the ciphers are put together by hand with a 16 byte key.
+    // Expected records computed with Python's cryptography.
+    #[test]
+    fn test_records_in_place() {
+        let key: [u8; 16] = core::array::from_fn(|i| i as u8);
+        let implicit_iv = [0xa0, 0xa1, 0xa2, 0xa3];
+        let explicit = [0xb0, 0xb1, 0xb2, 0xb3, 0xb4, 0xb5, 0xb6, 0xb7];
+        let mut iv = [0u8; GCM_NONCE_LENGTH];
+        iv[..4].copy_from_slice(&implicit_iv);
+        iv[4..].copy_from_slice(&explicit);
+
+        let mut tls12_encrypter = WCTls12Encrypter {
+            iv: iv.into(),
+            context: AesGcmContext::new(&key),
+        };
+        let mut tls12_decrypter = WCTls12Decrypter {
+            implicit_iv,
+            context: AesGcmContext::new(&key),
+        };
+        let expected = hex!(
+            "b0b1b2b3b4b5b6b6" "2f0ae3fc3ae893d4a8" "b16c41cdd686487f207f468ddfb44e73"
+        );
+        encrypt_and_decrypt(&mut tls12_encrypter, &mut tls12_decrypter, &expected);
+
+        let iv: [u8; GCM_NONCE_LENGTH] = core::array::from_fn(|i| 0xa0 + i as u8);
+        let mut tls13_encrypter = WCTls13Cipher {
+            context: AesGcmContext::new(&key),
+            iv: Iv::new(iv),
+        };
+        let mut tls13_decrypter = WCTls13Cipher {
+            context: AesGcmContext::new(&key),
+            iv: Iv::new(iv),
+        };
+        let expected = hex!("579f0af6c5cacbac4abc88" "56e3a85ef1dad72274975b993060bf");
+        encrypt_and_decrypt(&mut tls13_encrypter, &mut tls13_decrypter, &expected);
+    }
+
+    fn encrypt_and_decrypt(
+        encrypter: &mut dyn MessageEncrypter,
+        decrypter: &mut dyn MessageDecrypter,
+        expected: &[u8],
+    ) {
+        let plain = OutboundPlainMessage {
+            typ: ContentType::ApplicationData,
+            version: ProtocolVersion::TLSv1_2,
+            payload: b"hello GCM"[..].into(),
+        };
+        let encrypted = encrypter.encrypt(plain, 1).unwrap().encode();
+
+        // Skip the 5 byte record header.
+        assert_eq!(&encrypted[5..], expected);
+
+        let mut record = encrypted[5..].to_vec();
+        let decrypted = decrypter
+            .decrypt(
+                InboundOpaqueMessage::new(
+                    ContentType::ApplicationData,
+                    ProtocolVersion::TLSv1_2,
+                    &mut record,
+                ),
+                1,
+            )
+            .unwrap();
+        assert_eq!(decrypted.typ, ContentType::ApplicationData);
+        assert_eq!(decrypted.payload, b"hello GCM");
+
+        // Flipping a bit in the tag must be caught, not panic.
+        let mut tampered = encrypted[5..].to_vec();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(decrypter
+            .decrypt(
+                InboundOpaqueMessage::new(
+                    ContentType::ApplicationData,
+                    ProtocolVersion::TLSv1_2,
+                    &mut tampered,
+                ),
+                1,
+            )
+            .is_err());
+
+        // Too short to even hold the tag.
+        let mut truncated = encrypted[5..12].to_vec();
+        assert!(decrypter
+            .decrypt(
+                InboundOpaqueMessage::new(
+                    ContentType::ApplicationData,
+                    ProtocolVersion::TLSv1_2,
+                    &mut truncated,
+                ),
+                1,
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_tls12_extract_keys() {
+        let implicit = [0xa0, 0xa1, 0xa2, 0xa3];
+        let explicit = [0xb0, 0xb1, 0xb2, 0xb3, 0xb4, 0xb5, 0xb6, 0xb7];
+
+        let secrets = Tls12AeadAlgorithm::extract_keys(
+            &Aes128Gcm,
+            AeadKey::from([0x42; 32]),
+            &implicit,
+            &explicit,
+        )
+        .unwrap();
+
+        match secrets {
+            ConnectionTrafficSecrets::Aes128Gcm { iv, .. } => {
+                assert_eq!(&iv.as_ref()[..4], &implicit);
+                assert_eq!(&iv.as_ref()[4..], &explicit);
+            }
+            _ => panic!("unexpected secrets"),
+        }
+    }
 }
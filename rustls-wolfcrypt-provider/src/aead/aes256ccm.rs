@@ -0,0 +1,158 @@
+use crate::aead::aes128ccm::{
+    WCTls12Decrypter, WCTls12Encrypter, CCM_8_TAG_LENGTH, CCM_TAG_LENGTH,
+};
+use alloc::boxed::Box;
+use rustls::crypto::cipher::{
+    AeadKey, KeyBlockShape, MessageDecrypter, MessageEncrypter, Tls12AeadAlgorithm,
+    UnsupportedOperationError,
+};
+use rustls::ConnectionTrafficSecrets;
+
+/// AES-256-CCM with a 16 byte tag (TLS_ECDHE_ECDSA_WITH_AES_256_CCM).
+/// TLS 1.3 doesn't define any AES-256-CCM suite, so this is TLS 1.2 only.
+pub struct Aes256Ccm;
+
+/// AES-256-CCM with an 8 byte tag (TLS_ECDHE_ECDSA_WITH_AES_256_CCM_8).
+pub struct Aes256Ccm8;
+
+impl Tls12AeadAlgorithm for Aes256Ccm {
+    fn encrypter(&self, key: AeadKey, iv: &[u8], extra: &[u8]) -> Box<dyn MessageEncrypter> {
+        Box::new(WCTls12Encrypter::new(
+            key.as_ref(),
+            iv,
+            extra,
+            CCM_TAG_LENGTH,
+        ))
+    }
+
+    fn decrypter(&self, key: AeadKey, iv: &[u8]) -> Box<dyn MessageDecrypter> {
+        Box::new(WCTls12Decrypter::new(key.as_ref(), iv, CCM_TAG_LENGTH))
+    }
+
+    fn key_block_shape(&self) -> KeyBlockShape {
+        KeyBlockShape {
+            enc_key_len: 32,
+            fixed_iv_len: 4,
+            explicit_nonce_len: 8,
+        }
+    }
+
+    // rustls has no ConnectionTrafficSecrets variant for CCM.
+    fn extract_keys(
+        &self,
+        _key: AeadKey,
+        _iv: &[u8],
+        _explicit: &[u8],
+    ) -> Result<ConnectionTrafficSecrets, UnsupportedOperationError> {
+        Err(UnsupportedOperationError)
+    }
+}
+
+impl Tls12AeadAlgorithm for Aes256Ccm8 {
+    fn encrypter(&self, key: AeadKey, iv: &[u8], extra: &[u8]) -> Box<dyn MessageEncrypter> {
+        Box::new(WCTls12Encrypter::new(
+            key.as_ref(),
+            iv,
+            extra,
+            CCM_8_TAG_LENGTH,
+        ))
+    }
+
+    fn decrypter(&self, key: AeadKey, iv: &[u8]) -> Box<dyn MessageDecrypter> {
+        Box::new(WCTls12Decrypter::new(key.as_ref(), iv, CCM_8_TAG_LENGTH))
+    }
+
+    fn key_block_shape(&self) -> KeyBlockShape {
+        KeyBlockShape {
+            enc_key_len: 32,
+            fixed_iv_len: 4,
+            explicit_nonce_len: 8,
+        }
+    }
+
+    fn extract_keys(
+        &self,
+        _key: AeadKey,
+        _iv: &[u8],
+        _explicit: &[u8],
+    ) -> Result<ConnectionTrafficSecrets, UnsupportedOperationError> {
+        Err(UnsupportedOperationError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use rustls::crypto::cipher::{InboundOpaqueMessage, OutboundPlainMessage};
+    use rustls::{ContentType, ProtocolVersion};
+
+    const KEY: [u8; 32] = [
+        0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e,
+        0x0f, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d,
+        0x1e, 0x1f,
+    ];
+
+    fn encrypt_and_decrypt_tls12(alg: &dyn Tls12AeadAlgorithm, expected: &[u8]) {
+        let implicit_iv = [0xa0, 0xa1, 0xa2, 0xa3];
+        let explicit = [0xb0, 0xb1, 0xb2, 0xb3, 0xb4, 0xb5, 0xb6, 0xb7];
+        let mut encrypter = alg.encrypter(AeadKey::from(KEY), &implicit_iv, &explicit);
+        let mut decrypter = alg.decrypter(AeadKey::from(KEY), &implicit_iv);
+
+        let plain = OutboundPlainMessage {
+            typ: ContentType::ApplicationData,
+            version: ProtocolVersion::TLSv1_2,
+            payload: b"hello CCM"[..].into(),
+        };
+        let encrypted = encrypter.encrypt(plain, 1).unwrap().encode();
+
+        assert_eq!(&encrypted[5..], expected);
+
+        let mut record = encrypted[5..].to_vec();
+        let decrypted = decrypter
+            .decrypt(
+                InboundOpaqueMessage::new(
+                    ContentType::ApplicationData,
+                    ProtocolVersion::TLSv1_2,
+                    &mut record,
+                ),
+                1,
+            )
+            .unwrap();
+        assert_eq!(decrypted.payload, b"hello CCM");
+
+        let mut tampered = encrypted[5..].to_vec();
+        tampered[8] ^= 1;
+        assert!(decrypter
+            .decrypt(
+                InboundOpaqueMessage::new(
+                    ContentType::ApplicationData,
+                    ProtocolVersion::TLSv1_2,
+                    &mut tampered,
+                ),
+                1,
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_aes256ccm_tls12_record() {
+        let expected = vec![
+            0xb0, 0xb1, 0xb2, 0xb3, 0xb4, 0xb5, 0xb6, 0xb6, 0x51, 0x5f, 0x77, 0x52, 0x64, 0xcb,
+            0xa0, 0xad, 0xa5, 0x9c, 0x67, 0x59, 0x59, 0x34, 0x97, 0xa7, 0xb3, 0x8e, 0x6b, 0xa3,
+            0x94, 0x42, 0xbd, 0x0e, 0x84,
+        ];
+
+        encrypt_and_decrypt_tls12(&Aes256Ccm, &expected);
+    }
+
+    #[test]
+    fn test_aes256ccm8_tls12_record() {
+        let expected = vec![
+            0xb0, 0xb1, 0xb2, 0xb3, 0xb4, 0xb5, 0xb6, 0xb6, 0x51, 0x5f, 0x77, 0x52, 0x64, 0xcb,
+            0xa0, 0xad, 0xa5, 0x2a, 0x4f, 0x0c, 0xbf, 0x45, 0xbd, 0xfd, 0x18,
+        ];
+
+        encrypt_and_decrypt_tls12(&Aes256Ccm8, &expected);
+    }
+}
@@ -1,8 +1,6 @@
+use crate::aead::context::ChaCha20Poly1305Context;
 use alloc::boxed::Box;
-use alloc::vec;
-use alloc::vec::Vec;
 use chacha20poly1305::KeySizeUser;
-use core::mem;
 use rustls::crypto::cipher::{
     make_tls12_aad, make_tls13_aad, AeadKey, InboundOpaqueMessage, InboundPlainMessage, Iv,
     KeyBlockShape, MessageDecrypter, MessageEncrypter, Nonce, OutboundOpaqueMessage,
@@ -10,9 +8,6 @@ use rustls::crypto::cipher::{
     UnsupportedOperationError, NONCE_LEN,
 };
 use rustls::{ConnectionTrafficSecrets, ContentType, ProtocolVersion};
-use wolfcrypt_rs::*;
-
-use crate::error::check_if_zero;
 
 const CHACHAPOLY1305_OVERHEAD: usize = 16;
 
@@ -20,21 +15,15 @@ pub struct Chacha20Poly1305;
 
 impl Tls12AeadAlgorithm for Chacha20Poly1305 {
     fn encrypter(&self, key: AeadKey, iv: &[u8], _: &[u8]) -> Box<dyn MessageEncrypter> {
-        let mut key_as_vec = vec![0u8; 32];
-        key_as_vec.copy_from_slice(key.as_ref());
-
         Box::new(WCTls12Cipher {
-            key: key_as_vec,
+            context: ChaCha20Poly1305Context::new(key.as_ref()),
             iv: Iv::copy(iv),
         })
     }
 
     fn decrypter(&self, key: AeadKey, iv: &[u8]) -> Box<dyn MessageDecrypter> {
-        let mut key_as_vec = vec![0u8; 32];
-        key_as_vec.copy_from_slice(key.as_ref());
-
         Box::new(WCTls12Cipher {
-            key: key_as_vec,
+            context: ChaCha20Poly1305Context::new(key.as_ref()),
             iv: Iv::copy(iv),
         })
     }
@@ -63,7 +52,7 @@ impl Tls12AeadAlgorithm for Chacha20Poly1305 {
 }
 
 pub struct WCTls12Cipher {
-    key: Vec<u8>,
+    context: ChaCha20Poly1305Context,
     iv: Iv,
 }
 
@@ -82,43 +71,21 @@ impl MessageEncrypter for WCTls12Cipher {
         // We copy the payload provided into the PrefixedPayload variable
         // just created using extend_from_chunks, since the payload
         // is contained inside the enum OutboundChunks.
+        // Followed by the room for the authentication tag.
         payload.extend_from_chunks(&m.payload);
+        payload.extend_from_slice(&[0u8; CHACHAPOLY1305_OVERHEAD]);
 
         let nonce = Nonce::new(&self.iv, seq);
         let aad = make_tls12_aad(seq, m.typ, m.version, m.payload.len());
-        let mut encrypted = vec![0u8; m.payload.len()];
-        let mut auth_tag: [u8; CHACHA20_POLY1305_AEAD_AUTHTAG_SIZE as usize] =
-            unsafe { mem::zeroed() };
-        let payload_raw = payload.as_ref();
-
-        //  This function encrypts an input message, inPlaintext,
-        //  using the ChaCha20 stream cipher, into the output buffer, outCiphertext.
-        //  It also performs Poly-1305 authentication (on the cipher text),
-        //  and stores the generated authentication tag in the output buffer, outAuthTag.
-        let ret = unsafe {
-            wc_ChaCha20Poly1305_Encrypt(
-                self.key.as_ptr(),
-                nonce.0.as_ptr(),
-                aad.as_ptr(),
-                aad.len() as word32,
-                payload_raw.as_ptr(),
-                m.payload.len() as word32,
-                encrypted.as_mut_ptr(),
-                auth_tag.as_mut_ptr(),
-            )
-        };
-        check_if_zero(ret).unwrap();
-
-        let mut output = PrefixedPayload::with_capacity(total_len);
-
-        // Finally we copy the encrypted payload into a PrefixedPayload
-        // struct, extending it from a slice (encrypted is a Vec<u8>)...
-        output.extend_from_slice(encrypted.as_slice());
 
-        // ...and add at the end of it the authentication tag.
-        output.extend_from_slice(&auth_tag);
+        // The payload is encrypted in place with the ChaCha20 stream cipher,
+        // and the Poly1305 tag (on the cipher text) is written right after it.
+        let (message, auth_tag) = payload.as_mut().split_at_mut(m.payload.len());
+        self.context
+            .seal_in_place(&nonce.0, &aad, message, auth_tag)
+            .map_err(|_| rustls::Error::EncryptError)?;
 
-        Ok(OutboundOpaqueMessage::new(m.typ, m.version, output))
+        Ok(OutboundOpaqueMessage::new(m.typ, m.version, payload))
     }
 
     fn encrypted_payload_len(&self, payload_len: usize) -> usize {
@@ -133,34 +100,22 @@ impl MessageDecrypter for WCTls12Cipher {
         seq: u64,
     ) -> Result<InboundPlainMessage<'a>, rustls::Error> {
         let payload = &mut m.payload;
+        if payload.len() < CHACHAPOLY1305_OVERHEAD {
+            return Err(rustls::Error::DecryptError);
+        }
 
         // We substract the tag, so this len will only consider
         // the message that we are trying to decrypt.
         let message_len = payload.len() - CHACHAPOLY1305_OVERHEAD;
         let nonce = Nonce::new(&self.iv, seq);
         let aad = make_tls12_aad(seq, m.typ, m.version, message_len);
-        let mut auth_tag = [0u8; CHACHAPOLY1305_OVERHEAD];
-        auth_tag.copy_from_slice(&payload[message_len..]);
-
-        // This function decrypts input ciphertext, inCiphertext,
-        // using the ChaCha20 stream cipher, into the output buffer, outPlaintext.
-        // It also performs Poly-1305 authentication, comparing the given inAuthTag
-        // to an authentication generated with the inAAD (arbitrary length additional authentication data).
-        // Note: If the generated authentication tag does not match the supplied
-        // authentication tag, the text is not decrypted.
-        let ret = unsafe {
-            wc_ChaCha20Poly1305_Decrypt(
-                self.key.as_ptr(),
-                nonce.0.as_ptr(),
-                aad.as_ptr(),
-                aad.len() as word32,
-                payload[..message_len].as_ptr(), // we decrypt only the payload, we don't include the tag.
-                message_len as word32,
-                auth_tag.as_ptr(),
-                payload[..message_len].as_mut_ptr(),
-            )
-        };
-        check_if_zero(ret).unwrap();
+
+        // The Poly1305 tag is checked first, and the text
+        // is only decrypted (in place) if it matches.
+        let (message, auth_tag) = payload.split_at_mut(message_len);
+        self.context
+            .open_in_place(&nonce.0, &aad, message, auth_tag)
+            .map_err(|_| rustls::Error::DecryptError)?;
 
         // We extract the final result...
         payload.truncate(message_len);
@@ -175,21 +130,15 @@ impl MessageDecrypter for WCTls12Cipher {
 
 impl Tls13AeadAlgorithm for Chacha20Poly1305 {
     fn encrypter(&self, key: AeadKey, iv: Iv) -> Box<dyn MessageEncrypter> {
-        let mut key_as_array = [0u8; 32];
-        key_as_array[..32].copy_from_slice(key.as_ref());
-
         Box::new(WCTls13Cipher {
-            key: key_as_array,
+            context: ChaCha20Poly1305Context::new(key.as_ref()),
             iv,
         })
     }
 
     fn decrypter(&self, key: AeadKey, iv: Iv) -> Box<dyn MessageDecrypter> {
-        let mut key_as_array = [0u8; 32];
-        key_as_array[..32].copy_from_slice(key.as_ref());
-
         Box::new(WCTls13Cipher {
-            key: key_as_array,
+            context: ChaCha20Poly1305Context::new(key.as_ref()),
             iv,
         })
     }
@@ -208,7 +157,7 @@ impl Tls13AeadAlgorithm for Chacha20Poly1305 {
 }
 
 pub struct WCTls13Cipher {
-    key: [u8; 32],
+    context: ChaCha20Poly1305Context,
     iv: Iv,
 }
 
@@ -218,7 +167,8 @@ impl MessageEncrypter for WCTls13Cipher {
         m: OutboundPlainMessage,
         seq: u64,
     ) -> Result<OutboundOpaqueMessage, rustls::Error> {
-        let total_len = self.encrypted_payload_len(m.payload.len());
+        let payload_len = m.payload.len();
+        let total_len = self.encrypted_payload_len(payload_len);
         let mut payload = PrefixedPayload::with_capacity(total_len);
 
         // We copy the payload provided into the PrefixedPayload variable
@@ -228,34 +178,19 @@ impl MessageEncrypter for WCTls13Cipher {
         payload.extend_from_chunks(&m.payload);
         payload.extend_from_slice(&m.typ.to_array());
 
+        // Room for the authentication tag at the end of it,
+        // written in place by the encryption.
+        payload.extend_from_slice(&[0u8; CHACHAPOLY1305_OVERHEAD]);
+
         let nonce = Nonce::new(&self.iv, seq);
         let aad = make_tls13_aad(total_len);
-        let mut auth_tag: [u8; CHACHA20_POLY1305_AEAD_AUTHTAG_SIZE as usize] =
-            unsafe { mem::zeroed() };
 
-        // This function encrypts an input message, inPlaintext,
-        // using the ChaCha20 stream cipher, into the output buffer, outCiphertext.
-        // It also performs Poly-1305 authentication (on the cipher text),
-        // and stores the generated authentication tag in the output buffer, outAuthTag.
         // We need to also need to include for the encoding type, apparently, hence the + 1
         // otherwise the rustls returns EoF.
-        let ret = unsafe {
-            wc_ChaCha20Poly1305_Encrypt(
-                self.key.as_ptr(),
-                nonce.0.as_ptr(),
-                aad.as_ptr(),
-                aad.len() as word32,
-                payload.as_ref()[..m.payload.len() + 1].as_ptr(),
-                (m.payload.len() + 1) as word32,
-                payload.as_mut()[..m.payload.len() + 1].as_mut_ptr(),
-                auth_tag.as_mut_ptr(),
-            )
-        };
-        check_if_zero(ret).unwrap();
-
-        // Finally, we add the authentication tag at the end of it
-        // after the process of encryption is done.
-        payload.extend_from_slice(&auth_tag);
+        let (message, auth_tag) = payload.as_mut().split_at_mut(payload_len + 1);
+        self.context
+            .seal_in_place(&nonce.0, &aad, message, auth_tag)
+            .map_err(|_| rustls::Error::EncryptError)?;
 
         Ok(OutboundOpaqueMessage::new(
             ContentType::ApplicationData,
@@ -276,33 +211,20 @@ impl MessageDecrypter for WCTls13Cipher {
         seq: u64,
     ) -> Result<InboundPlainMessage<'a>, rustls::Error> {
         let payload = &mut m.payload;
+        if payload.len() < CHACHAPOLY1305_OVERHEAD {
+            return Err(rustls::Error::DecryptError);
+        }
+
         let nonce = Nonce::new(&self.iv, seq);
         let aad = make_tls13_aad(payload.len());
-        let mut auth_tag = [0u8; CHACHAPOLY1305_OVERHEAD];
         let message_len = payload.len() - CHACHAPOLY1305_OVERHEAD;
-        auth_tag.copy_from_slice(&payload[message_len..]);
-
-        // This function decrypts input ciphertext, inCiphertext,
-        // using the ChaCha20 stream cipher, into the output buffer, outPlaintext.
-        // It also performs Poly-1305 authentication, comparing the given inAuthTag
-        // to an authentication generated with the inAAD (arbitrary length additional authentication data).
-        // Note: If the generated authentication tag does not match the supplied
-        // authentication tag, the text is not decrypted.
-        let ret = unsafe {
-            wc_ChaCha20Poly1305_Decrypt(
-                self.key.as_ptr(),
-                nonce.0.as_ptr(),
-                aad.as_ptr(),
-                aad.len() as word32,
-                // [..message_len] since we want to exclude the
-                // the auth_tag.
-                payload[..message_len].as_ptr(),
-                message_len as word32,
-                auth_tag.as_ptr(),
-                payload[..message_len].as_mut_ptr(),
-            )
-        };
-        check_if_zero(ret).unwrap();
+
+        // Split at message_len since we want to exclude the
+        // the auth_tag from what gets decrypted.
+        let (message, auth_tag) = payload.split_at_mut(message_len);
+        self.context
+            .open_in_place(&nonce.0, &aad, message, auth_tag)
+            .map_err(|_| rustls::Error::DecryptError)?;
 
         // We extract the final result...
         payload.truncate(message_len);
@@ -314,6 +236,9 @@ impl MessageDecrypter for WCTls13Cipher {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::error::check_if_zero;
+    use core::mem;
+    use wolfcrypt_rs::*;
     use wycheproof::{aead::TestFlag, TestResult};
 
     #[test]
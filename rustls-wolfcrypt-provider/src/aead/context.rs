@@ -0,0 +1,554 @@
+use crate::error::{check_if_zero, WCError, WCResult};
+use alloc::boxed::Box;
+use core::cell::UnsafeCell;
+use core::hint;
+use core::mem;
+use core::ops::{Deref, DerefMut};
+use core::ptr;
+use core::sync::atomic::{AtomicBool, Ordering};
+use wolfcrypt_rs::*;
+
+const POLY1305_KEY_LENGTH: usize = 32;
+const POLY1305_TAG_LENGTH: usize = 16;
+pub(crate) const AES_BLOCK_LENGTH: usize = 16;
+
+/// An `Aes` living on the heap, so that the key schedule is computed once,
+/// when the traffic key is installed, and not again for every record.
+/// It's released with `wc_AesFree` (and wiped) on drop.
+struct HeapAes(Box<AlignedAes>);
+
+/// wolfSSL's AES-NI/AVX code (the `aesni` and `intelasm` features) loads
+/// the key schedule with aligned instructions; the C struct is declared
+/// ALIGN16 for that, which is enforced here whatever bindgen made of it.
+#[repr(C, align(16))]
+struct AlignedAes(Aes);
+
+// wolfCrypt keeps a heap hint and a device context pointer in there,
+// both unused (null) here; the struct is only ever touched through &mut.
+unsafe impl Send for HeapAes {}
+unsafe impl Sync for HeapAes {}
+
+impl HeapAes {
+    fn new() -> Self {
+        let mut aes: Box<AlignedAes> = Box::new(unsafe { mem::zeroed() });
+
+        // Initialize Aes structure.
+        let ret = unsafe { wc_AesInit(&mut aes.0, ptr::null_mut(), INVALID_DEVID) };
+        check_if_zero(ret).unwrap();
+
+        HeapAes(aes)
+    }
+
+    fn as_mut_ptr(&mut self) -> *mut Aes {
+        &mut self.0 .0
+    }
+}
+
+impl Drop for HeapAes {
+    fn drop(&mut self) {
+        unsafe {
+            wc_AesFree(self.as_mut_ptr());
+            ptr::write_volatile(self.as_mut_ptr(), mem::zeroed());
+        }
+    }
+}
+
+/// AES-GCM, keyed once for the lifetime of the traffic key.
+pub(crate) struct AesGcmContext(HeapAes);
+
+impl AesGcmContext {
+    pub(crate) fn new(key: &[u8]) -> Self {
+        let mut aes = HeapAes::new();
+
+        // This function is used to set the key for AES GCM (Galois/Counter Mode).
+        // It initializes an AES object with the given key.
+        let ret = unsafe { wc_AesGcmSetKey(aes.as_mut_ptr(), key.as_ptr(), key.len() as word32) };
+        check_if_zero(ret).unwrap();
+
+        AesGcmContext(aes)
+    }
+
+    /// Encrypts `in_out` in place, and writes the authentication tag into `tag`.
+    pub(crate) fn seal_in_place(
+        &mut self,
+        nonce: &[u8],
+        aad: &[u8],
+        in_out: &mut [u8],
+        tag: &mut [u8],
+    ) -> WCResult {
+        let data = in_out.as_mut_ptr();
+
+        let ret = unsafe {
+            wc_AesGcmEncrypt(
+                self.0.as_mut_ptr(),
+                data,
+                data,
+                in_out.len() as word32,
+                nonce.as_ptr(),
+                nonce.len() as word32,
+                tag.as_mut_ptr(),
+                tag.len() as word32,
+                aad.as_ptr(),
+                aad.len() as word32,
+            )
+        };
+
+        check_if_zero(ret)
+    }
+
+    /// Checks `tag` and decrypts `in_out` in place.
+    pub(crate) fn open_in_place(
+        &mut self,
+        nonce: &[u8],
+        aad: &[u8],
+        in_out: &mut [u8],
+        tag: &[u8],
+    ) -> WCResult {
+        let data = in_out.as_mut_ptr();
+
+        let ret = unsafe {
+            wc_AesGcmDecrypt(
+                self.0.as_mut_ptr(),
+                data,
+                data,
+                in_out.len() as word32,
+                nonce.as_ptr(),
+                nonce.len() as word32,
+                tag.as_ptr(),
+                tag.len() as word32,
+                aad.as_ptr(),
+                aad.len() as word32,
+            )
+        };
+
+        check_if_zero(ret)
+    }
+}
+
+/// AES-CCM, keyed once for the lifetime of the traffic key.
+/// The tag length is whatever the `tag` slices are (16 or 8 bytes).
+pub(crate) struct AesCcmContext(HeapAes);
+
+impl AesCcmContext {
+    pub(crate) fn new(key: &[u8]) -> Self {
+        let mut aes = HeapAes::new();
+
+        let ret = unsafe { wc_AesCcmSetKey(aes.as_mut_ptr(), key.as_ptr(), key.len() as word32) };
+        check_if_zero(ret).unwrap();
+
+        AesCcmContext(aes)
+    }
+
+    /// Encrypts `in_out` in place, and writes the authentication tag into `tag`.
+    pub(crate) fn seal_in_place(
+        &mut self,
+        nonce: &[u8],
+        aad: &[u8],
+        in_out: &mut [u8],
+        tag: &mut [u8],
+    ) -> WCResult {
+        let data = in_out.as_mut_ptr();
+
+        let ret = unsafe {
+            wc_AesCcmEncrypt(
+                self.0.as_mut_ptr(),
+                data,
+                data,
+                in_out.len() as word32,
+                nonce.as_ptr(),
+                nonce.len() as word32,
+                tag.as_mut_ptr(),
+                tag.len() as word32,
+                aad.as_ptr(),
+                aad.len() as word32,
+            )
+        };
+
+        check_if_zero(ret)
+    }
+
+    /// Checks `tag` and decrypts `in_out` in place.
+    pub(crate) fn open_in_place(
+        &mut self,
+        nonce: &[u8],
+        aad: &[u8],
+        in_out: &mut [u8],
+        tag: &[u8],
+    ) -> WCResult {
+        let data = in_out.as_mut_ptr();
+
+        let ret = unsafe {
+            wc_AesCcmDecrypt(
+                self.0.as_mut_ptr(),
+                data,
+                data,
+                in_out.len() as word32,
+                nonce.as_ptr(),
+                nonce.len() as word32,
+                tag.as_ptr(),
+                tag.len() as word32,
+                aad.as_ptr(),
+                aad.len() as word32,
+            )
+        };
+
+        check_if_zero(ret)
+    }
+}
+
+/// A single AES block encryption (ECB), keyed once for the lifetime of the key;
+/// that's what QUIC header protection uses (RFC 9001, 5.4.3).
+pub(crate) struct AesBlockContext(HeapAes);
+
+impl AesBlockContext {
+    pub(crate) fn new(key: &[u8]) -> Self {
+        let mut aes = HeapAes::new();
+
+        let ret = unsafe {
+            wc_AesSetKey(
+                aes.as_mut_ptr(),
+                key.as_ptr(),
+                key.len() as word32,
+                ptr::null(),
+                AES_ENCRYPTION as i32,
+            )
+        };
+        check_if_zero(ret).unwrap();
+
+        AesBlockContext(aes)
+    }
+
+    /// Encrypts the single block `block` in place.
+    pub(crate) fn encrypt_block(&mut self, block: &mut [u8; AES_BLOCK_LENGTH]) -> WCResult {
+        let data = block.as_mut_ptr();
+
+        let ret = unsafe { wc_AesEncryptDirect(self.0.as_mut_ptr(), data, data) };
+
+        check_if_zero(ret)
+    }
+}
+
+/// ChaCha20 (RFC 8439, 2.4), keyed once for the lifetime of the key;
+/// the nonce and block counter are given on every call.
+pub(crate) struct ChaCha20Context(Box<ChaCha>);
+
+// Plain data, only ever touched through &mut.
+unsafe impl Send for ChaCha20Context {}
+unsafe impl Sync for ChaCha20Context {}
+
+impl ChaCha20Context {
+    pub(crate) fn new(key: &[u8]) -> Self {
+        let mut chacha: Box<ChaCha> = Box::new(unsafe { mem::zeroed() });
+
+        let ret = unsafe { wc_Chacha_SetKey(&mut *chacha, key.as_ptr(), key.len() as word32) };
+        check_if_zero(ret).unwrap();
+
+        ChaCha20Context(chacha)
+    }
+
+    /// XORs `in_out` with the key stream starting from block `counter`.
+    pub(crate) fn apply_keystream(
+        &mut self,
+        nonce: &[u8],
+        counter: u32,
+        in_out: &mut [u8],
+    ) -> WCResult {
+        let data = in_out.as_mut_ptr();
+        let mut ret;
+
+        ret = unsafe { wc_Chacha_SetIV(&mut *self.0, nonce.as_ptr(), counter) };
+        check_if_zero(ret)?;
+
+        ret = unsafe { wc_Chacha_Process(&mut *self.0, data, data, in_out.len() as word32) };
+        check_if_zero(ret)
+    }
+}
+
+impl Drop for ChaCha20Context {
+    fn drop(&mut self) {
+        unsafe { ptr::write_volatile(&mut *self.0, mem::zeroed()) };
+    }
+}
+
+/// ChaCha20-Poly1305 (RFC 8439), keyed once for the lifetime of the traffic key.
+///
+/// wolfCrypt's one shot ChaCha20-Poly1305 functions take the raw key and set up
+/// the cipher again for every call, so the AEAD is put together here instead,
+/// out of a ChaCha20 context that keeps the key and only gets a new nonce per record.
+pub(crate) struct ChaCha20Poly1305Context(ChaCha20Context);
+
+impl ChaCha20Poly1305Context {
+    pub(crate) fn new(key: &[u8]) -> Self {
+        ChaCha20Poly1305Context(ChaCha20Context::new(key))
+    }
+
+    /// Encrypts `in_out` in place, and writes the authentication tag into `tag`.
+    pub(crate) fn seal_in_place(
+        &mut self,
+        nonce: &[u8],
+        aad: &[u8],
+        in_out: &mut [u8],
+        tag: &mut [u8],
+    ) -> WCResult {
+        let poly1305_key = self.poly1305_key(nonce)?;
+
+        // RFC 8439, 2.8: the data itself is XORed with
+        // the key stream starting from block 1.
+        self.0.apply_keystream(nonce, 1, in_out)?;
+
+        // The tag is computed over the cipher text.
+        poly1305_mac(&poly1305_key, aad, in_out, tag)
+    }
+
+    /// Checks `tag` and decrypts `in_out` in place;
+    /// nothing is decrypted if the tag doesn't match.
+    pub(crate) fn open_in_place(
+        &mut self,
+        nonce: &[u8],
+        aad: &[u8],
+        in_out: &mut [u8],
+        tag: &[u8],
+    ) -> WCResult {
+        let poly1305_key = self.poly1305_key(nonce)?;
+        let mut expected = [0u8; POLY1305_TAG_LENGTH];
+
+        poly1305_mac(&poly1305_key, aad, in_out, &mut expected)?;
+
+        // Constant time comparison, we don't want to leak
+        // how much of the tag was right.
+        let diff = match tag.len() == expected.len() {
+            true => expected
+                .iter()
+                .zip(tag)
+                .fold(0u8, |acc, (a, b)| acc | (a ^ b)),
+            false => 1,
+        };
+        if diff != 0 {
+            return Err(WCError::Authentication);
+        }
+
+        self.0.apply_keystream(nonce, 1, in_out)
+    }
+
+    /// RFC 8439, 2.6: the one time Poly1305 key is the
+    /// beginning of the key stream for block 0.
+    fn poly1305_key(&mut self, nonce: &[u8]) -> Result<[u8; POLY1305_KEY_LENGTH], WCError> {
+        let mut poly1305_key = [0u8; POLY1305_KEY_LENGTH];
+
+        self.0.apply_keystream(nonce, 0, &mut poly1305_key)?;
+
+        Ok(poly1305_key)
+    }
+}
+
+/// Either of the AEADs that both rustls' QUIC and ticket keys may be using.
+pub(crate) enum AeadContext {
+    AesGcm(AesGcmContext),
+    ChaCha20Poly1305(ChaCha20Poly1305Context),
+}
+
+impl AeadContext {
+    /// Encrypts `in_out` in place, and writes the authentication tag into `tag`.
+    pub(crate) fn seal_in_place(
+        &mut self,
+        nonce: &[u8],
+        aad: &[u8],
+        in_out: &mut [u8],
+        tag: &mut [u8],
+    ) -> WCResult {
+        match self {
+            AeadContext::AesGcm(context) => context.seal_in_place(nonce, aad, in_out, tag),
+            AeadContext::ChaCha20Poly1305(context) => {
+                context.seal_in_place(nonce, aad, in_out, tag)
+            }
+        }
+    }
+
+    /// Checks `tag` and decrypts `in_out` in place.
+    pub(crate) fn open_in_place(
+        &mut self,
+        nonce: &[u8],
+        aad: &[u8],
+        in_out: &mut [u8],
+        tag: &[u8],
+    ) -> WCResult {
+        match self {
+            AeadContext::AesGcm(context) => context.open_in_place(nonce, aad, in_out, tag),
+            AeadContext::ChaCha20Poly1305(context) => {
+                context.open_in_place(nonce, aad, in_out, tag)
+            }
+        }
+    }
+}
+
+/// Some rustls traits (QUIC keys, ticketers) seal and open through `&self`,
+/// while the contexts here are only usable through `&mut`; they're kept behind
+/// this spin lock, which is never contended unless the key is shared between threads.
+pub(crate) struct Locked<T> {
+    busy: AtomicBool,
+    inner: UnsafeCell<T>,
+}
+
+// The cell is only touched by whoever set `busy`.
+unsafe impl<T: Send> Sync for Locked<T> {}
+
+impl<T> Locked<T> {
+    pub(crate) fn new(inner: T) -> Self {
+        Locked {
+            busy: AtomicBool::new(false),
+            inner: UnsafeCell::new(inner),
+        }
+    }
+
+    pub(crate) fn lock(&self) -> LockedGuard<'_, T> {
+        while self
+            .busy
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            hint::spin_loop();
+        }
+
+        LockedGuard(self)
+    }
+}
+
+pub(crate) struct LockedGuard<'a, T>(&'a Locked<T>);
+
+impl<T> Deref for LockedGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.0.inner.get() }
+    }
+}
+
+impl<T> DerefMut for LockedGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.0.inner.get() }
+    }
+}
+
+impl<T> Drop for LockedGuard<'_, T> {
+    fn drop(&mut self) {
+        self.0.busy.store(false, Ordering::Release);
+    }
+}
+
+/// Poly1305 over aad || pad16 || cipher_text || pad16 || lengths (RFC 8439, 2.8),
+/// which is what wc_Poly1305_MAC does.
+fn poly1305_mac(key: &[u8], aad: &[u8], cipher_text: &[u8], tag: &mut [u8]) -> WCResult {
+    let mut poly1305: Poly1305 = unsafe { mem::zeroed() };
+    let mut ret;
+
+    ret = unsafe { wc_Poly1305SetKey(&mut poly1305, key.as_ptr(), key.len() as word32) };
+    check_if_zero(ret)?;
+
+    ret = unsafe {
+        wc_Poly1305_MAC(
+            &mut poly1305,
+            aad.as_ptr(),
+            aad.len() as word32,
+            cipher_text.as_ptr(),
+            cipher_text.len() as word32,
+            tag.as_mut_ptr(),
+            tag.len() as word32,
+        )
+    };
+
+    unsafe { ptr::write_volatile(&mut poly1305, mem::zeroed()) };
+
+    check_if_zero(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    /*
+     * RFC 8439, 2.8.2.
+     * */
+    #[test]
+    fn test_chacha20poly1305_context_rfc8439() {
+        let key = hex!("808182838485868788898a8b8c8d8e8f909192939495969798999a9b9c9d9e9f");
+        let nonce = hex!("070000004041424344454647");
+        let aad = hex!("50515253c0c1c2c3c4c5c6c7");
+        let plain = b"Ladies and Gentlemen of the class of '99: If I could offer you \
+                      only one tip for the future, sunscreen would be it.";
+        let expected = hex!(
+            "d31a8d34648e60db7b86afbc53ef7ec2a4aded51296e08fea9e2b5a736ee62d6"
+            "3dbea45e8ca9671282fafb69da92728b1a71de0a9e060b2905d6a5b67ecd3b36"
+            "92ddbd7f2d778b8c9803aee328091b58fab324e4fad675945585808b4831d7bc"
+            "3ff4def08e4b7a9de576d26586cec64b6116"
+        );
+        let expected_tag = hex!("1ae10b594f09e26a7e902ecbd0600691");
+
+        let mut context = ChaCha20Poly1305Context::new(&key);
+
+        // Twice with the same context, the key has to survive a record.
+        for _ in 0..2 {
+            let mut in_out = plain.to_vec();
+            let mut tag = [0u8; POLY1305_TAG_LENGTH];
+
+            context
+                .seal_in_place(&nonce, &aad, &mut in_out, &mut tag)
+                .unwrap();
+            assert_eq!(in_out, expected);
+            assert_eq!(tag, expected_tag);
+
+            context
+                .open_in_place(&nonce, &aad, &mut in_out, &tag)
+                .unwrap();
+            assert_eq!(in_out, plain);
+        }
+
+        // A bad tag leaves the cipher text alone.
+        let mut in_out = expected.to_vec();
+        let mut bad_tag = expected_tag;
+        bad_tag[0] ^= 1;
+        assert!(context
+            .open_in_place(&nonce, &aad, &mut in_out, &bad_tag)
+            .is_err());
+        assert_eq!(in_out, expected);
+    }
+
+    /*
+     * With the aesni feature, wolfSSL has to pick the AES-NI
+     * code for our contexts whenever the CPU has it.
+     * */
+    #[cfg(all(feature = "aesni", target_arch = "x86_64"))]
+    #[test]
+    fn test_aes_gcm_context_uses_aesni() {
+        let mut context = AesGcmContext::new(&[0x42u8; 32]);
+        let aes = context.0.as_mut_ptr();
+
+        assert_eq!(aes as usize % 16, 0);
+        assert_eq!(
+            unsafe { (*aes).use_aesni } != 0,
+            std::is_x86_feature_detected!("aes")
+        );
+    }
+
+    #[test]
+    fn test_aes_gcm_context_reuse() {
+        let key = [0x42u8; 16];
+        let mut context = AesGcmContext::new(&key);
+
+        for seq in 0u8..4 {
+            let nonce = [seq; 12];
+            let mut in_out = *b"hello GCM";
+            let mut tag = [0u8; 16];
+
+            context
+                .seal_in_place(&nonce, b"aad", &mut in_out, &mut tag)
+                .unwrap();
+            assert_ne!(&in_out, b"hello GCM");
+
+            context
+                .open_in_place(&nonce, b"aad", &mut in_out, &tag)
+                .unwrap();
+            assert_eq!(&in_out, b"hello GCM");
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use crate::hmac::WCShaHmac;
+use alloc::boxed::Box;
+use rustls::crypto::cipher::{
+    make_tls13_aad, AeadKey, InboundOpaqueMessage, InboundPlainMessage, Iv, MessageDecrypter,
+    MessageEncrypter, Nonce, OutboundOpaqueMessage, OutboundPlainMessage, PrefixedPayload,
+    Tls13AeadAlgorithm, UnsupportedOperationError,
+};
+use rustls::crypto::hmac::{Hmac, Key};
+use rustls::{ConnectionTrafficSecrets, ContentType, ProtocolVersion};
+
+const SHA256_TAG_LENGTH: usize = 32;
+
+/// The HMAC-SHA256 "AEAD" of TLS_SHA256_SHA256 (RFC 9150).
+///
+/// Records are authenticated but not encrypted: the TLSInnerPlaintext
+/// goes out as is, followed by HMAC(key, nonce || additional_data || TLSInnerPlaintext).
+pub struct Sha256Hmac;
+
+impl Tls13AeadAlgorithm for Sha256Hmac {
+    fn encrypter(&self, key: AeadKey, iv: Iv) -> Box<dyn MessageEncrypter> {
+        Box::new(WCTls13IntegrityOnly {
+            key: WCShaHmac::Sha256.with_key(key.as_ref()),
+            iv,
+        })
+    }
+
+    fn decrypter(&self, key: AeadKey, iv: Iv) -> Box<dyn MessageDecrypter> {
+        Box::new(WCTls13IntegrityOnly {
+            key: WCShaHmac::Sha256.with_key(key.as_ref()),
+            iv,
+        })
+    }
+
+    fn key_len(&self) -> usize {
+        SHA256_TAG_LENGTH
+    }
+
+    fn extract_keys(
+        &self,
+        _key: AeadKey,
+        _iv: Iv,
+    ) -> Result<ConnectionTrafficSecrets, UnsupportedOperationError> {
+        Err(UnsupportedOperationError)
+    }
+}
+
+pub struct WCTls13IntegrityOnly {
+    key: Box<dyn Key>,
+    iv: Iv,
+}
+
+impl MessageEncrypter for WCTls13IntegrityOnly {
+    fn encrypt(
+        &mut self,
+        m: OutboundPlainMessage,
+        seq: u64,
+    ) -> Result<OutboundOpaqueMessage, rustls::Error> {
+        let payload_len = m.payload.len();
+        let total_len = self.encrypted_payload_len(payload_len);
+        let mut payload = PrefixedPayload::with_capacity(total_len);
+
+        payload.extend_from_chunks(&m.payload);
+        payload.extend_from_slice(&m.typ.to_array());
+
+        let nonce = Nonce::new(&self.iv, seq);
+        let aad = make_tls13_aad(total_len);
+        let tag = self
+            .key
+            .sign_concat(&nonce.0, &[&aad], &payload.as_ref()[..payload_len + 1]);
+
+        payload.extend_from_slice(tag.as_ref());
+
+        Ok(OutboundOpaqueMessage::new(
+            ContentType::ApplicationData,
+            ProtocolVersion::TLSv1_2,
+            payload,
+        ))
+    }
+
+    fn encrypted_payload_len(&self, payload_len: usize) -> usize {
+        payload_len + 1 + self.key.tag_len()
+    }
+}
+
+impl MessageDecrypter for WCTls13IntegrityOnly {
+    fn decrypt<'a>(
+        &mut self,
+        mut m: InboundOpaqueMessage<'a>,
+        seq: u64,
+    ) -> Result<InboundPlainMessage<'a>, rustls::Error> {
+        let payload = &mut m.payload;
+        let tag_len = self.key.tag_len();
+        if payload.len() < tag_len {
+            return Err(rustls::Error::DecryptError);
+        }
+
+        let nonce = Nonce::new(&self.iv, seq);
+        let aad = make_tls13_aad(payload.len());
+        let message_len = payload.len() - tag_len;
+        let expected = self
+            .key
+            .sign_concat(&nonce.0, &[&aad], &payload[..message_len]);
+
+        // Constant time comparison, we don't want to leak
+        // how much of the tag was right.
+        let diff = expected
+            .as_ref()
+            .iter()
+            .zip(&payload[message_len..])
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b));
+        if diff != 0 {
+            return Err(rustls::Error::DecryptError);
+        }
+
+        payload.truncate(message_len);
+
+        m.into_tls13_unpadded_message()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; 32] = [
+        0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e,
+        0x0f, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d,
+        0x1e, 0x1f,
+    ];
+    const IV: [u8; 12] = [
+        0xa0, 0xa1, 0xa2, 0xa3, 0xa4, 0xa5, 0xa6, 0xa7, 0xa8, 0xa9, 0xaa, 0xab,
+    ];
+
+    #[test]
+    fn test_sha256_integrity_only_record() {
+        // "hello HMAC" || ContentType::ApplicationData in the clear,
+        // followed by the HMAC-SHA256 tag.
+        let expected: [u8; 43] = [
+            0x68, 0x65, 0x6c, 0x6c, 0x6f, 0x20, 0x48, 0x4d, 0x41, 0x43, 0x17, 0x78, 0xcf, 0xbe,
+            0xc2, 0x3e, 0xcc, 0xb0, 0x7a, 0xfe, 0x7c, 0x1d, 0xbb, 0xe4, 0xb5, 0xfa, 0xc5, 0x7d,
+            0x69, 0x51, 0x41, 0x22, 0x22, 0x77, 0x5d, 0xd5, 0xfb, 0x53, 0x4a, 0x8d, 0xf7, 0x09,
+            0x29,
+        ];
+        let mut encrypter = Sha256Hmac.encrypter(AeadKey::from(KEY), Iv::new(IV));
+        let mut decrypter = Sha256Hmac.decrypter(AeadKey::from(KEY), Iv::new(IV));
+
+        let plain = OutboundPlainMessage {
+            typ: ContentType::ApplicationData,
+            version: ProtocolVersion::TLSv1_2,
+            payload: b"hello HMAC"[..].into(),
+        };
+        let encrypted = encrypter.encrypt(plain, 1).unwrap().encode();
+
+        assert_eq!(&encrypted[5..], &expected[..]);
+
+        let mut record = expected.to_vec();
+        let decrypted = decrypter
+            .decrypt(
+                InboundOpaqueMessage::new(
+                    ContentType::ApplicationData,
+                    ProtocolVersion::TLSv1_2,
+                    &mut record,
+                ),
+                1,
+            )
+            .unwrap();
+        assert_eq!(decrypted.typ, ContentType::ApplicationData);
+        assert_eq!(decrypted.payload, b"hello HMAC");
+
+        // The plaintext is readable, but can't be changed.
+        let mut tampered = expected.to_vec();
+        tampered[0] ^= 1;
+        assert!(decrypter
+            .decrypt(
+                InboundOpaqueMessage::new(
+                    ContentType::ApplicationData,
+                    ProtocolVersion::TLSv1_2,
+                    &mut tampered,
+                ),
+                1,
+            )
+            .is_err());
+    }
+}
@@ -0,0 +1,151 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use rustls::compress::{
+    CertCompressor, CertDecompressor, CompressionFailed, CompressionLevel, DecompressionFailed,
+};
+use rustls::CertificateCompressionAlgorithm;
+use wolfcrypt_rs::*;
+
+/// zlib's own default (and largest) window, what RFC 8879 peers will use.
+/// wolfCrypt's default is a smaller window, which can't inflate their data.
+const WINDOW_BITS: i32 = 15;
+
+/// Certificate compression (RFC 8879) with zlib, through wolfCrypt.
+pub static ZLIB_COMPRESSOR: &dyn CertCompressor = &WCZlibCompressor;
+
+/// Certificate decompression (RFC 8879) with zlib, through wolfCrypt.
+pub static ZLIB_DECOMPRESSOR: &dyn CertDecompressor = &WCZlibDecompressor;
+
+#[derive(Debug)]
+pub struct WCZlibCompressor;
+
+impl CertCompressor for WCZlibCompressor {
+    // wolfCrypt always deflates with zlib's default level,
+    // so the level hint isn't looked at.
+    fn compress(
+        &self,
+        input: Vec<u8>,
+        _level: CompressionLevel,
+    ) -> Result<Vec<u8>, CompressionFailed> {
+        // Enough room for incompressible input, a bit above zlib's deflateBound().
+        let mut output = vec![0u8; input.len() + input.len() / 1000 + 64];
+
+        // Returns the compressed length, or a negative error code.
+        let ret = unsafe {
+            wc_Compress_ex(
+                output.as_mut_ptr(),
+                output.len() as word32,
+                input.as_ptr(),
+                input.len() as word32,
+                0,
+                WINDOW_BITS as word32,
+            )
+        };
+        if ret <= 0 {
+            return Err(CompressionFailed);
+        }
+
+        output.truncate(ret as usize);
+
+        Ok(output)
+    }
+
+    fn algorithm(&self) -> CertificateCompressionAlgorithm {
+        CertificateCompressionAlgorithm::Zlib
+    }
+}
+
+#[derive(Debug)]
+pub struct WCZlibDecompressor;
+
+impl CertDecompressor for WCZlibDecompressor {
+    fn decompress(&self, input: &[u8], output: &mut [u8]) -> Result<(), DecompressionFailed> {
+        // Returns the decompressed length, or a negative error code
+        // (input that inflates to more than `output` is an error too).
+        let ret = unsafe {
+            wc_DeCompress_ex(
+                output.as_mut_ptr(),
+                output.len() as word32,
+                input.as_ptr(),
+                input.len() as word32,
+                WINDOW_BITS,
+            )
+        };
+
+        // The peer declared the uncompressed length, it has to be exact.
+        match ret >= 0 && ret as usize == output.len() {
+            true => Ok(()),
+            false => Err(DecompressionFailed),
+        }
+    }
+
+    fn algorithm(&self) -> CertificateCompressionAlgorithm {
+        CertificateCompressionAlgorithm::Zlib
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    const PLAIN: &[u8] = b"-----BEGIN CERTIFICATE----------BEGIN CERTIFICATE-----\
+                           -----BEGIN CERTIFICATE----------BEGIN CERTIFICATE-----";
+
+    #[test]
+    fn test_zlib_round_trip() {
+        for level in [CompressionLevel::Interactive, CompressionLevel::Amortized] {
+            let compressed = ZLIB_COMPRESSOR.compress(PLAIN.to_vec(), level).unwrap();
+            assert!(compressed.len() < PLAIN.len());
+
+            let mut output = vec![0u8; PLAIN.len()];
+            ZLIB_DECOMPRESSOR
+                .decompress(&compressed, &mut output)
+                .unwrap();
+            assert_eq!(output, PLAIN);
+        }
+
+        // Random looking data doesn't shrink, but still has to go through.
+        let incompressible: Vec<u8> = (0..4096u32)
+            .map(|i| (i.wrapping_mul(2654435761) >> 13) as u8)
+            .collect();
+        let compressed = ZLIB_COMPRESSOR
+            .compress(incompressible.clone(), CompressionLevel::Interactive)
+            .unwrap();
+        let mut output = vec![0u8; incompressible.len()];
+        ZLIB_DECOMPRESSOR
+            .decompress(&compressed, &mut output)
+            .unwrap();
+        assert_eq!(output, incompressible);
+    }
+
+    /*
+     * Compressed by zlib itself (level 9, 32K window).
+     * */
+    #[test]
+    fn test_zlib_decompress_from_zlib() {
+        let compressed = hex!("78dad3d505022757774f3f0567d7a0104f374f67c710575d38a0a61400741b19a9");
+
+        let mut output = vec![0u8; PLAIN.len()];
+        ZLIB_DECOMPRESSOR
+            .decompress(&compressed, &mut output)
+            .unwrap();
+        assert_eq!(output, PLAIN);
+
+        // The declared length is wrong, either way.
+        let mut short = vec![0u8; PLAIN.len() - 1];
+        assert!(ZLIB_DECOMPRESSOR
+            .decompress(&compressed, &mut short)
+            .is_err());
+        let mut long = vec![0u8; PLAIN.len() + 1];
+        assert!(ZLIB_DECOMPRESSOR
+            .decompress(&compressed, &mut long)
+            .is_err());
+
+        // Truncated, or not zlib at all.
+        assert!(ZLIB_DECOMPRESSOR
+            .decompress(&compressed[..compressed.len() - 4], &mut output)
+            .is_err());
+        assert!(ZLIB_DECOMPRESSOR.decompress(PLAIN, &mut output).is_err());
+    }
+}
@@ -55,7 +55,7 @@ fn check_error(ret: i32) -> WCResult {
         -173 => Err(WCError::InvalidArgument),
         -132 => Err(WCError::Buffer),
         -181..=-180 | -213 => Err(WCError::Authentication),
-        -199 | -236 => Err(WCError::RandomError),
+        -199 | -209 | -236 => Err(WCError::RandomError),
         -162..=-140 => Err(WCError::ASNParse),
         -262 | -216 => Err(WCError::KeyError),
         -174 | -271 => Err(WCError::NotAvailable),
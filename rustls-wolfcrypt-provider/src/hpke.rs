@@ -0,0 +1,896 @@
+use crate::aead::context::ChaCha20Poly1305Context;
+use crate::error::check_if_zero;
+use crate::random::PooledRng;
+use crate::types::*;
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::mem;
+use core::ptr;
+use foreign_types::ForeignType;
+use rustls::crypto::hpke::{
+    EncapsulatedSecret, Hpke, HpkeOpener, HpkePrivateKey, HpkePublicKey, HpkeSealer, HpkeSuite,
+};
+use rustls::internal::msgs::enums::{HpkeAead, HpkeKdf, HpkeKem};
+use rustls::internal::msgs::handshake::HpkeSymmetricCipherSuite;
+use rustls::Error;
+use wolfcrypt_rs::*;
+
+const TAG_LENGTH: usize = 16;
+const X25519_KEY_LENGTH: usize = 32;
+const P256_PRIVATE_KEY_LENGTH: usize = 32;
+const P256_PUBLIC_KEY_LENGTH: usize = 65;
+const NONCE_LENGTH: usize = 12;
+
+/// The RFC 9180 AEAD id, which wolfCrypt's HPKE doesn't know about.
+const HPKE_CHACHA20_POLY1305: u32 = 0x0003;
+
+/// The HPKE (RFC 9180) suites wolfCrypt can do, in base mode.
+///
+/// wolfCrypt's HPKE only implements the AES-GCM AEADs; for ChaCha20-Poly1305
+/// its key schedule is still used, and the messages are sealed and opened here.
+pub static ALL_SUPPORTED_SUITES: &[&dyn Hpke] = &[
+    DH_KEM_P256_HKDF_SHA256_AES_128,
+    DH_KEM_P256_HKDF_SHA256_AES_256,
+    DH_KEM_P256_HKDF_SHA256_CHACHA20_POLY1305,
+    DH_KEM_X25519_HKDF_SHA256_AES_128,
+    DH_KEM_X25519_HKDF_SHA256_AES_256,
+    DH_KEM_X25519_HKDF_SHA256_CHACHA20_POLY1305,
+];
+
+pub static DH_KEM_P256_HKDF_SHA256_AES_128: &WCHpke = &WCHpke {
+    suite: HpkeSuite {
+        kem: HpkeKem::DHKEM_P256_HKDF_SHA256,
+        sym: HpkeSymmetricCipherSuite {
+            kdf_id: HpkeKdf::HKDF_SHA256,
+            aead_id: HpkeAead::AES_128_GCM,
+        },
+    },
+    kem: Kem::P256,
+    aead: HPKE_AES_128_GCM,
+};
+
+pub static DH_KEM_P256_HKDF_SHA256_AES_256: &WCHpke = &WCHpke {
+    suite: HpkeSuite {
+        kem: HpkeKem::DHKEM_P256_HKDF_SHA256,
+        sym: HpkeSymmetricCipherSuite {
+            kdf_id: HpkeKdf::HKDF_SHA256,
+            aead_id: HpkeAead::AES_256_GCM,
+        },
+    },
+    kem: Kem::P256,
+    aead: HPKE_AES_256_GCM,
+};
+
+pub static DH_KEM_P256_HKDF_SHA256_CHACHA20_POLY1305: &WCHpke = &WCHpke {
+    suite: HpkeSuite {
+        kem: HpkeKem::DHKEM_P256_HKDF_SHA256,
+        sym: HpkeSymmetricCipherSuite {
+            kdf_id: HpkeKdf::HKDF_SHA256,
+            aead_id: HpkeAead::CHACHA20_POLY_1305,
+        },
+    },
+    kem: Kem::P256,
+    aead: HPKE_CHACHA20_POLY1305,
+};
+
+pub static DH_KEM_X25519_HKDF_SHA256_AES_128: &WCHpke = &WCHpke {
+    suite: HpkeSuite {
+        kem: HpkeKem::DHKEM_X25519_HKDF_SHA256,
+        sym: HpkeSymmetricCipherSuite {
+            kdf_id: HpkeKdf::HKDF_SHA256,
+            aead_id: HpkeAead::AES_128_GCM,
+        },
+    },
+    kem: Kem::X25519,
+    aead: HPKE_AES_128_GCM,
+};
+
+pub static DH_KEM_X25519_HKDF_SHA256_AES_256: &WCHpke = &WCHpke {
+    suite: HpkeSuite {
+        kem: HpkeKem::DHKEM_X25519_HKDF_SHA256,
+        sym: HpkeSymmetricCipherSuite {
+            kdf_id: HpkeKdf::HKDF_SHA256,
+            aead_id: HpkeAead::AES_256_GCM,
+        },
+    },
+    kem: Kem::X25519,
+    aead: HPKE_AES_256_GCM,
+};
+
+pub static DH_KEM_X25519_HKDF_SHA256_CHACHA20_POLY1305: &WCHpke = &WCHpke {
+    suite: HpkeSuite {
+        kem: HpkeKem::DHKEM_X25519_HKDF_SHA256,
+        sym: HpkeSymmetricCipherSuite {
+            kdf_id: HpkeKdf::HKDF_SHA256,
+            aead_id: HpkeAead::CHACHA20_POLY_1305,
+        },
+    },
+    kem: Kem::X25519,
+    aead: HPKE_CHACHA20_POLY1305,
+};
+
+#[derive(Clone, Copy, Debug)]
+enum Kem {
+    X25519,
+    P256,
+}
+
+impl Kem {
+    fn id(self) -> u32 {
+        match self {
+            Kem::X25519 => DHKEM_X25519_HKDF_SHA256,
+            Kem::P256 => DHKEM_P256_HKDF_SHA256,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct WCHpke {
+    suite: HpkeSuite,
+    kem: Kem,
+    aead: u32,
+}
+
+impl WCHpke {
+    fn init(&self) -> Result<wolfcrypt_rs::Hpke, Error> {
+        let mut hpke: wolfcrypt_rs::Hpke = unsafe { mem::zeroed() };
+
+        // ChaCha20-Poly1305 has the same key and nonce sizes as AES-256-GCM,
+        // so wolfCrypt's key schedule is set up for the latter, and the AEAD
+        // id, which goes into the labels (suite_id, RFC 9180 5.1), swapped.
+        let aead = match self.aead {
+            HPKE_CHACHA20_POLY1305 => HPKE_AES_256_GCM,
+            aead => aead,
+        };
+
+        let ret = unsafe {
+            wc_HpkeInit(
+                &mut hpke,
+                self.kem.id() as i32,
+                HKDF_SHA256 as i32,
+                aead as i32,
+                ptr::null_mut(),
+            )
+        };
+        check_if_zero(ret).map_err(|_| Error::General("wc_HpkeInit failed".into()))?;
+
+        if self.aead == HPKE_CHACHA20_POLY1305 {
+            // suite_id = "HPKE" || kem_id || kdf_id || aead_id
+            hpke.aead = self.aead;
+            hpke.hpke_suite_id[8..10].copy_from_slice(&(self.aead as u16).to_be_bytes());
+        }
+
+        Ok(hpke)
+    }
+
+    fn is_chacha20_poly1305(&self) -> bool {
+        self.aead == HPKE_CHACHA20_POLY1305
+    }
+
+    /// The encapsulated secret is the serialized public part of the ephemeral key.
+    fn serialize_public_key(
+        &self,
+        hpke: &mut wolfcrypt_rs::Hpke,
+        key: &mut KemKey,
+    ) -> Result<Vec<u8>, Error> {
+        let mut out = vec![0u8; HPKE_Npk_MAX as usize];
+        let mut out_len: word16 = out.len() as word16;
+
+        let ret = unsafe {
+            wc_HpkeSerializePublicKey(hpke, key.as_mut_ptr(), out.as_mut_ptr(), &mut out_len)
+        };
+        check_if_zero(ret)
+            .map_err(|_| Error::General("wc_HpkeSerializePublicKey failed".into()))?;
+
+        out.truncate(out_len as usize);
+
+        Ok(out)
+    }
+}
+
+impl Hpke for WCHpke {
+    fn seal(
+        &self,
+        info: &[u8],
+        aad: &[u8],
+        plaintext: &[u8],
+        pub_key: &HpkePublicKey,
+    ) -> Result<(EncapsulatedSecret, Vec<u8>), Error> {
+        if self.is_chacha20_poly1305() {
+            let (enc, mut sealer) = self.setup_sealer(info, pub_key)?;
+            return Ok((enc, sealer.seal(aad, plaintext)?));
+        }
+
+        let mut hpke = self.init()?;
+        let mut receiver = KemKey::from_public(self.kem, &pub_key.0)?;
+        let mut ephemeral = KemKey::generate(self.kem)?;
+        let mut ciphertext = vec![0u8; plaintext.len() + TAG_LENGTH];
+
+        // The tag is appended to the ciphertext.
+        let ret = unsafe {
+            wc_HpkeSealBase(
+                &mut hpke,
+                ephemeral.as_mut_ptr(),
+                receiver.as_mut_ptr(),
+                info.as_ptr() as *mut u8,
+                info.len() as word32,
+                aad.as_ptr() as *mut u8,
+                aad.len() as word32,
+                plaintext.as_ptr() as *mut u8,
+                plaintext.len() as word32,
+                ciphertext.as_mut_ptr(),
+            )
+        };
+        check_if_zero(ret).map_err(|_| Error::EncryptError)?;
+
+        let enc = self.serialize_public_key(&mut hpke, &mut ephemeral)?;
+
+        Ok((EncapsulatedSecret(enc), ciphertext))
+    }
+
+    fn setup_sealer(
+        &self,
+        info: &[u8],
+        pub_key: &HpkePublicKey,
+    ) -> Result<(EncapsulatedSecret, Box<dyn HpkeSealer + 'static>), Error> {
+        let mut hpke = self.init()?;
+        let mut receiver = KemKey::from_public(self.kem, &pub_key.0)?;
+        let mut ephemeral = KemKey::generate(self.kem)?;
+        let mut context: HpkeBaseContext = unsafe { mem::zeroed() };
+
+        let ret = unsafe {
+            wc_HpkeInitSealContext(
+                &mut hpke,
+                &mut context,
+                ephemeral.as_mut_ptr(),
+                receiver.as_mut_ptr(),
+                info.as_ptr() as *mut u8,
+                info.len() as word32,
+            )
+        };
+        check_if_zero(ret).map_err(|_| Error::General("wc_HpkeInitSealContext failed".into()))?;
+
+        let enc = self.serialize_public_key(&mut hpke, &mut ephemeral)?;
+
+        Ok((
+            EncapsulatedSecret(enc),
+            Box::new(WCHpkeContext::new(self, hpke, context)),
+        ))
+    }
+
+    fn open(
+        &self,
+        enc: &EncapsulatedSecret,
+        info: &[u8],
+        aad: &[u8],
+        ciphertext: &[u8],
+        secret_key: &HpkePrivateKey,
+    ) -> Result<Vec<u8>, Error> {
+        if self.is_chacha20_poly1305() {
+            return self
+                .setup_opener(enc, info, secret_key)?
+                .open(aad, ciphertext);
+        }
+
+        if ciphertext.len() < TAG_LENGTH {
+            return Err(Error::DecryptError);
+        }
+
+        let mut hpke = self.init()?;
+        let mut receiver = KemKey::from_private(self.kem, secret_key.secret_bytes())?;
+        let plain_len = ciphertext.len() - TAG_LENGTH;
+        let mut plaintext = vec![0u8; plain_len];
+
+        // Here the ciphertext length leaves the tag out,
+        // wolfCrypt expects to find it right after.
+        let ret = unsafe {
+            wc_HpkeOpenBase(
+                &mut hpke,
+                receiver.as_mut_ptr(),
+                enc.0.as_ptr(),
+                enc.0.len() as word16,
+                info.as_ptr() as *mut u8,
+                info.len() as word32,
+                aad.as_ptr() as *mut u8,
+                aad.len() as word32,
+                ciphertext.as_ptr() as *mut u8,
+                plain_len as word32,
+                plaintext.as_mut_ptr(),
+            )
+        };
+        check_if_zero(ret).map_err(|_| Error::DecryptError)?;
+
+        Ok(plaintext)
+    }
+
+    fn setup_opener(
+        &self,
+        enc: &EncapsulatedSecret,
+        info: &[u8],
+        secret_key: &HpkePrivateKey,
+    ) -> Result<Box<dyn HpkeOpener + 'static>, Error> {
+        let mut hpke = self.init()?;
+        let mut receiver = KemKey::from_private(self.kem, secret_key.secret_bytes())?;
+        let mut context: HpkeBaseContext = unsafe { mem::zeroed() };
+
+        let ret = unsafe {
+            wc_HpkeInitOpenContext(
+                &mut hpke,
+                &mut context,
+                receiver.as_mut_ptr(),
+                enc.0.as_ptr(),
+                enc.0.len() as word16,
+                info.as_ptr() as *mut u8,
+                info.len() as word32,
+            )
+        };
+        check_if_zero(ret).map_err(|_| Error::General("wc_HpkeInitOpenContext failed".into()))?;
+
+        Ok(Box::new(WCHpkeContext::new(self, hpke, context)))
+    }
+
+    fn generate_key_pair(&self) -> Result<(HpkePublicKey, HpkePrivateKey), Error> {
+        let mut hpke = self.init()?;
+        let mut key = KemKey::generate(self.kem)?;
+
+        let public = self.serialize_public_key(&mut hpke, &mut key)?;
+        let private = key.export_private()?;
+
+        Ok((HpkePublicKey(public), HpkePrivateKey::from(private)))
+    }
+
+    fn suite(&self) -> HpkeSuite {
+        self.suite
+    }
+}
+
+/// A base mode context, for sealing or opening
+/// a sequence of messages with the same key.
+pub struct WCHpkeContext {
+    suite: HpkeSuite,
+    hpke: wolfcrypt_rs::Hpke,
+    context: HpkeBaseContext,
+    /// Keyed with `context.key` for the ChaCha20-Poly1305 suites,
+    /// which wolfCrypt can't seal or open with.
+    chacha20_poly1305: Option<ChaCha20Poly1305Context>,
+}
+
+// The only pointer in there is the wolfCrypt heap hint, which is always null.
+unsafe impl Send for WCHpkeContext {}
+unsafe impl Sync for WCHpkeContext {}
+
+impl WCHpkeContext {
+    fn new(hpke_suite: &WCHpke, hpke: wolfcrypt_rs::Hpke, context: HpkeBaseContext) -> Self {
+        let chacha20_poly1305 = match hpke_suite.is_chacha20_poly1305() {
+            true => Some(ChaCha20Poly1305Context::new(
+                &context.key[..hpke.Nk as usize],
+            )),
+            false => None,
+        };
+
+        WCHpkeContext {
+            suite: hpke_suite.suite,
+            hpke,
+            context,
+            chacha20_poly1305,
+        }
+    }
+
+    /// ComputeNonce() from RFC 9180, 5.2:
+    /// base_nonce XOR the big endian sequence number.
+    fn nonce(context: &HpkeBaseContext) -> [u8; NONCE_LENGTH] {
+        let mut nonce = [0u8; NONCE_LENGTH];
+        nonce.copy_from_slice(&context.base_nonce[..NONCE_LENGTH]);
+
+        for (n, s) in nonce[NONCE_LENGTH - 4..]
+            .iter_mut()
+            .zip(context.seq.to_be_bytes())
+        {
+            *n ^= s;
+        }
+
+        nonce
+    }
+
+    /// IncrementSeq() from RFC 9180, 5.2.
+    fn increment_seq(&mut self) -> Result<(), Error> {
+        self.context.seq = self
+            .context
+            .seq
+            .checked_add(1)
+            .ok_or_else(|| Error::General("HPKE sequence number overflow".into()))?;
+
+        Ok(())
+    }
+}
+
+impl HpkeSealer for WCHpkeContext {
+    fn seal(&mut self, aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, Error> {
+        if let Some(aead) = &mut self.chacha20_poly1305 {
+            let nonce = Self::nonce(&self.context);
+            let mut ciphertext = plaintext.to_vec();
+            let mut tag = [0u8; TAG_LENGTH];
+
+            aead.seal_in_place(&nonce, aad, &mut ciphertext, &mut tag)
+                .map_err(|_| Error::EncryptError)?;
+            self.increment_seq()?;
+
+            // The tag is appended to the ciphertext.
+            ciphertext.extend_from_slice(&tag);
+
+            return Ok(ciphertext);
+        }
+
+        let mut ciphertext = vec![0u8; plaintext.len() + TAG_LENGTH];
+
+        // The sequence number is kept (and incremented) by wolfCrypt.
+        let ret = unsafe {
+            wc_HpkeContextSealBase(
+                &mut self.hpke,
+                &mut self.context,
+                aad.as_ptr() as *mut u8,
+                aad.len() as word32,
+                plaintext.as_ptr() as *mut u8,
+                plaintext.len() as word32,
+                ciphertext.as_mut_ptr(),
+            )
+        };
+        check_if_zero(ret).map_err(|_| Error::EncryptError)?;
+
+        Ok(ciphertext)
+    }
+}
+
+impl HpkeOpener for WCHpkeContext {
+    fn open(&mut self, aad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, Error> {
+        if ciphertext.len() < TAG_LENGTH {
+            return Err(Error::DecryptError);
+        }
+
+        let plain_len = ciphertext.len() - TAG_LENGTH;
+
+        if let Some(aead) = &mut self.chacha20_poly1305 {
+            let (ciphertext, tag) = ciphertext.split_at(plain_len);
+            let nonce = Self::nonce(&self.context);
+            let mut plaintext = ciphertext.to_vec();
+
+            aead.open_in_place(&nonce, aad, &mut plaintext, tag)
+                .map_err(|_| Error::DecryptError)?;
+            self.increment_seq()?;
+
+            return Ok(plaintext);
+        }
+
+        let mut plaintext = vec![0u8; plain_len];
+
+        let ret = unsafe {
+            wc_HpkeContextOpenBase(
+                &mut self.hpke,
+                &mut self.context,
+                aad.as_ptr() as *mut u8,
+                aad.len() as word32,
+                ciphertext.as_ptr() as *mut u8,
+                plain_len as word32,
+                plaintext.as_mut_ptr(),
+            )
+        };
+        check_if_zero(ret).map_err(|_| Error::DecryptError)?;
+
+        Ok(plaintext)
+    }
+}
+
+impl Drop for WCHpkeContext {
+    fn drop(&mut self) {
+        // The context holds the AEAD key and the exporter secret.
+        unsafe { ptr::write_volatile(&mut self.context, mem::zeroed()) };
+    }
+}
+
+impl fmt::Debug for WCHpkeContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WCHpkeContext")
+            .field("suite", &self.suite)
+            .finish()
+    }
+}
+
+/// A key of either KEM, handed over to wolfCrypt's HPKE as a void pointer.
+/// Boxed so that it doesn't move while wolfCrypt looks at it.
+enum KemKey {
+    X25519(Box<curve25519_key>),
+    P256(Box<ecc_key>),
+}
+
+impl KemKey {
+    fn new(kem: Kem) -> Result<Self, Error> {
+        let ret;
+        let key = match kem {
+            Kem::X25519 => {
+                let mut key: Box<curve25519_key> = Box::new(unsafe { mem::zeroed() });
+                ret = unsafe { wc_curve25519_init(&mut *key) };
+                KemKey::X25519(key)
+            }
+            Kem::P256 => {
+                let mut key: Box<ecc_key> = Box::new(unsafe { mem::zeroed() });
+                ret = unsafe { wc_ecc_init(&mut *key) };
+                KemKey::P256(key)
+            }
+        };
+        check_if_zero(ret).map_err(|_| Error::General("key init failed".into()))?;
+
+        Ok(key)
+    }
+
+    fn generate(kem: Kem) -> Result<Self, Error> {
+        let mut key = Self::new(kem)?;
+        let rng = PooledRng::acquire();
+
+        let ret = match &mut key {
+            KemKey::X25519(key) => unsafe {
+                wc_curve25519_make_key(rng.as_ptr(), X25519_KEY_LENGTH as i32, &mut **key)
+            },
+            KemKey::P256(key) => unsafe {
+                wc_ecc_make_key_ex(
+                    rng.as_ptr(),
+                    P256_PRIVATE_KEY_LENGTH as i32,
+                    &mut **key,
+                    ecc_curve_id_ECC_SECP256R1,
+                )
+            },
+        };
+        check_if_zero(rng.check(ret))
+            .map_err(|_| Error::General("key generation failed".into()))?;
+
+        Ok(key)
+    }
+
+    /// Imports the public key (SerializePublicKey() from RFC 9180, 7.1.1).
+    fn from_public(kem: Kem, public: &[u8]) -> Result<Self, Error> {
+        let mut key = Self::new(kem)?;
+        let endian = EC25519_LITTLE_ENDIAN as i32;
+
+        let ret = match &mut key {
+            KemKey::X25519(key) => {
+                if public.len() != X25519_KEY_LENGTH {
+                    return Err(Error::General("invalid HPKE public key".into()));
+                }
+
+                unsafe {
+                    match wc_curve25519_check_public(
+                        public.as_ptr(),
+                        public.len() as word32,
+                        endian,
+                    ) {
+                        0 => wc_curve25519_import_public_ex(
+                            public.as_ptr(),
+                            public.len() as word32,
+                            &mut **key,
+                            endian,
+                        ),
+                        err => err,
+                    }
+                }
+            }
+            KemKey::P256(key) => {
+                if public.len() != P256_PUBLIC_KEY_LENGTH {
+                    return Err(Error::General("invalid HPKE public key".into()));
+                }
+
+                // Uncompressed point, then checked to be on the curve.
+                unsafe {
+                    match wc_ecc_import_x963_ex(
+                        public.as_ptr(),
+                        public.len() as word32,
+                        &mut **key,
+                        ecc_curve_id_ECC_SECP256R1,
+                    ) {
+                        0 => wc_ecc_check_key(&mut **key),
+                        err => err,
+                    }
+                }
+            }
+        };
+        check_if_zero(ret).map_err(|_| Error::General("invalid HPKE public key".into()))?;
+
+        Ok(key)
+    }
+
+    /// Imports the private key (SerializePrivateKey() from RFC 9180, 7.1.2),
+    /// along with its public part, which goes into the KEM context.
+    fn from_private(kem: Kem, private: &[u8]) -> Result<Self, Error> {
+        let mut key = Self::new(kem)?;
+
+        let ret = match &mut key {
+            KemKey::X25519(key) => {
+                if private.len() != X25519_KEY_LENGTH {
+                    return Err(Error::General("invalid HPKE private key".into()));
+                }
+
+                // RFC 7748 scalars are clamped when used, wolfCrypt wants them
+                // clamped already; the result of X25519 is the same either way.
+                let mut scalar = [0u8; X25519_KEY_LENGTH];
+                scalar.copy_from_slice(private);
+                scalar[0] &= 248;
+                scalar[31] &= 127;
+                scalar[31] |= 64;
+
+                let mut public = [0u8; X25519_KEY_LENGTH];
+                let ret = unsafe {
+                    match wc_curve25519_make_pub(
+                        public.len() as i32,
+                        public.as_mut_ptr(),
+                        scalar.len() as i32,
+                        scalar.as_ptr(),
+                    ) {
+                        0 => wc_curve25519_import_private_raw_ex(
+                            scalar.as_ptr(),
+                            scalar.len() as word32,
+                            public.as_ptr(),
+                            public.len() as word32,
+                            &mut **key,
+                            EC25519_LITTLE_ENDIAN as i32,
+                        ),
+                        err => err,
+                    }
+                };
+
+                unsafe { ptr::write_volatile(&mut scalar, [0u8; X25519_KEY_LENGTH]) };
+
+                ret
+            }
+            KemKey::P256(key) => {
+                if private.len() != P256_PRIVATE_KEY_LENGTH {
+                    return Err(Error::General("invalid HPKE private key".into()));
+                }
+
+                unsafe {
+                    match wc_ecc_import_private_key_ex(
+                        private.as_ptr(),
+                        private.len() as word32,
+                        ptr::null(),
+                        0,
+                        &mut **key,
+                        ecc_curve_id_ECC_SECP256R1,
+                    ) {
+                        0 => wc_ecc_make_pub(&mut **key, ptr::null_mut()),
+                        err => err,
+                    }
+                }
+            }
+        };
+        check_if_zero(ret).map_err(|_| Error::General("invalid HPKE private key".into()))?;
+
+        Ok(key)
+    }
+
+    fn export_private(&mut self) -> Result<Vec<u8>, Error> {
+        let ret;
+        let mut out = vec![0u8; 32];
+        let mut out_len: word32 = out.len() as word32;
+
+        match self {
+            KemKey::X25519(key) => {
+                ret = unsafe {
+                    wc_curve25519_export_private_raw_ex(
+                        &mut **key,
+                        out.as_mut_ptr(),
+                        &mut out_len,
+                        EC25519_LITTLE_ENDIAN as i32,
+                    )
+                };
+            }
+            KemKey::P256(key) => {
+                ret = unsafe {
+                    wc_ecc_export_private_only(&mut **key, out.as_mut_ptr(), &mut out_len)
+                };
+            }
+        }
+        check_if_zero(ret).map_err(|_| Error::General("private key export failed".into()))?;
+
+        out.truncate(out_len as usize);
+
+        Ok(out)
+    }
+
+    fn as_mut_ptr(&mut self) -> *mut core::ffi::c_void {
+        match self {
+            KemKey::X25519(key) => &mut **key as *mut curve25519_key as *mut _,
+            KemKey::P256(key) => &mut **key as *mut ecc_key as *mut _,
+        }
+    }
+}
+
+impl Drop for KemKey {
+    fn drop(&mut self) {
+        match self {
+            KemKey::X25519(key) => unsafe { wc_curve25519_free(&mut **key) },
+            KemKey::P256(key) => {
+                unsafe { wc_ecc_free(&mut **key) };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+
+    // "Ode on a Grecian Urn"
+    const INFO: [u8; 20] = hex!("4f6465206f6e2061204772656369616e2055726e");
+    const PLAINTEXT: &[u8] = b"Beauty is truth, truth beauty";
+
+    #[test]
+    fn test_round_trip_all_suites() {
+        for suite in ALL_SUPPORTED_SUITES {
+            let (public, private) = suite.generate_key_pair().unwrap();
+
+            let (enc, ciphertext) = suite.seal(&INFO, b"aad", PLAINTEXT, &public).unwrap();
+            assert_eq!(ciphertext.len(), PLAINTEXT.len() + TAG_LENGTH);
+            assert_eq!(
+                suite
+                    .open(&enc, &INFO, b"aad", &ciphertext, &private)
+                    .unwrap(),
+                PLAINTEXT
+            );
+
+            // Wrong additional data.
+            assert!(suite
+                .open(&enc, &INFO, b"AAD", &ciphertext, &private)
+                .is_err());
+
+            // Someone else's key.
+            let (_, other) = suite.generate_key_pair().unwrap();
+            assert!(suite
+                .open(&enc, &INFO, b"aad", &ciphertext, &other)
+                .is_err());
+
+            assert!(suite
+                .seal(&INFO, b"", PLAINTEXT, &HpkePublicKey(vec![]))
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn test_context_all_suites() {
+        for suite in ALL_SUPPORTED_SUITES {
+            let (public, private) = suite.generate_key_pair().unwrap();
+
+            let (enc, mut sealer) = suite.setup_sealer(&INFO, &public).unwrap();
+            let mut opener = suite.setup_opener(&enc, &INFO, &private).unwrap();
+
+            let first = sealer.seal(b"Count-0", PLAINTEXT).unwrap();
+            let second = sealer.seal(b"Count-1", PLAINTEXT).unwrap();
+
+            // A new nonce for each message.
+            assert_ne!(first, second);
+
+            assert_eq!(opener.open(b"Count-0", &first).unwrap(), PLAINTEXT);
+            assert_eq!(opener.open(b"Count-1", &second).unwrap(), PLAINTEXT);
+
+            // Out of order.
+            assert!(opener.open(b"Count-0", &first).is_err());
+        }
+    }
+
+    /*
+     * RFC 9180, A.1.1: DHKEM(X25519, HKDF-SHA256), HKDF-SHA256, AES-128-GCM, base mode.
+     * */
+    #[test]
+    fn test_rfc9180_a1_x25519_aes128gcm() {
+        let suite = DH_KEM_X25519_HKDF_SHA256_AES_128;
+        let enc = EncapsulatedSecret(
+            hex!("37fda3567bdbd628e88668c3c8d7e97d1d1253b6d4ea6d44c150f741f1bf4431").to_vec(),
+        );
+        let private = HpkePrivateKey::from(
+            hex!("4612c550263fc8ad58375df3f557aac531d26850903e55a9f23f21d8534e8ac8").to_vec(),
+        );
+        let first = hex!(
+            "f938558b5d72f1a23810b4be2ab4f84331acc02fc97babc53a52ae8218a355a96d8770ac83d07bea87e13c512a"
+        );
+        let second = hex!(
+            "af2d7e9ac9ae7e270f46ba1f975be53c09f8d875bdc8535458c2494e8a6eab251c03d0c22a56b8ca42c2063b84"
+        );
+
+        assert_eq!(
+            suite
+                .open(&enc, &INFO, b"Count-0", &first, &private)
+                .unwrap(),
+            PLAINTEXT
+        );
+
+        let mut opener = suite.setup_opener(&enc, &INFO, &private).unwrap();
+        assert_eq!(opener.open(b"Count-0", &first).unwrap(), PLAINTEXT);
+        assert_eq!(opener.open(b"Count-1", &second).unwrap(), PLAINTEXT);
+    }
+
+    /*
+     * RFC 9180, A.2.1: DHKEM(X25519, HKDF-SHA256), HKDF-SHA256, ChaCha20Poly1305, base mode.
+     * */
+    #[test]
+    fn test_rfc9180_a2_x25519_chacha20poly1305() {
+        let suite = DH_KEM_X25519_HKDF_SHA256_CHACHA20_POLY1305;
+        let enc = EncapsulatedSecret(
+            hex!("1afa08d3dec047a643885163f1180476fa7ddb54c6a8029ea33f95796bf2ac4a").to_vec(),
+        );
+        let private = HpkePrivateKey::from(
+            hex!("8057991eef8f1f1af18f4a9491d16a1ce333f695d4db8e38da75975c4478e0fb").to_vec(),
+        );
+        let first = hex!(
+            "1c5250d8034ec2b784ba2cfd69dbdb8af406cfe3ff938e131f0def8c8b60b4db21993c62ce81883d2dd1b51a28"
+        );
+        let second = hex!(
+            "6b53c051e4199c518de79594e1c4ab18b96f081549d45ce015be002090bb119e85285337cc95ba5f59992dc98c"
+        );
+
+        assert_eq!(
+            suite
+                .open(&enc, &INFO, b"Count-0", &first, &private)
+                .unwrap(),
+            PLAINTEXT
+        );
+
+        let mut opener = suite.setup_opener(&enc, &INFO, &private).unwrap();
+
+        // A bad message doesn't move the sequence number on.
+        let mut tampered = first;
+        tampered[0] ^= 1;
+        assert!(opener.open(b"Count-0", &tampered).is_err());
+
+        assert_eq!(opener.open(b"Count-0", &first).unwrap(), PLAINTEXT);
+        assert_eq!(opener.open(b"Count-1", &second).unwrap(), PLAINTEXT);
+    }
+
+    #[test]
+    fn test_p256_chacha20poly1305_open() {
+        let suite = DH_KEM_P256_HKDF_SHA256_CHACHA20_POLY1305;
+        let enc = EncapsulatedSecret(
+            hex!(
+                "04c65cf9f5dcc7a873cd1c89ccc024fb9e1f99b59358cff150cd734f8ca553f7d7"
+                "cdb088110aabb46850c1f2ce7867af1a7fdb0fe1060e32986ecf4e119fbfbeb5"
+            )
+            .to_vec(),
+        );
+        let private = HpkePrivateKey::from(
+            hex!("299e902723c9055be112813c7e8a64a796d1b71141c7bbe0be6b8a74dff5c5f6").to_vec(),
+        );
+        let ciphertext = hex!(
+            "6d87ff26567a5ec6c765bc551aca5a860bfe45e4d537b8690e3116e788b07e55fb0945c19bb852ad9cfafe8726"
+        );
+
+        assert_eq!(
+            suite
+                .open(&enc, &INFO, b"Count-0", &ciphertext, &private)
+                .unwrap(),
+            PLAINTEXT
+        );
+    }
+
+    #[test]
+    fn test_p256_aes256gcm_open() {
+        let suite = DH_KEM_P256_HKDF_SHA256_AES_256;
+        let enc = EncapsulatedSecret(
+            hex!(
+                "049a781ca6d055a7f30d0c9ff87936c739f6816ef5f5e72b4b946404b0a1a83b2a"
+                "c19f842945ea2bf65aa1649b2b02ff79854c8d5ecfcd403862e8a97ea66c71c1"
+            )
+            .to_vec(),
+        );
+        let private = HpkePrivateKey::from(
+            hex!("81bae876b70513c9decc608eed549977a81afa1c2b6b4080aec256339e792e0f").to_vec(),
+        );
+        let ciphertext = hex!(
+            "0c78bd6edf885ed21f200d47cbba52d53fddc7ab25ad2386e6737e192d49fe27d7de46d732b37ed77a9200084b"
+        );
+
+        assert_eq!(
+            suite
+                .open(&enc, &INFO, b"Count-0", &ciphertext, &private)
+                .unwrap(),
+            PLAINTEXT
+        );
+    }
+}
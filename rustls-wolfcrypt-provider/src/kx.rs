@@ -2,21 +2,77 @@ use alloc::boxed::Box;
 use crypto::SupportedKxGroup;
 use rustls::crypto;
 
+mod brainpool;
+mod ecc;
+mod ffdhe;
+mod hybrid;
+mod mlkem;
+#[cfg(feature = "std")]
+pub mod pool;
 mod sec256r1;
 mod sec384r1;
 mod sec521r1;
 mod x25519;
+mod x448;
 
-pub const ALL_KX_GROUPS: &[&dyn SupportedKxGroup] = &[&X25519, &SecP256R1, &SecP384R1, &SecP521R1];
+pub use brainpool::{BRAINPOOLP256R1TLS13, BRAINPOOLP384R1TLS13, BRAINPOOLP512R1TLS13};
+pub use ffdhe::{FFDHE2048, FFDHE3072, FFDHE4096, FFDHE6144, FFDHE8192};
+pub use mlkem::{MLKEM1024, MLKEM768};
+
+/// The groups `provider()` offers. The pure ML-KEM and FFDHE groups aren't
+/// among them: they're exported so that they can be put in
+/// `CryptoProvider::kx_groups` when a peer needs them.
+pub const ALL_KX_GROUPS: &[&dyn SupportedKxGroup] = &[
+    &X25519MLKEM768,
+    &X25519,
+    &SecP256R1,
+    &SecP384R1,
+    &SecP521R1,
+    &X448,
+    &BRAINPOOLP256R1TLS13,
+    &BRAINPOOLP384R1TLS13,
+    &BRAINPOOLP512R1TLS13,
+    &SECP256R1MLKEM768,
+    &SECP384R1MLKEM1024,
+];
+
+/// The RFC 7919 groups, for peers that refuse ECDHE. The TLS 1.2 DHE_RSA
+/// suites can only be negotiated with one of them in `kx_groups`.
+pub const FFDHE_KX_GROUPS: &[&dyn SupportedKxGroup] =
+    &[&FFDHE2048, &FFDHE3072, &FFDHE4096, &FFDHE6144, &FFDHE8192];
 
 macro_rules! define_kx_group {
-    ($name:ident, $kx_type:ty, $kx_func:ident, $named_group:expr) => {
+    ($name:ident, $kx_type:ty, $kx_func:ident, $named_group:expr $(, pooled($take:ident))?) => {
         #[derive(Debug)]
         pub struct $name;
 
         impl crypto::SupportedKxGroup for $name {
             fn start(&self) -> Result<Box<dyn crypto::ActiveKeyExchange>, rustls::Error> {
-                Ok(Box::new(<$kx_type>::$kx_func()))
+                // A pre-generated key pair, if the pool is enabled and has one.
+                $(
+                    #[cfg(feature = "std")]
+                    if let Some(kx) = pool::$take() {
+                        return Ok(Box::new(kx));
+                    }
+                )?
+
+                Ok(Box::new(<$kx_type>::$kx_func()?))
+            }
+
+            // Generates our key and derives the secret in one go, without
+            // boxing an ActiveKeyExchange in between.
+            fn start_and_complete(
+                &self,
+                peer_pub_key: &[u8],
+            ) -> Result<crypto::CompletedKeyExchange, rustls::Error> {
+                $(
+                    #[cfg(feature = "std")]
+                    if let Some(kx) = pool::$take() {
+                        return kx.into_completed(peer_pub_key);
+                    }
+                )?
+
+                <$kx_type>::start_and_complete(peer_pub_key)
             }
 
             fn name(&self) -> rustls::NamedGroup {
@@ -31,13 +87,21 @@ define_kx_group!(
     X25519,
     x25519::KeyExchangeX25519,
     use_curve25519,
-    rustls::NamedGroup::X25519
+    rustls::NamedGroup::X25519,
+    pooled(take_x25519)
+);
+define_kx_group!(
+    X448,
+    x448::KeyExchangeX448,
+    use_curve448,
+    rustls::NamedGroup::X448
 );
 define_kx_group!(
     SecP256R1,
     sec256r1::KeyExchangeSecP256r1,
     use_secp256r1,
-    rustls::NamedGroup::secp256r1
+    rustls::NamedGroup::secp256r1,
+    pooled(take_secp256r1)
 );
 define_kx_group!(
     SecP384R1,
@@ -51,3 +115,95 @@ define_kx_group!(
     use_secp521r1,
     rustls::NamedGroup::secp521r1
 );
+
+// Define hybrid post-quantum KeyExchange groups
+pub const X25519MLKEM768: hybrid::Hybrid = hybrid::Hybrid {
+    classical: &X25519,
+    post_quantum: &mlkem::MLKEM768,
+    name: rustls::NamedGroup::X25519MLKEM768,
+    layout: hybrid::Layout {
+        classical_share_len: 32,
+        post_quantum_client_share_len: 1184,
+        post_quantum_server_share_len: 1088,
+        post_quantum_first: true,
+    },
+};
+
+// Both NIST curve hybrids put the uncompressed ECDH point first.
+pub const SECP256R1MLKEM768: hybrid::Hybrid = hybrid::Hybrid {
+    classical: &SecP256R1,
+    post_quantum: &mlkem::MLKEM768,
+    name: rustls::NamedGroup::secp256r1MLKEM768,
+    layout: hybrid::Layout {
+        classical_share_len: 65,
+        post_quantum_client_share_len: 1184,
+        post_quantum_server_share_len: 1088,
+        post_quantum_first: false,
+    },
+};
+
+// rustls doesn't have a name for this codepoint (yet).
+pub const SECP384R1MLKEM1024: hybrid::Hybrid = hybrid::Hybrid {
+    classical: &SecP384R1,
+    post_quantum: &mlkem::MLKEM1024,
+    name: rustls::NamedGroup::Unknown(0x11ed),
+    layout: hybrid::Layout {
+        classical_share_len: 97,
+        post_quantum_client_share_len: 1568,
+        post_quantum_server_share_len: 1568,
+        post_quantum_first: false,
+    },
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The default groups, along with the ones that have to be opted into.
+    fn all_groups() -> impl Iterator<Item = &'static dyn SupportedKxGroup> {
+        let opt_in: [&'static dyn SupportedKxGroup; 2] = [&MLKEM768, &MLKEM1024];
+
+        ALL_KX_GROUPS
+            .iter()
+            .chain(FFDHE_KX_GROUPS)
+            .copied()
+            .chain(opt_in)
+    }
+
+    #[test]
+    fn test_opt_in_groups_are_not_offered_by_default() {
+        for group in [rustls::NamedGroup::MLKEM768, rustls::NamedGroup::MLKEM1024] {
+            assert!(!ALL_KX_GROUPS.iter().any(|g| g.name() == group));
+        }
+
+        for group in FFDHE_KX_GROUPS {
+            assert!(!ALL_KX_GROUPS.iter().any(|g| g.name() == group.name()));
+        }
+    }
+
+    #[test]
+    fn test_start_and_complete_all_groups() {
+        for group in all_groups() {
+            let client = group.start().unwrap();
+            let server = group.start_and_complete(client.pub_key()).unwrap();
+
+            assert_eq!(server.group, group.name());
+            assert_eq!(
+                client.complete(&server.pub_key).unwrap().secret_bytes(),
+                server.secret.secret_bytes(),
+            );
+        }
+    }
+
+    #[test]
+    fn test_start_and_complete_rejects_bad_shares() {
+        for group in all_groups() {
+            let client = group.start().unwrap();
+            let mut too_long = client.pub_key().to_vec();
+            too_long.push(0);
+
+            assert!(group.start_and_complete(&too_long).is_err());
+            assert!(group.start_and_complete(&[]).is_err());
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use crate::kx::ecc::EccKeyPair;
+use alloc::boxed::Box;
+use rustls::crypto::{ActiveKeyExchange, CompletedKeyExchange, SharedSecret, SupportedKxGroup};
+use rustls::ffdhe_groups::FfdheGroup;
+use rustls::{NamedGroup, ProtocolVersion};
+use wolfcrypt_rs::*;
+
+/// A brainpool curve, as used for TLS 1.3 key exchange by RFC 8734.
+///
+/// rustls doesn't name the TLS 1.3 brainpool codepoints, so they are
+/// carried as `NamedGroup::Unknown`.
+#[derive(Clone, Copy, Debug)]
+pub struct Brainpool {
+    curve_id: ecc_curve_id,
+    name: NamedGroup,
+}
+
+pub const BRAINPOOLP256R1TLS13: Brainpool = Brainpool {
+    curve_id: ecc_curve_id_ECC_BRAINPOOLP256R1,
+    name: NamedGroup::Unknown(0x001f),
+};
+
+pub const BRAINPOOLP384R1TLS13: Brainpool = Brainpool {
+    curve_id: ecc_curve_id_ECC_BRAINPOOLP384R1,
+    name: NamedGroup::Unknown(0x0020),
+};
+
+pub const BRAINPOOLP512R1TLS13: Brainpool = Brainpool {
+    curve_id: ecc_curve_id_ECC_BRAINPOOLP512R1,
+    name: NamedGroup::Unknown(0x0021),
+};
+
+impl SupportedKxGroup for Brainpool {
+    fn start(&self) -> Result<Box<dyn ActiveKeyExchange>, rustls::Error> {
+        Ok(Box::new(KeyExchangeBrainpool::use_brainpool(self)?))
+    }
+
+    fn start_and_complete(
+        &self,
+        peer_pub_key: &[u8],
+    ) -> Result<CompletedKeyExchange, rustls::Error> {
+        EccKeyPair::generate(self.curve_id)?.into_completed(self.name, peer_pub_key)
+    }
+
+    fn ffdhe_group(&self) -> Option<FfdheGroup<'static>> {
+        None
+    }
+
+    fn name(&self) -> NamedGroup {
+        self.name
+    }
+
+    // The tls13 codepoints are not defined for TLS 1.2, which uses
+    // its own (deprecated) brainpool codepoints.
+    fn usable_for_version(&self, version: ProtocolVersion) -> bool {
+        version == ProtocolVersion::TLSv1_3
+    }
+}
+
+/// The ephemeral key is generated once and kept as is
+/// until the key exchange completes.
+pub struct KeyExchangeBrainpool {
+    key_pair: EccKeyPair,
+    curve: Brainpool,
+}
+
+impl KeyExchangeBrainpool {
+    pub fn use_brainpool(curve: &Brainpool) -> Result<Self, rustls::Error> {
+        Ok(KeyExchangeBrainpool {
+            key_pair: EccKeyPair::generate(curve.curve_id)?,
+            curve: *curve,
+        })
+    }
+
+    pub fn derive_shared_secret(
+        &mut self,
+        peer_pub_key: &[u8],
+    ) -> Result<SharedSecret, rustls::Error> {
+        // Same uncompressed point format as the NIST curves.
+        self.key_pair.shared_secret(peer_pub_key)
+    }
+}
+
+impl ActiveKeyExchange for KeyExchangeBrainpool {
+    fn complete(mut self: Box<Self>, peer_pub_key: &[u8]) -> Result<SharedSecret, rustls::Error> {
+        self.derive_shared_secret(peer_pub_key)
+    }
+
+    fn pub_key(&self) -> &[u8] {
+        self.key_pair.pub_key()
+    }
+
+    fn group(&self) -> NamedGroup {
+        self.curve.name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_brainpool_kx() {
+        for (curve, field_len) in [
+            (BRAINPOOLP256R1TLS13, 32),
+            (BRAINPOOLP384R1TLS13, 48),
+            (BRAINPOOLP512R1TLS13, 64),
+        ] {
+            let mut alice = Box::new(KeyExchangeBrainpool::use_brainpool(&curve).unwrap());
+            let mut bob = Box::new(KeyExchangeBrainpool::use_brainpool(&curve).unwrap());
+            let alice_pub_key = alice.pub_key().to_vec();
+            let bob_pub_key = bob.pub_key().to_vec();
+
+            assert_eq!(alice_pub_key.len(), 1 + 2 * field_len);
+            assert_eq!(
+                alice
+                    .derive_shared_secret(&bob_pub_key)
+                    .unwrap()
+                    .secret_bytes(),
+                bob.derive_shared_secret(&alice_pub_key)
+                    .unwrap()
+                    .secret_bytes(),
+            )
+        }
+    }
+
+    #[test]
+    fn test_brainpool_rejects_malformed_share() {
+        let mut alice =
+            Box::new(KeyExchangeBrainpool::use_brainpool(&BRAINPOOLP256R1TLS13).unwrap());
+        let mut compressed = alice.pub_key().to_vec();
+        compressed[0] = 0x02;
+        let no_prefix = alice.pub_key()[1..].to_vec();
+
+        assert!(alice.derive_shared_secret(&compressed).is_err());
+        assert!(alice.derive_shared_secret(&no_prefix).is_err());
+    }
+}
@@ -0,0 +1,231 @@
+use crate::error::check_if_zero;
+use crate::random::PooledRng;
+use crate::types::*;
+use alloc::boxed::Box;
+use alloc::vec;
+use core::mem;
+use core::ptr;
+use foreign_types::ForeignType;
+use rustls::crypto::{CompletedKeyExchange, SharedSecret};
+use rustls::{NamedGroup, PeerMisbehaved};
+use wolfcrypt_rs::*;
+use zeroize::Zeroizing;
+
+/// An ephemeral key pair on one of wolfCrypt's ECC curves, kept as generated
+/// for the whole key exchange: the private scalar is never exported.
+///
+/// `wc_ecc_shared_secret` needs the key to be bound (`wc_ecc_set_rng`)
+/// to an RNG, which it uses for blinding. The key is bound to a pooled
+/// DRBG only while it's in use, so that key pairs waiting for the peer
+/// (or in the key share pool) don't hold on to one. On drop the key is
+/// freed, which zeroes the private scalar.
+pub(crate) struct EccKeyPair {
+    key: Box<ecc_key>,
+    curve_id: ecc_curve_id,
+    field_len: usize,
+    pub_key_bytes: Box<[u8]>,
+}
+
+// The key only points to an RNG while we derive the secret,
+// through &mut self; otherwise it's plain data.
+unsafe impl Send for EccKeyPair {}
+unsafe impl Sync for EccKeyPair {}
+
+impl EccKeyPair {
+    pub(crate) fn generate(curve_id: ecc_curve_id) -> Result<Self, rustls::Error> {
+        let mut key: Box<ecc_key> = Box::new(unsafe { mem::zeroed() });
+        let rng = PooledRng::acquire();
+        let mut ret;
+
+        ret = unsafe { wc_ecc_init(&mut *key) };
+        check_if_zero(ret).map_err(|_| rustls::Error::General("wc_ecc_init failed".into()))?;
+
+        let key_size = unsafe { wc_ecc_get_curve_size_from_id(curve_id) };
+
+        // From here on, dropping the key pair frees the key.
+        let mut key_pair = EccKeyPair {
+            key,
+            curve_id,
+            field_len: key_size as usize,
+            pub_key_bytes: Box::new([]),
+        };
+
+        ret = rng.check(unsafe {
+            wc_ecc_make_key_ex(rng.as_ptr(), key_size, &mut *key_pair.key, curve_id)
+        });
+        check_if_zero(ret)
+            .map_err(|_| rustls::Error::General("wc_ecc_make_key_ex failed".into()))?;
+
+        // Uncompressed point, the format TLS uses
+        // (https://www.rfc-editor.org/rfc/rfc8446#section-4.2.8.2):
+        // one byte prefix (0x04) + X coord + Y coord.
+        let mut pub_key_bytes = vec![0u8; 1 + 2 * key_pair.field_len];
+        let mut pub_key_len: word32 = pub_key_bytes.len() as word32;
+
+        ret = unsafe {
+            wc_ecc_export_x963(
+                &mut *key_pair.key,
+                pub_key_bytes.as_mut_ptr(),
+                &mut pub_key_len,
+            )
+        };
+        check_if_zero(ret)
+            .map_err(|_| rustls::Error::General("wc_ecc_export_x963 failed".into()))?;
+
+        key_pair.pub_key_bytes = pub_key_bytes.into_boxed_slice();
+
+        Ok(key_pair)
+    }
+
+    pub(crate) fn pub_key(&self) -> &[u8] {
+        &self.pub_key_bytes
+    }
+
+    /// Derives the shared secret with the peer's uncompressed point,
+    /// with a single `wc_ecc_shared_secret` on the key as generated.
+    pub(crate) fn shared_secret(
+        &mut self,
+        peer_pub_key: &[u8],
+    ) -> Result<SharedSecret, rustls::Error> {
+        let field_len = self.field_len;
+        let mut peer: ecc_key = unsafe { mem::zeroed() };
+        let peer_object = ECCKeyObject::new(&mut peer);
+        let mut ret;
+
+        // Only the uncompressed point format is allowed in TLS 1.3
+        // (RFC 8446, 4.2.8.2), and it's the one we advertise for TLS 1.2.
+        if peer_pub_key.len() != 1 + 2 * field_len || peer_pub_key[0] != 0x04 {
+            return Err(rustls::Error::PeerMisbehaved(
+                PeerMisbehaved::InvalidKeyShare,
+            ));
+        }
+
+        peer_object.init();
+
+        ret = unsafe {
+            wc_ecc_import_unsigned(
+                peer_object.as_ptr(),
+                peer_pub_key[1..1 + field_len].as_ptr(),
+                peer_pub_key[1 + field_len..].as_ptr(),
+                ptr::null_mut(),
+                self.curve_id,
+            )
+        };
+
+        // Wiped on the way out; the secret only leaves as a SharedSecret.
+        let mut out = Zeroizing::new(vec![0u8; field_len]);
+        let mut out_len: word32 = out.len() as word32;
+
+        // SP 800-56A, 5.6.2.3.3: the point has to be on the curve (which
+        // also rules out the point at infinity) with coordinates in the
+        // field range, and, for the curves we use (cofactor 1),
+        // that's enough for it to have the right order.
+        if ret == 0 {
+            ret = unsafe { wc_ecc_check_key(peer_object.as_ptr()) };
+        }
+
+        if ret == 0 {
+            let rng = PooledRng::acquire();
+
+            ret = unsafe { wc_ecc_set_rng(&mut *self.key, rng.as_ptr()) };
+
+            if ret == 0 {
+                ret = rng.check(unsafe {
+                    wc_ecc_shared_secret(
+                        &mut *self.key,
+                        peer_object.as_ptr(),
+                        out.as_mut_ptr(),
+                        &mut out_len,
+                    )
+                });
+            }
+
+            // The DRBG goes back to the pool, the key mustn't keep pointing to it.
+            unsafe { wc_ecc_set_rng(&mut *self.key, ptr::null_mut()) };
+        }
+        unsafe { wc_ecc_free(peer_object.as_ptr()) };
+
+        check_if_zero(ret)
+            .map_err(|_| rustls::Error::PeerMisbehaved(PeerMisbehaved::InvalidKeyShare))?;
+
+        Ok(SharedSecret::from(&out[..out_len as usize]))
+    }
+
+    /// The server side of the exchange, once we have the peer's share:
+    /// derives the secret and hands back our share along with it.
+    pub(crate) fn into_completed(
+        mut self,
+        group: NamedGroup,
+        peer_pub_key: &[u8],
+    ) -> Result<CompletedKeyExchange, rustls::Error> {
+        let secret = self.shared_secret(peer_pub_key)?;
+
+        Ok(CompletedKeyExchange {
+            group,
+            pub_key: self.pub_key_bytes.to_vec(),
+            secret,
+        })
+    }
+}
+
+impl Drop for EccKeyPair {
+    fn drop(&mut self) {
+        unsafe {
+            wc_ecc_free(&mut *self.key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ecc_rejects_invalid_points() {
+        for curve_id in [
+            ecc_curve_id_ECC_SECP256R1,
+            ecc_curve_id_ECC_SECP384R1,
+            ecc_curve_id_ECC_SECP521R1,
+            ecc_curve_id_ECC_BRAINPOOLP256R1,
+            ecc_curve_id_ECC_BRAINPOOLP384R1,
+            ecc_curve_id_ECC_BRAINPOOLP512R1,
+        ] {
+            let mut alice = EccKeyPair::generate(curve_id).unwrap();
+            let bob = EccKeyPair::generate(curve_id).unwrap();
+            let good = bob.pub_key().to_vec();
+
+            let mut off_curve = good.clone();
+            *off_curve.last_mut().unwrap() ^= 1;
+
+            let mut infinity = vec![0u8; good.len()];
+            infinity[0] = 0x04;
+
+            // x >= p, for all of these curves.
+            let mut out_of_range = good.clone();
+            out_of_range[1..1 + alice.field_len].fill(0xff);
+
+            let mut compressed = good[..1 + alice.field_len].to_vec();
+            compressed[0] = 0x02 | (good.last().unwrap() & 1);
+
+            for bad in [
+                &off_curve[..],
+                &infinity[..],
+                &out_of_range[..],
+                &compressed[..],
+                &good[1..],
+                &good[..good.len() - 1],
+                &[][..],
+            ] {
+                assert_eq!(
+                    alice.shared_secret(bad).err(),
+                    Some(rustls::Error::PeerMisbehaved(
+                        PeerMisbehaved::InvalidKeyShare
+                    )),
+                );
+            }
+
+            // The key pair is still usable afterwards.
+            assert!(alice.shared_secret(&good).is_ok());
+        }
+    }
+}
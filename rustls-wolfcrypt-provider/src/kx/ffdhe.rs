@@ -0,0 +1,382 @@
+use crate::random::PooledRng;
+use crate::{error::check_if_zero, types::*};
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::mem;
+use foreign_types::ForeignType;
+use rustls::crypto::{ActiveKeyExchange, CompletedKeyExchange, SharedSecret, SupportedKxGroup};
+use rustls::ffdhe_groups::{self, FfdheGroup};
+use rustls::{NamedGroup, PeerMisbehaved, ProtocolVersion, SupportedProtocolVersion};
+use wolfcrypt_rs::*;
+use zeroize::Zeroizing;
+
+/// A finite field Diffie-Hellman group from RFC 7919.
+///
+/// The key is set up with wolfCrypt's built-in copy of the group
+/// (`wc_DhSetNamedKey`), which it knows to be safe and checks peer values
+/// against (including the subgroup check, with q). rustls's copy of the
+/// same parameters is only what we advertise through `ffdhe_group`.
+#[derive(Clone, Copy, Debug)]
+pub struct Ffdhe {
+    name: NamedGroup,
+    named_key: i32,
+    group: FfdheGroup<'static>,
+}
+
+pub const FFDHE2048: Ffdhe = Ffdhe {
+    name: NamedGroup::FFDHE2048,
+    named_key: WC_FFDHE_2048 as i32,
+    group: ffdhe_groups::FFDHE2048,
+};
+
+pub const FFDHE3072: Ffdhe = Ffdhe {
+    name: NamedGroup::FFDHE3072,
+    named_key: WC_FFDHE_3072 as i32,
+    group: ffdhe_groups::FFDHE3072,
+};
+
+pub const FFDHE4096: Ffdhe = Ffdhe {
+    name: NamedGroup::FFDHE4096,
+    named_key: WC_FFDHE_4096 as i32,
+    group: ffdhe_groups::FFDHE4096,
+};
+
+pub const FFDHE6144: Ffdhe = Ffdhe {
+    name: NamedGroup::FFDHE6144,
+    named_key: WC_FFDHE_6144 as i32,
+    group: ffdhe_groups::FFDHE6144,
+};
+
+pub const FFDHE8192: Ffdhe = Ffdhe {
+    name: NamedGroup::FFDHE8192,
+    named_key: WC_FFDHE_8192 as i32,
+    group: ffdhe_groups::FFDHE8192,
+};
+
+impl Ffdhe {
+    /// This function initializes a DhKey object and loads wolfCrypt's own
+    /// parameters for the group into it.
+    fn set_key(&self, key_object: &DhKeyObject) -> Result<(), rustls::Error> {
+        key_object.init();
+
+        let ret = unsafe { wc_DhSetNamedKey(key_object.as_ptr(), self.named_key) };
+        check_if_zero(ret).map_err(|_| rustls::Error::General("wc_DhSetNamedKey failed".into()))
+    }
+
+    /// This function checks the peer's public value against the key's
+    /// parameters: 2 <= y <= p - 2, and y^q = 1 mod p. The value has to be
+    /// left-padded to the size of the prime (RFC 8446, 4.2.8.1).
+    fn check_peer_pub_key(
+        &self,
+        key_object: &DhKeyObject,
+        peer_pub_key: &[u8],
+    ) -> Result<(), rustls::Error> {
+        if peer_pub_key.len() != self.group.p.len() {
+            return Err(rustls::Error::PeerMisbehaved(
+                PeerMisbehaved::InvalidKeyShare,
+            ));
+        }
+
+        let ret = unsafe {
+            wc_DhCheckPubKey(
+                key_object.as_ptr(),
+                peer_pub_key.as_ptr(),
+                peer_pub_key.len() as word32,
+            )
+        };
+        check_if_zero(ret)
+            .map_err(|_| rustls::Error::PeerMisbehaved(PeerMisbehaved::InvalidKeyShare))
+    }
+}
+
+impl SupportedKxGroup for Ffdhe {
+    fn start(&self) -> Result<Box<dyn ActiveKeyExchange>, rustls::Error> {
+        Ok(Box::new(KeyExchangeFfdhe::use_ffdhe(self)?))
+    }
+
+    // The private key is generated and used right away with the same DhKey,
+    // and only lives in a local buffer that is wiped on the way out.
+    fn start_and_complete(
+        &self,
+        peer_pub_key: &[u8],
+    ) -> Result<CompletedKeyExchange, rustls::Error> {
+        let p_len = self.group.p.len();
+        let mut key: DhKey = unsafe { mem::zeroed() };
+        let key_object = DhKeyObject::new(&mut key);
+        let rng = PooledRng::acquire();
+        let mut priv_key_raw = Zeroizing::new(vec![0u8; p_len]);
+        let mut priv_key_raw_len: word32 = priv_key_raw.len() as word32;
+        let mut pub_key_raw = vec![0u8; p_len];
+        let mut pub_key_raw_len: word32 = pub_key_raw.len() as word32;
+        let mut out = Zeroizing::new(vec![0u8; p_len]);
+        let mut out_len: word32 = out.len() as word32;
+        let mut ret;
+
+        self.set_key(&key_object)?;
+        // Nothing gets generated for a share we would reject anyway.
+        self.check_peer_pub_key(&key_object, peer_pub_key)?;
+
+        ret = rng.check(unsafe {
+            wc_DhGenerateKeyPair(
+                key_object.as_ptr(),
+                rng.as_ptr(),
+                priv_key_raw.as_mut_ptr(),
+                &mut priv_key_raw_len,
+                pub_key_raw.as_mut_ptr(),
+                &mut pub_key_raw_len,
+            )
+        });
+        check_if_zero(ret)
+            .map_err(|_| rustls::Error::General("wc_DhGenerateKeyPair failed".into()))?;
+
+        ret = unsafe {
+            wc_DhAgree(
+                key_object.as_ptr(),
+                out.as_mut_ptr(),
+                &mut out_len,
+                priv_key_raw.as_ptr(),
+                priv_key_raw_len,
+                peer_pub_key.as_ptr(),
+                peer_pub_key.len() as word32,
+            )
+        };
+        check_if_zero(ret).map_err(|_| rustls::Error::General("wc_DhAgree failed".into()))?;
+
+        Ok(CompletedKeyExchange {
+            group: self.name,
+            pub_key: left_pad(&pub_key_raw[..pub_key_raw_len as usize], p_len),
+            secret: SharedSecret::from(left_pad(&out[..out_len as usize], p_len)),
+        })
+    }
+
+    fn ffdhe_group(&self) -> Option<FfdheGroup<'static>> {
+        Some(self.group)
+    }
+
+    fn name(&self) -> NamedGroup {
+        self.name
+    }
+}
+
+/// wc_DhGenerateKeyPair hands the private key back to us rather than
+/// keeping it in the DhKey, so it's held here until the exchange completes,
+/// and wiped on drop.
+pub struct KeyExchangeFfdhe {
+    pub_key_bytes: Box<[u8]>,
+    priv_key_bytes: Zeroizing<Vec<u8>>,
+    group: Ffdhe,
+}
+
+impl KeyExchangeFfdhe {
+    pub fn use_ffdhe(group: &Ffdhe) -> Result<Self, rustls::Error> {
+        let mut key: DhKey = unsafe { mem::zeroed() };
+        let key_object = DhKeyObject::new(&mut key);
+        let rng = PooledRng::acquire();
+        let p_len = group.group.p.len();
+        let mut priv_key_raw = Zeroizing::new(vec![0u8; p_len]);
+        let mut priv_key_raw_len: word32 = priv_key_raw.len() as word32;
+        let mut pub_key_raw = vec![0u8; p_len];
+        let mut pub_key_raw_len: word32 = pub_key_raw.len() as word32;
+
+        group.set_key(&key_object)?;
+
+        // This function generates a private key (sized after the prime,
+        // following the group's work factor) and computes the matching
+        // public value g^x mod p.
+        let ret = rng.check(unsafe {
+            wc_DhGenerateKeyPair(
+                key_object.as_ptr(),
+                rng.as_ptr(),
+                priv_key_raw.as_mut_ptr(),
+                &mut priv_key_raw_len,
+                pub_key_raw.as_mut_ptr(),
+                &mut pub_key_raw_len,
+            )
+        });
+        check_if_zero(ret)
+            .map_err(|_| rustls::Error::General("wc_DhGenerateKeyPair failed".into()))?;
+
+        priv_key_raw.truncate(priv_key_raw_len as usize);
+
+        Ok(KeyExchangeFfdhe {
+            pub_key_bytes: left_pad(&pub_key_raw[..pub_key_raw_len as usize], p_len)
+                .into_boxed_slice(),
+            priv_key_bytes: priv_key_raw,
+            group: *group,
+        })
+    }
+
+    pub fn derive_shared_secret(&self, peer_pub_key: &[u8]) -> Result<Box<[u8]>, rustls::Error> {
+        let p = self.group.group.p;
+        let mut key: DhKey = unsafe { mem::zeroed() };
+        let key_object = DhKeyObject::new(&mut key);
+        let mut out = Zeroizing::new(vec![0u8; p.len()]);
+        let mut out_len: word32 = out.len() as word32;
+
+        self.group.set_key(&key_object)?;
+        self.group.check_peer_pub_key(&key_object, peer_pub_key)?;
+
+        // This function computes the shared secret y^x mod p from our
+        // private key and the peer's public value.
+        let ret = unsafe {
+            wc_DhAgree(
+                key_object.as_ptr(),
+                out.as_mut_ptr(),
+                &mut out_len,
+                self.priv_key_bytes.as_ptr(),
+                self.priv_key_bytes.len() as word32,
+                peer_pub_key.as_ptr(),
+                peer_pub_key.len() as word32,
+            )
+        };
+        check_if_zero(ret).map_err(|_| rustls::Error::General("wc_DhAgree failed".into()))?;
+
+        Ok(left_pad(&out[..out_len as usize], p.len()).into_boxed_slice())
+    }
+}
+
+/// TLS 1.3 wants both the key shares and the shared secret to be
+/// left-padded with zeros to the size of the prime (RFC 8446, 4.2.8.1
+/// and 7.4.1), while wolfCrypt returns the minimal encoding.
+fn left_pad(bytes: &[u8], len: usize) -> Vec<u8> {
+    let mut out = vec![0u8; len];
+    out[len - bytes.len()..].copy_from_slice(bytes);
+    out
+}
+
+impl ActiveKeyExchange for KeyExchangeFfdhe {
+    fn complete(self: Box<Self>, peer_pub_key: &[u8]) -> Result<SharedSecret, rustls::Error> {
+        let secret = self.derive_shared_secret(peer_pub_key)?;
+
+        Ok(SharedSecret::from(&*secret))
+    }
+
+    // TLS 1.2 peers may send their value without the leading zeros, and
+    // the secret is used without them (RFC 5246, 8.1.2); TLS 1.3 pads both.
+    fn complete_for_tls_version(
+        self: Box<Self>,
+        peer_pub_key: &[u8],
+        tls_version: &SupportedProtocolVersion,
+    ) -> Result<SharedSecret, rustls::Error> {
+        if tls_version.version != ProtocolVersion::TLSv1_2 {
+            return self.complete(peer_pub_key);
+        }
+
+        let p_len = self.group.group.p.len();
+        if peer_pub_key.is_empty() || peer_pub_key.len() > p_len {
+            return Err(rustls::Error::PeerMisbehaved(
+                PeerMisbehaved::InvalidKeyShare,
+            ));
+        }
+
+        let secret = self.derive_shared_secret(&left_pad(peer_pub_key, p_len))?;
+        let leading_zeros = secret.iter().take_while(|byte| **byte == 0).count();
+
+        Ok(SharedSecret::from(&secret[leading_zeros..]))
+    }
+
+    fn pub_key(&self) -> &[u8] {
+        &self.pub_key_bytes
+    }
+
+    fn ffdhe_group(&self) -> Option<FfdheGroup<'static>> {
+        Some(self.group.group)
+    }
+
+    fn group(&self) -> NamedGroup {
+        self.group.name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ffdhe_kx() {
+        for group in [FFDHE2048, FFDHE3072, FFDHE4096, FFDHE6144, FFDHE8192] {
+            let alice = Box::new(KeyExchangeFfdhe::use_ffdhe(&group).unwrap());
+            let bob = Box::new(KeyExchangeFfdhe::use_ffdhe(&group).unwrap());
+
+            assert_eq!(alice.pub_key().len(), group.group.p.len());
+
+            let alice_secret = alice.derive_shared_secret(bob.pub_key()).unwrap();
+            let bob_secret = bob.derive_shared_secret(alice.pub_key()).unwrap();
+
+            assert_eq!(alice_secret.len(), group.group.p.len());
+            assert_eq!(alice_secret, bob_secret);
+        }
+    }
+
+    #[test]
+    fn test_ffdhe_named_keys_match_rfc7919() {
+        for group in [FFDHE2048, FFDHE3072, FFDHE4096, FFDHE6144, FFDHE8192] {
+            let mut key: DhKey = unsafe { mem::zeroed() };
+            let key_object = DhKeyObject::new(&mut key);
+            let p_len = group.group.p.len();
+            let mut p = vec![0u8; p_len];
+            let mut p_len_out: word32 = p.len() as word32;
+            let mut q = vec![0u8; p_len];
+            let mut q_len_out: word32 = q.len() as word32;
+            let mut g = vec![0u8; p_len];
+            let mut g_len_out: word32 = g.len() as word32;
+
+            group.set_key(&key_object).unwrap();
+
+            let ret = unsafe {
+                wc_DhExportParamsRaw(
+                    key_object.as_ptr(),
+                    p.as_mut_ptr(),
+                    &mut p_len_out,
+                    q.as_mut_ptr(),
+                    &mut q_len_out,
+                    g.as_mut_ptr(),
+                    &mut g_len_out,
+                )
+            };
+            check_if_zero(ret).unwrap();
+
+            // What wolfCrypt uses is what we advertise.
+            assert_eq!(&p[..p_len_out as usize], group.group.p);
+            assert_eq!(&g[..g_len_out as usize], group.group.g);
+        }
+    }
+
+    #[test]
+    fn test_ffdhe_rejects_bad_pub_values() {
+        let alice = Box::new(KeyExchangeFfdhe::use_ffdhe(&FFDHE2048).unwrap());
+        let p_len = FFDHE2048.group.p.len();
+
+        let mut one = vec![0u8; p_len];
+        one[p_len - 1] = 1;
+
+        assert!(alice.derive_shared_secret(&one).is_err());
+        assert!(alice.derive_shared_secret(FFDHE2048.group.p).is_err());
+        assert!(alice.derive_shared_secret(&[]).is_err());
+        assert!(alice.derive_shared_secret(&vec![0xff; p_len + 1]).is_err());
+
+        // 2^8 is a valid value (g = 2 generates the subgroup),
+        // but it isn't padded to the size of the prime.
+        assert!(alice.derive_shared_secret(&[0x01, 0x00]).is_err());
+        assert!(FFDHE2048.start_and_complete(&[0x01, 0x00]).is_err());
+    }
+
+    #[test]
+    fn test_ffdhe_tls12_accepts_unpadded_value() {
+        // 2^8 again, as a TLS 1.2 peer may send it.
+        let peer_pub_key = [0x01, 0x00];
+        let bob = Box::new(KeyExchangeFfdhe::use_ffdhe(&FFDHE2048).unwrap());
+
+        let padded = bob
+            .derive_shared_secret(&left_pad(&peer_pub_key, FFDHE2048.group.p.len()))
+            .unwrap();
+
+        let secret = bob
+            .complete_for_tls_version(&peer_pub_key, &rustls::version::TLS12)
+            .unwrap();
+        let leading_zeros = padded.iter().take_while(|byte| **byte == 0).count();
+
+        assert_eq!(secret.secret_bytes(), &padded[leading_zeros..]);
+    }
+}
@@ -0,0 +1,165 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use rustls::crypto::{ActiveKeyExchange, CompletedKeyExchange, SharedSecret, SupportedKxGroup};
+use rustls::ffdhe_groups::FfdheGroup;
+use rustls::{NamedGroup, PeerMisbehaved, ProtocolVersion};
+
+/// A hybrid key exchange group, combining a classical (EC)DH group with
+/// an ML-KEM one, as described in draft-ietf-tls-ecdhe-mlkem.
+///
+/// Both key shares and shared secrets are the plain concatenation of
+/// the two components, in the order given by `post_quantum_first`.
+#[derive(Debug)]
+pub struct Hybrid {
+    pub classical: &'static dyn SupportedKxGroup,
+    pub post_quantum: &'static dyn SupportedKxGroup,
+    pub name: NamedGroup,
+    pub layout: Layout,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Layout {
+    /// Length of the classical key share (same for client and server).
+    pub classical_share_len: usize,
+    /// Length of the ML-KEM encapsulation key, sent by the client.
+    pub post_quantum_client_share_len: usize,
+    /// Length of the ML-KEM ciphertext, sent by the server.
+    pub post_quantum_server_share_len: usize,
+    /// X25519MLKEM768 puts the ML-KEM component first, while the
+    /// NIST curve hybrids put the classical component first.
+    pub post_quantum_first: bool,
+}
+
+impl Layout {
+    fn split<'a>(
+        &self,
+        share: &'a [u8],
+        post_quantum_share_len: usize,
+    ) -> Result<(&'a [u8], &'a [u8]), rustls::Error> {
+        if share.len() != self.classical_share_len + post_quantum_share_len {
+            return Err(rustls::Error::PeerMisbehaved(
+                PeerMisbehaved::InvalidKeyShare,
+            ));
+        }
+
+        // We always return (post_quantum, classical).
+        if self.post_quantum_first {
+            Ok(share.split_at(post_quantum_share_len))
+        } else {
+            let (classical, post_quantum) = share.split_at(self.classical_share_len);
+            Ok((post_quantum, classical))
+        }
+    }
+
+    fn concat(&self, post_quantum: &[u8], classical: &[u8]) -> Vec<u8> {
+        if self.post_quantum_first {
+            [post_quantum, classical].concat()
+        } else {
+            [classical, post_quantum].concat()
+        }
+    }
+}
+
+impl SupportedKxGroup for Hybrid {
+    fn start(&self) -> Result<Box<dyn ActiveKeyExchange>, rustls::Error> {
+        let classical = self.classical.start()?;
+        let post_quantum = self.post_quantum.start()?;
+        let pub_key_bytes = self
+            .layout
+            .concat(post_quantum.pub_key(), classical.pub_key());
+
+        Ok(Box::new(KeyExchangeHybrid {
+            classical,
+            post_quantum,
+            pub_key_bytes,
+            name: self.name,
+            layout: self.layout,
+        }))
+    }
+
+    // The server side: the ML-KEM half needs the client's encapsulation key
+    // to produce our share, so it can't go through start() and complete().
+    fn start_and_complete(
+        &self,
+        peer_pub_key: &[u8],
+    ) -> Result<CompletedKeyExchange, rustls::Error> {
+        let (post_quantum_share, classical_share) = self
+            .layout
+            .split(peer_pub_key, self.layout.post_quantum_client_share_len)?;
+
+        let classical = self.classical.start_and_complete(classical_share)?;
+        let post_quantum = self.post_quantum.start_and_complete(post_quantum_share)?;
+
+        Ok(CompletedKeyExchange {
+            group: self.name,
+            pub_key: self
+                .layout
+                .concat(&post_quantum.pub_key, &classical.pub_key),
+            secret: SharedSecret::from(self.layout.concat(
+                post_quantum.secret.secret_bytes(),
+                classical.secret.secret_bytes(),
+            )),
+        })
+    }
+
+    fn ffdhe_group(&self) -> Option<FfdheGroup<'static>> {
+        None
+    }
+
+    fn name(&self) -> NamedGroup {
+        self.name
+    }
+
+    fn usable_for_version(&self, version: ProtocolVersion) -> bool {
+        version == ProtocolVersion::TLSv1_3
+    }
+}
+
+pub struct KeyExchangeHybrid {
+    classical: Box<dyn ActiveKeyExchange>,
+    post_quantum: Box<dyn ActiveKeyExchange>,
+    pub_key_bytes: Vec<u8>,
+    name: NamedGroup,
+    layout: Layout,
+}
+
+impl ActiveKeyExchange for KeyExchangeHybrid {
+    fn complete(self: Box<Self>, peer_pub_key: &[u8]) -> Result<SharedSecret, rustls::Error> {
+        let (post_quantum_share, classical_share) = self
+            .layout
+            .split(peer_pub_key, self.layout.post_quantum_server_share_len)?;
+
+        let classical = self.classical.complete(classical_share)?;
+        let post_quantum = self.post_quantum.complete(post_quantum_share)?;
+
+        Ok(SharedSecret::from(self.layout.concat(
+            post_quantum.secret_bytes(),
+            classical.secret_bytes(),
+        )))
+    }
+
+    // Lets rustls send the classical share on its own next to the hybrid
+    // one, so servers without ML-KEM support don't need a HelloRetryRequest.
+    fn hybrid_component(&self) -> Option<(NamedGroup, &[u8])> {
+        Some((self.classical.group(), self.classical.pub_key()))
+    }
+
+    fn complete_hybrid_component(
+        self: Box<Self>,
+        peer_pub_key: &[u8],
+    ) -> Result<SharedSecret, rustls::Error> {
+        self.classical.complete(peer_pub_key)
+    }
+
+    fn pub_key(&self) -> &[u8] {
+        &self.pub_key_bytes
+    }
+
+    fn ffdhe_group(&self) -> Option<FfdheGroup<'static>> {
+        None
+    }
+
+    fn group(&self) -> NamedGroup {
+        self.name
+    }
+}
@@ -0,0 +1,295 @@
+use crate::random::PooledRng;
+use crate::{error::check_if_zero, types::*};
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::mem;
+use core::ptr;
+use foreign_types::ForeignType;
+use rustls::crypto::{ActiveKeyExchange, CompletedKeyExchange, SharedSecret, SupportedKxGroup};
+use rustls::ffdhe_groups::FfdheGroup;
+use rustls::{NamedGroup, PeerMisbehaved, ProtocolVersion};
+use wolfcrypt_rs::*;
+use zeroize::Zeroizing;
+
+const MLKEM_SHARED_SECRET_LEN: usize = 32;
+
+/// An ML-KEM (FIPS 203) parameter set, used as a TLS 1.3 key exchange group.
+///
+/// The client share is the encapsulation key, the server share is the
+/// ciphertext produced by encapsulating against it.
+#[derive(Clone, Copy, Debug)]
+pub struct MlKem {
+    kyber_type: i32,
+    pub_key_len: usize,
+    ciphertext_len: usize,
+    name: NamedGroup,
+}
+
+pub const MLKEM768: MlKem = MlKem {
+    kyber_type: WC_ML_KEM_768 as i32,
+    pub_key_len: 1184,
+    ciphertext_len: 1088,
+    name: NamedGroup::MLKEM768,
+};
+
+pub const MLKEM1024: MlKem = MlKem {
+    kyber_type: WC_ML_KEM_1024 as i32,
+    pub_key_len: 1568,
+    ciphertext_len: 1568,
+    name: NamedGroup::MLKEM1024,
+};
+
+impl MlKem {
+    /// Length of the key share sent by the client (the encapsulation key).
+    pub fn pub_key_len(&self) -> usize {
+        self.pub_key_len
+    }
+
+    /// Length of the key share sent by the server (the ciphertext).
+    pub fn ciphertext_len(&self) -> usize {
+        self.ciphertext_len
+    }
+
+    /// This function is the server side of the exchange: it imports the peer's
+    /// encapsulation key and encapsulates a fresh shared secret against it.
+    /// Returns the ciphertext (our key share) together with the shared secret.
+    pub fn encapsulate(
+        &self,
+        peer_pub_key: &[u8],
+    ) -> Result<(Vec<u8>, SharedSecret), rustls::Error> {
+        if peer_pub_key.len() != self.pub_key_len {
+            return Err(rustls::Error::PeerMisbehaved(
+                PeerMisbehaved::InvalidKeyShare,
+            ));
+        }
+
+        let mut key = KyberKeyBox::new(self.kyber_type)?;
+        let rng = PooledRng::acquire();
+        let mut ciphertext = vec![0u8; self.ciphertext_len];
+        let mut secret = Zeroizing::new([0u8; MLKEM_SHARED_SECRET_LEN]);
+        let mut ret;
+
+        // This function decodes the peer's encoded public (encapsulation) key,
+        // it also checks that its length matches the parameter set.
+        ret = unsafe {
+            wc_KyberKey_DecodePublicKey(
+                key.as_mut_ptr(),
+                peer_pub_key.as_ptr(),
+                peer_pub_key.len() as word32,
+            )
+        };
+        check_if_zero(ret)
+            .map_err(|_| rustls::Error::PeerMisbehaved(PeerMisbehaved::InvalidKeyShare))?;
+
+        // This function generates a random shared secret and encapsulates it
+        // under the peer's public key, writing the ciphertext to ct.
+        ret = rng.check(unsafe {
+            wc_KyberKey_Encapsulate(
+                key.as_mut_ptr(),
+                ciphertext.as_mut_ptr(),
+                secret.as_mut_ptr(),
+                rng.as_ptr(),
+            )
+        });
+        check_if_zero(ret)
+            .map_err(|_| rustls::Error::General("wc_KyberKey_Encapsulate failed".into()))?;
+
+        Ok((ciphertext, SharedSecret::from(&secret[..])))
+    }
+}
+
+impl SupportedKxGroup for MlKem {
+    fn start(&self) -> Result<Box<dyn ActiveKeyExchange>, rustls::Error> {
+        Ok(Box::new(KeyExchangeMlKem::use_mlkem(self)?))
+    }
+
+    fn start_and_complete(
+        &self,
+        peer_pub_key: &[u8],
+    ) -> Result<CompletedKeyExchange, rustls::Error> {
+        let (ciphertext, secret) = self.encapsulate(peer_pub_key)?;
+
+        Ok(CompletedKeyExchange {
+            group: self.name,
+            pub_key: ciphertext,
+            secret,
+        })
+    }
+
+    fn ffdhe_group(&self) -> Option<FfdheGroup<'static>> {
+        None
+    }
+
+    fn name(&self) -> NamedGroup {
+        self.name
+    }
+
+    fn usable_for_version(&self, version: ProtocolVersion) -> bool {
+        version == ProtocolVersion::TLSv1_3
+    }
+}
+
+/// Owns a heap allocated KyberKey, so that the key stays valid
+/// (and gets freed) for as long as the key exchange is alive.
+struct KyberKeyBox(Box<KyberKey>);
+
+// The key is only ever accessed through a Box<Self> (or &mut self) that
+// the key exchange owns exclusively.
+unsafe impl Send for KyberKeyBox {}
+unsafe impl Sync for KyberKeyBox {}
+
+impl KyberKeyBox {
+    fn new(kyber_type: i32) -> Result<Self, rustls::Error> {
+        let mut key: Box<KyberKey> = Box::new(unsafe { mem::zeroed() });
+
+        // This function initializes a KyberKey object for the given
+        // ML-KEM parameter set.
+        let ret =
+            unsafe { wc_KyberKey_Init(kyber_type, key.as_mut(), ptr::null_mut(), INVALID_DEVID) };
+        check_if_zero(ret).map_err(|_| rustls::Error::General("wc_KyberKey_Init failed".into()))?;
+
+        Ok(KyberKeyBox(key))
+    }
+
+    fn as_mut_ptr(&mut self) -> *mut KyberKey {
+        self.0.as_mut()
+    }
+}
+
+impl Drop for KyberKeyBox {
+    fn drop(&mut self) {
+        // Zeroizes the private key material and releases the resources of the key.
+        unsafe {
+            wc_KyberKey_Free(self.0.as_mut());
+        }
+    }
+}
+
+pub struct KeyExchangeMlKem {
+    key: KyberKeyBox,
+    pub_key_bytes: Box<[u8]>,
+    group: MlKem,
+}
+
+impl KeyExchangeMlKem {
+    pub fn use_mlkem(group: &MlKem) -> Result<Self, rustls::Error> {
+        let mut key = KyberKeyBox::new(group.kyber_type)?;
+        let rng = PooledRng::acquire();
+        let mut pub_key_bytes = vec![0u8; group.pub_key_len];
+        let mut ret;
+
+        // This function generates a new (decapsulation, encapsulation) key pair.
+        ret = rng.check(unsafe { wc_KyberKey_MakeKey(key.as_mut_ptr(), rng.as_ptr()) });
+        check_if_zero(ret)
+            .map_err(|_| rustls::Error::General("wc_KyberKey_MakeKey failed".into()))?;
+
+        // The encoded public (encapsulation) key is the key share that we send.
+        ret = unsafe {
+            wc_KyberKey_EncodePublicKey(
+                key.as_mut_ptr(),
+                pub_key_bytes.as_mut_ptr(),
+                pub_key_bytes.len() as word32,
+            )
+        };
+        check_if_zero(ret)
+            .map_err(|_| rustls::Error::General("wc_KyberKey_EncodePublicKey failed".into()))?;
+
+        Ok(KeyExchangeMlKem {
+            key,
+            pub_key_bytes: pub_key_bytes.into_boxed_slice(),
+            group: *group,
+        })
+    }
+
+    /// The peer's key share is the ciphertext, which we decapsulate
+    /// with our private key to recover the shared secret.
+    pub fn decapsulate(&mut self, ciphertext: &[u8]) -> Result<SharedSecret, rustls::Error> {
+        if ciphertext.len() != self.group.ciphertext_len {
+            return Err(rustls::Error::PeerMisbehaved(
+                PeerMisbehaved::InvalidKeyShare,
+            ));
+        }
+
+        let mut secret = Zeroizing::new([0u8; MLKEM_SHARED_SECRET_LEN]);
+
+        let ret = unsafe {
+            wc_KyberKey_Decapsulate(
+                self.key.as_mut_ptr(),
+                secret.as_mut_ptr(),
+                ciphertext.as_ptr(),
+                ciphertext.len() as word32,
+            )
+        };
+        check_if_zero(ret)
+            .map_err(|_| rustls::Error::PeerMisbehaved(PeerMisbehaved::InvalidKeyShare))?;
+
+        Ok(SharedSecret::from(&secret[..]))
+    }
+}
+
+impl ActiveKeyExchange for KeyExchangeMlKem {
+    fn complete(mut self: Box<Self>, peer_pub_key: &[u8]) -> Result<SharedSecret, rustls::Error> {
+        self.decapsulate(peer_pub_key)
+    }
+
+    fn pub_key(&self) -> &[u8] {
+        &self.pub_key_bytes
+    }
+
+    fn ffdhe_group(&self) -> Option<FfdheGroup<'static>> {
+        None
+    }
+
+    fn group(&self) -> NamedGroup {
+        self.group.name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mlkem768_kx() {
+        let client = Box::new(KeyExchangeMlKem::use_mlkem(&MLKEM768).unwrap());
+        assert_eq!(client.pub_key().len(), MLKEM768.pub_key_len());
+
+        let server = MLKEM768.start_and_complete(client.pub_key()).unwrap();
+        assert_eq!(server.pub_key.len(), MLKEM768.ciphertext_len());
+
+        let client_secret = client.complete(&server.pub_key).unwrap();
+
+        assert_eq!(client_secret.secret_bytes(), server.secret.secret_bytes());
+    }
+
+    #[test]
+    fn test_mlkem768_rejects_bad_share_len() {
+        let client = Box::new(KeyExchangeMlKem::use_mlkem(&MLKEM768).unwrap());
+
+        assert!(MLKEM768.start_and_complete(&client.pub_key()[1..]).is_err());
+        assert!(client.complete(&[0u8; 32]).is_err());
+    }
+
+    #[test]
+    fn test_mlkem1024_kx() {
+        let client = MLKEM1024.start().unwrap();
+        assert_eq!(client.pub_key().len(), MLKEM1024.pub_key_len());
+        assert_eq!(client.group(), NamedGroup::MLKEM1024);
+
+        let server = MLKEM1024.start_and_complete(client.pub_key()).unwrap();
+        assert_eq!(server.pub_key.len(), MLKEM1024.ciphertext_len());
+
+        let client_secret = client.complete(&server.pub_key).unwrap();
+
+        assert_eq!(client_secret.secret_bytes(), server.secret.secret_bytes());
+    }
+
+    #[test]
+    fn test_mlkem_parameter_sets_do_not_mix() {
+        let client = MLKEM768.start().unwrap();
+
+        // A 768 encapsulation key is never a valid 1024 one.
+        assert!(MLKEM1024.start_and_complete(client.pub_key()).is_err());
+    }
+}
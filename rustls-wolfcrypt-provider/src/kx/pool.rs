@@ -0,0 +1,320 @@
+use super::sec256r1::KeyExchangeSecP256r1;
+use super::x25519::KeyExchangeX25519;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use rustls::NamedGroup;
+use std::process;
+use std::sync::{Condvar, Mutex, MutexGuard, OnceLock};
+use std::thread::{self, JoinHandle};
+
+/// How the key share pool is sized, per group.
+#[derive(Clone, Copy, Debug)]
+pub struct KeySharePoolConfig {
+    /// How many key pairs are kept ready.
+    pub depth: usize,
+    /// Once a group is down to this many key pairs,
+    /// the background thread fills it up to `depth` again.
+    pub refill_threshold: usize,
+}
+
+impl Default for KeySharePoolConfig {
+    fn default() -> Self {
+        KeySharePoolConfig {
+            depth: 128,
+            refill_threshold: 32,
+        }
+    }
+}
+
+static POOL: OnceLock<Arc<Pool>> = OnceLock::new();
+
+/// Starts a background thread that keeps X25519 and secp256r1 key pairs
+/// ready, so that `start()` on those groups (and on the hybrids built
+/// on them) doesn't have to generate one while the handshake waits.
+/// When the pool runs dry, they fall back to generating inline.
+///
+/// Each key pair leaves the pool when it's taken, so none is ever used twice.
+/// A process forked from one with the pool enabled has a copy of the key
+/// pairs its parent hands out, so it drops them and generates its own inline.
+/// The pool can only be enabled once per process.
+pub fn enable(config: KeySharePoolConfig) -> Result<(), rustls::Error> {
+    let pool = Pool::start(config)?;
+
+    POOL.set(pool).map_err(|pool| {
+        pool.shutdown();
+        rustls::Error::General("key share pool already enabled".into())
+    })
+}
+
+/// Stops the background thread, and drops the key pairs it had ready
+/// (which wipes them); from then on key shares are generated inline.
+/// The pool can't be enabled again afterwards.
+pub fn disable() {
+    if let Some(pool) = POOL.get() {
+        pool.shutdown();
+    }
+}
+
+/// How many key pairs of `group` are ready to be taken.
+pub fn available(group: NamedGroup) -> usize {
+    POOL.get().map_or(0, |pool| pool.available(group))
+}
+
+pub(crate) fn take_x25519() -> Option<KeyExchangeX25519> {
+    POOL.get()?.take(|keys| &mut keys.x25519)
+}
+
+pub(crate) fn take_secp256r1() -> Option<KeyExchangeSecP256r1> {
+    POOL.get()?.take(|keys| &mut keys.secp256r1)
+}
+
+struct Pool {
+    config: KeySharePoolConfig,
+    keys: Mutex<Keys>,
+    refill: Condvar,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+struct Keys {
+    x25519: VecDeque<KeyExchangeX25519>,
+    secp256r1: VecDeque<KeyExchangeSecP256r1>,
+    /// The process the key pairs were generated in.
+    generated_by: u32,
+    shutdown: bool,
+}
+
+impl Pool {
+    /// A pool along with its refill thread, which runs until `shutdown()`.
+    fn start(config: KeySharePoolConfig) -> Result<Arc<Self>, rustls::Error> {
+        if config.depth == 0 || config.refill_threshold >= config.depth {
+            return Err(rustls::Error::General(
+                "invalid key share pool config".into(),
+            ));
+        }
+
+        let pool = Arc::new(Pool {
+            config,
+            keys: Mutex::new(Keys {
+                x25519: VecDeque::new(),
+                secp256r1: VecDeque::new(),
+                generated_by: process::id(),
+                shutdown: false,
+            }),
+            refill: Condvar::new(),
+            thread: Mutex::new(None),
+        });
+
+        let refilled = Arc::clone(&pool);
+        let thread = thread::Builder::new()
+            .name("wolfcrypt-key-share-pool".into())
+            .spawn(move || refilled.refill_until_shutdown())
+            .map_err(|_| rustls::Error::General("couldn't start the key share pool".into()))?;
+        *pool.thread.lock().unwrap() = Some(thread);
+
+        Ok(pool)
+    }
+
+    /// Tells the refill thread to stop, waits for it, and
+    /// drops whatever key pairs were left.
+    fn shutdown(&self) {
+        self.keys.lock().unwrap().shutdown = true;
+        self.refill.notify_one();
+
+        if let Some(thread) = self.thread.lock().unwrap().take() {
+            let _ = thread.join();
+        }
+
+        let mut keys = self.keys.lock().unwrap();
+        keys.x25519.clear();
+        keys.secp256r1.clear();
+    }
+
+    /// The key pairs, unless they were generated before a fork: the
+    /// parent hands out the same ones, so they're dropped. The refill
+    /// thread didn't come along, so the queues stay empty from then on.
+    fn lock_keys(&self) -> MutexGuard<'_, Keys> {
+        let mut keys = self.keys.lock().unwrap();
+
+        if keys.generated_by != process::id() {
+            keys.x25519.clear();
+            keys.secp256r1.clear();
+            keys.generated_by = process::id();
+            keys.shutdown = true;
+        }
+
+        keys
+    }
+
+    fn available(&self, group: NamedGroup) -> usize {
+        let keys = self.lock_keys();
+
+        match group {
+            NamedGroup::X25519 => keys.x25519.len(),
+            NamedGroup::secp256r1 => keys.secp256r1.len(),
+            _ => 0,
+        }
+    }
+
+    fn take<T>(&self, queue: fn(&mut Keys) -> &mut VecDeque<T>) -> Option<T> {
+        let mut keys = self.lock_keys();
+        let key = queue(&mut keys).pop_front();
+
+        if queue(&mut keys).len() <= self.config.refill_threshold {
+            self.refill.notify_one();
+        }
+
+        key
+    }
+
+    fn refill_until_shutdown(&self) {
+        let threshold = self.config.refill_threshold;
+
+        loop {
+            self.fill(|keys| &mut keys.x25519, KeyExchangeX25519::use_curve25519);
+            self.fill(
+                |keys| &mut keys.secp256r1,
+                KeyExchangeSecP256r1::use_secp256r1,
+            );
+
+            // Sleep until one of the groups is down to the threshold.
+            let keys = self.keys.lock().unwrap();
+            let keys = self
+                .refill
+                .wait_while(keys, |keys| {
+                    !keys.shutdown
+                        && keys.x25519.len() > threshold
+                        && keys.secp256r1.len() > threshold
+                })
+                .unwrap();
+
+            if keys.shutdown {
+                return;
+            }
+        }
+    }
+
+    /// Generates key pairs until the queue is at `depth`,
+    /// without holding the lock while doing so.
+    /// If generating fails, the queue is left as is until the next refill;
+    /// meanwhile `start()` generates inline and reports the error.
+    fn fill<T>(
+        &self,
+        queue: fn(&mut Keys) -> &mut VecDeque<T>,
+        generate: fn() -> Result<T, rustls::Error>,
+    ) {
+        loop {
+            {
+                let mut keys = self.keys.lock().unwrap();
+                if keys.shutdown || queue(&mut keys).len() >= self.config.depth {
+                    return;
+                }
+            }
+
+            let Ok(key) = generate() else {
+                return;
+            };
+            queue(&mut self.keys.lock().unwrap()).push_back(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+    use rustls::crypto::ActiveKeyExchange;
+    use std::time::{Duration, Instant};
+
+    fn wait_for(pool: &Pool, group: NamedGroup, count: usize) {
+        let start = Instant::now();
+
+        while pool.available(group) < count {
+            assert!(start.elapsed() < Duration::from_secs(30));
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    // A pool of its own, the process wide one is left alone.
+    #[test]
+    fn test_pool_hands_out_each_key_once() {
+        assert!(enable(KeySharePoolConfig {
+            depth: 4,
+            refill_threshold: 4,
+        })
+        .is_err());
+
+        let pool = Pool::start(KeySharePoolConfig {
+            depth: 4,
+            refill_threshold: 1,
+        })
+        .unwrap();
+
+        wait_for(&pool, NamedGroup::X25519, 4);
+        wait_for(&pool, NamedGroup::secp256r1, 4);
+
+        // More than the depth: the pool gets refilled in the
+        // background, or the keys are generated inline.
+        let mut pub_keys = Vec::new();
+        for _ in 0..16 {
+            let x25519 = match pool.take(|keys| &mut keys.x25519) {
+                Some(kx) => kx,
+                None => KeyExchangeX25519::use_curve25519().unwrap(),
+            };
+            let secp256r1 = match pool.take(|keys| &mut keys.secp256r1) {
+                Some(kx) => kx,
+                None => KeyExchangeSecP256r1::use_secp256r1().unwrap(),
+            };
+
+            for pub_key in [x25519.pub_key(), secp256r1.pub_key()] {
+                assert!(!pub_keys.contains(&pub_key.to_vec()));
+                pub_keys.push(pub_key.to_vec());
+            }
+        }
+
+        wait_for(&pool, NamedGroup::X25519, 4);
+        wait_for(&pool, NamedGroup::secp256r1, 4);
+
+        // A pooled key pair works like any other.
+        let alice = Box::new(pool.take(|keys| &mut keys.x25519).unwrap());
+        let bob = Box::new(pool.take(|keys| &mut keys.x25519).unwrap());
+        let bob_pub_key = bob.pub_key().to_vec();
+        assert_eq!(
+            bob.complete(alice.pub_key()).unwrap().secret_bytes(),
+            alice.complete(&bob_pub_key).unwrap().secret_bytes(),
+        );
+
+        // The thread stops, and the keys it left are gone.
+        pool.shutdown();
+        assert!(pool.thread.lock().unwrap().is_none());
+        assert_eq!(pool.available(NamedGroup::X25519), 0);
+        assert_eq!(pool.available(NamedGroup::secp256r1), 0);
+        assert!(pool.take(|keys| &mut keys.x25519).is_none());
+
+        // Only the thread's own reference was dropped with it.
+        assert_eq!(Arc::strong_count(&pool), 1);
+    }
+
+    #[test]
+    fn test_pool_drops_keys_generated_before_fork() {
+        let pool = Pool::start(KeySharePoolConfig {
+            depth: 4,
+            refill_threshold: 1,
+        })
+        .unwrap();
+        pool.shutdown();
+
+        // What a child sees: the parent's key pairs, under another pid.
+        {
+            let mut keys = pool.keys.lock().unwrap();
+            keys.x25519
+                .push_back(KeyExchangeX25519::use_curve25519().unwrap());
+            keys.secp256r1
+                .push_back(KeyExchangeSecP256r1::use_secp256r1().unwrap());
+            keys.generated_by = process::id().wrapping_add(1);
+        }
+
+        assert!(pool.take(|keys| &mut keys.x25519).is_none());
+        assert_eq!(pool.available(NamedGroup::secp256r1), 0);
+        assert!(pool.take(|keys| &mut keys.secp256r1).is_none());
+    }
+}
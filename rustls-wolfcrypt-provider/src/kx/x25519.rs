@@ -1,131 +1,194 @@
+use crate::random::PooledRng;
 use crate::{error::check_if_zero, types::*};
 use alloc::boxed::Box;
 use core::mem;
+use core::ptr;
 use foreign_types::ForeignType;
+use rustls::crypto::{CompletedKeyExchange, SharedSecret};
+use rustls::{NamedGroup, PeerMisbehaved};
 use wolfcrypt_rs::*;
+use zeroize::Zeroizing;
 
+/// The key pair is kept in the curve25519_key it was generated in
+/// until the key exchange completes: the private key is never exported.
+/// On drop the key is freed and wiped; this also goes for the key pairs
+/// waiting in the key share pool.
 pub struct KeyExchangeX25519 {
+    key: Box<curve25519_key>,
     pub_key_bytes: Box<[u8]>,
-    priv_key_bytes: Box<[u8]>,
 }
 
+// The curve25519_key is plain data, only ever touched through &mut self.
+unsafe impl Send for KeyExchangeX25519 {}
+unsafe impl Sync for KeyExchangeX25519 {}
+
 impl KeyExchangeX25519 {
-    pub fn use_curve25519() -> Self {
-        let mut key: curve25519_key = unsafe { mem::zeroed() };
-        let key_object = Curve25519KeyObject::new(&mut key);
-        let mut rng: WC_RNG = unsafe { mem::zeroed() };
-        let rng_object = WCRngObject::new(&mut rng);
+    pub fn use_curve25519() -> Result<Self, rustls::Error> {
+        let rng = PooledRng::acquire();
         let mut ret;
         let mut pub_key_raw: [u8; 32] = [0; 32];
         let mut pub_key_raw_len: word32 = pub_key_raw.len() as word32;
-        let mut priv_key_raw: [u8; 32] = [0; 32];
-        let mut priv_key_raw_len: word32 = priv_key_raw.len() as word32;
         let endian: u32 = EC25519_LITTLE_ENDIAN;
 
+        // From here on, dropping the key exchange frees the key.
+        let mut kx = KeyExchangeX25519 {
+            key: Box::new(unsafe { mem::zeroed() }),
+            pub_key_bytes: Box::new([]),
+        };
+        let key_object = Curve25519KeyObject::new(&mut kx.key);
+
         // We initialize the curve25519 key object.
         key_object.init();
 
-        // We initialize the rng object.
-        rng_object.init();
-
         // This function generates a Curve25519 key using the given random number generator, rng,
         // of the size given (keysize), and stores it in the given curve25519_key structure.
-        ret = unsafe { wc_curve25519_make_key(&mut rng, 32, key_object.as_ptr()) };
-        check_if_zero(ret).unwrap();
+        ret = rng.check(unsafe { wc_curve25519_make_key(rng.as_ptr(), 32, key_object.as_ptr()) });
+        check_if_zero(ret)
+            .map_err(|_| rustls::Error::General("wc_curve25519_make_key failed".into()))?;
 
-        // Export curve25519 key pair. Big or little endian.
+        // Only the public half is exported, it's our key share.
         ret = unsafe {
-            wc_curve25519_export_key_raw_ex(
+            wc_curve25519_export_public_ex(
                 key_object.as_ptr(),
-                priv_key_raw.as_mut_ptr(),
-                &mut priv_key_raw_len,
                 pub_key_raw.as_mut_ptr(),
                 &mut pub_key_raw_len,
                 endian.try_into().unwrap(),
             )
         };
-        check_if_zero(ret).unwrap();
+        check_if_zero(ret)
+            .map_err(|_| rustls::Error::General("wc_curve25519_export_public_ex failed".into()))?;
 
-        KeyExchangeX25519 {
-            pub_key_bytes: Box::new(pub_key_raw),
-            priv_key_bytes: Box::new(priv_key_raw),
-        }
+        kx.pub_key_bytes = Box::new(pub_key_raw);
+
+        Ok(kx)
     }
 
-    pub fn derive_shared_secret(&self, peer_pub_key: &[u8]) -> Box<[u8]> {
-        let mut ret;
-        let endian: u32 = EC25519_LITTLE_ENDIAN;
-        let mut pub_key_provided: curve25519_key = unsafe { mem::zeroed() };
-        let pub_key_provided_object = Curve25519KeyObject::new(&mut pub_key_provided);
-        let mut out: [u8; 32] = [0; 32];
-        let mut out_len: word32 = out.len() as word32;
-        let mut private_key: curve25519_key = unsafe { mem::zeroed() };
-        let private_key_object = Curve25519KeyObject::new(&mut private_key);
-
-        // This function checks that a public key buffer holds a valid
-        // Curve25519 key value given the endian ordering.
-        ret = unsafe {
-            wc_curve25519_check_public(peer_pub_key.as_ptr(), 32, endian.try_into().unwrap())
-        };
-        check_if_zero(ret).unwrap();
+    pub fn derive_shared_secret(
+        &mut self,
+        peer_pub_key: &[u8],
+    ) -> Result<SharedSecret, rustls::Error> {
+        let key_object = Curve25519KeyObject::new(&mut self.key);
 
-        // We initialize the curve25519 key object before we import the public key in it.
-        pub_key_provided_object.init();
+        shared_secret(&key_object, peer_pub_key)
+    }
 
-        // This function imports a public key from the given input buffer
-        // and stores it in the curve25519_key structure.
-        ret = unsafe {
-            wc_curve25519_import_public_ex(
-                peer_pub_key.as_ptr(),
-                32,
-                &mut pub_key_provided,
-                endian.try_into().unwrap(),
-            )
-        };
-        check_if_zero(ret).unwrap();
+    /// The server side, in one go: a fresh key is generated and used right
+    /// away on the peer's key share, without the private key ever leaving
+    /// the curve25519_key it was generated in. The key exchange owns the key,
+    /// so it's freed and wiped on the way out, whether or not this succeeds.
+    pub fn start_and_complete(peer_pub_key: &[u8]) -> Result<CompletedKeyExchange, rustls::Error> {
+        Self::use_curve25519()?.into_completed(peer_pub_key)
+    }
 
-        // We initialize the curve25519 key object before we import the private key in it.
-        private_key_object.init();
+    /// Same as `start_and_complete`, for a key pair that was generated ahead
+    /// of time (see the `pool` module).
+    pub fn into_completed(
+        mut self,
+        peer_pub_key: &[u8],
+    ) -> Result<CompletedKeyExchange, rustls::Error> {
+        let secret = self.derive_shared_secret(peer_pub_key)?;
 
-        // This function imports a private key from the given input buffer
-        // and stores it in the the curve25519_key structure.
-        ret = unsafe {
-            wc_curve25519_import_private_ex(
-                self.priv_key_bytes.as_ptr(),
-                32,
-                private_key_object.as_ptr(),
-                endian.try_into().unwrap(),
-            )
-        };
-        check_if_zero(ret).unwrap();
+        Ok(CompletedKeyExchange {
+            group: NamedGroup::X25519,
+            pub_key: self.pub_key_bytes.to_vec(),
+            secret,
+        })
+    }
+}
 
-        // This function computes a shared secret key given a secret private key and
-        // a received public key. Stores the generated secret in the buffer out.
-        ret = unsafe {
-            wc_curve25519_shared_secret_ex(
-                private_key_object.as_ptr(),
-                &mut pub_key_provided,
-                out.as_mut_ptr(),
-                &mut out_len,
-                endian.try_into().unwrap(),
-            )
-        };
-        check_if_zero(ret).unwrap();
+/// Computes the shared secret with the peer's public key, rejecting anything
+/// a well-behaved peer can't send (RFC 7748, 6.1): a key share of the wrong
+/// length, and points of low order, which give an all-zero shared secret
+/// whatever our private key is.
+fn shared_secret(
+    private_key_object: &Curve25519KeyObject,
+    peer_pub_key: &[u8],
+) -> Result<SharedSecret, rustls::Error> {
+    let mut pub_key_provided: curve25519_key = unsafe { mem::zeroed() };
+    let pub_key_provided_object = Curve25519KeyObject::new(&mut pub_key_provided);
+    let mut out = Zeroizing::new([0u8; 32]);
+    let mut out_len: word32 = out.len() as word32;
+    let endian: u32 = EC25519_LITTLE_ENDIAN;
+    let mut ret;
+
+    let Ok(mut peer_u) = <[u8; 32]>::try_from(peer_pub_key) else {
+        return Err(rustls::Error::PeerMisbehaved(
+            PeerMisbehaved::InvalidKeyShare,
+        ));
+    };
 
-        Box::new(out)
+    // RFC 7748, 5: the most significant bit of the u-coordinate is ignored,
+    // and non-canonical values (p = 2^255 - 19 up to 2^255 - 1) are taken
+    // mod p. wolfCrypt refuses both, so the bit is masked and u reduced here.
+    // p and p + 1 reduce to the low order points 0 and 1, which the all-zero
+    // check below catches; p + 2 and up are ordinary points.
+    peer_u[31] &= 0x7f;
+    if peer_u[0] >= 0xed && peer_u[1..31].iter().all(|&byte| byte == 0xff) && peer_u[31] == 0x7f {
+        // u - p, which is below 19.
+        let reduced = peer_u[0] - 0xed;
+        peer_u = [0; 32];
+        peer_u[0] = reduced;
+    }
+
+    // We initialize the curve25519 key object before we import the public key in it.
+    pub_key_provided_object.init();
+
+    // This function imports a public key from the given input buffer
+    // and stores it in the curve25519_key structure.
+    ret = unsafe {
+        wc_curve25519_import_public_ex(
+            peer_u.as_ptr(),
+            peer_u.len() as word32,
+            pub_key_provided_object.as_ptr(),
+            endian.try_into().unwrap(),
+        )
+    };
+    check_if_zero(ret)
+        .map_err(|_| rustls::Error::PeerMisbehaved(PeerMisbehaved::InvalidKeyShare))?;
+
+    // This function computes a shared secret key given a secret private key and
+    // a received public key. Stores the generated secret in the buffer out.
+    ret = unsafe {
+        wc_curve25519_shared_secret_ex(
+            private_key_object.as_ptr(),
+            pub_key_provided_object.as_ptr(),
+            out.as_mut_ptr(),
+            &mut out_len,
+            endian.try_into().unwrap(),
+        )
+    };
+    check_if_zero(ret)
+        .map_err(|_| rustls::Error::PeerMisbehaved(PeerMisbehaved::InvalidKeyShare))?;
+
+    // Recent wolfSSL versions already refuse to return an all-zero secret,
+    // but we don't want to depend on how it was configured. The check
+    // doesn't branch on the individual bytes.
+    if out.iter().fold(0u8, |acc, byte| acc | byte) == 0 {
+        return Err(rustls::Error::PeerMisbehaved(
+            PeerMisbehaved::InvalidKeyShare,
+        ));
+    }
+
+    Ok(SharedSecret::from(&out[..out_len as usize]))
+}
+
+impl Drop for KeyExchangeX25519 {
+    fn drop(&mut self) {
+        unsafe {
+            wc_curve25519_free(&mut *self.key);
+            ptr::write_volatile(&mut *self.key, mem::zeroed());
+        }
     }
 }
 
 impl rustls::crypto::ActiveKeyExchange for KeyExchangeX25519 {
     fn complete(
-        self: Box<Self>,
+        mut self: Box<Self>,
         peer_pub_key: &[u8],
     ) -> Result<rustls::crypto::SharedSecret, rustls::Error> {
         // We derive the shared secret with our private key and
         // the received public key.
-        let secret = self.derive_shared_secret(peer_pub_key);
-
-        Ok(rustls::crypto::SharedSecret::from(&*secret))
+        self.derive_shared_secret(peer_pub_key)
     }
 
     fn pub_key(&self) -> &[u8] {
@@ -140,16 +203,108 @@ impl rustls::crypto::ActiveKeyExchange for KeyExchangeX25519 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use hex_literal::hex;
     use rustls::crypto::ActiveKeyExchange;
 
     #[test]
     fn test_curve25519_kx() {
-        let alice = Box::new(KeyExchangeX25519::use_curve25519());
-        let bob = Box::new(KeyExchangeX25519::use_curve25519());
+        let mut alice = Box::new(KeyExchangeX25519::use_curve25519().unwrap());
+        let mut bob = Box::new(KeyExchangeX25519::use_curve25519().unwrap());
+        let alice_pub_key = alice.pub_key().to_vec();
+        let bob_pub_key = bob.pub_key().to_vec();
 
         assert_eq!(
-            alice.derive_shared_secret(bob.pub_key().try_into().unwrap()),
-            bob.derive_shared_secret(alice.pub_key().try_into().unwrap()),
+            alice
+                .derive_shared_secret(&bob_pub_key)
+                .unwrap()
+                .secret_bytes(),
+            bob.derive_shared_secret(&alice_pub_key)
+                .unwrap()
+                .secret_bytes(),
         )
     }
+
+    #[test]
+    fn test_curve25519_rejects_bad_pub_keys() {
+        let mut alice = Box::new(KeyExchangeX25519::use_curve25519().unwrap());
+        let alice_pub_key = alice.pub_key().to_vec();
+
+        // RFC 7748 has no list, these are the usual suspects: 0, 1, a point
+        // of order 8, p - 1, p and p + 1 (the last two reduce to 0 and 1).
+        let low_order = [
+            [0u8; 32],
+            hex!("0100000000000000000000000000000000000000000000000000000000000000"),
+            hex!("e0eb7a7c3b41b8ae1656e3faf19fc46ada098deb9c32b1fd866205165f49b800"),
+            hex!("ecffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff7f"),
+            hex!("edffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff7f"),
+            hex!("eeffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff7f"),
+        ];
+        for peer_pub_key in low_order {
+            assert_eq!(
+                alice.derive_shared_secret(&peer_pub_key).err(),
+                Some(rustls::Error::PeerMisbehaved(
+                    PeerMisbehaved::InvalidKeyShare
+                )),
+            );
+            assert!(KeyExchangeX25519::start_and_complete(&peer_pub_key).is_err());
+        }
+
+        // p + 9 is the base point, non-canonically: the shared
+        // secret is our own public key.
+        let base_point_plus_p =
+            hex!("f6ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff7f");
+        assert_eq!(
+            alice
+                .derive_shared_secret(&base_point_plus_p)
+                .unwrap()
+                .secret_bytes(),
+            &alice_pub_key[..],
+        );
+
+        assert!(alice.derive_shared_secret(&alice_pub_key[1..]).is_err());
+        assert!(alice.derive_shared_secret(&[]).is_err());
+        assert!(KeyExchangeX25519::start_and_complete(&[0u8; 32 + 1]).is_err());
+    }
+
+    /*
+     * RFC 7748, 5.2: the second test vector's u-coordinate has its top bit
+     * set, which has to be ignored rather than rejected.
+     * */
+    #[test]
+    fn test_curve25519_masks_top_bit() {
+        let scalar = hex!("4b66e9d4d1b4673c5ad22691957d6af5c11b6421e0ea01d42ca4169e7918ba0d");
+        let u = hex!("e5210f12786811d3f4b7959d0538ae2c31dbe7106fc03c3efc4cd549c715a493");
+        let expected = hex!("95cbde9476e8907d7aade45cb4b873f88b595a68799fa152e6f8f7647aac7957");
+
+        let mut key: curve25519_key = unsafe { mem::zeroed() };
+        let key_object = Curve25519KeyObject::new(&mut key);
+        key_object.init();
+
+        // wolfCrypt wants the scalar clamped already (RFC 7748, 5).
+        let mut clamped = scalar;
+        clamped[0] &= 248;
+        clamped[31] &= 127;
+        clamped[31] |= 64;
+        let ret = unsafe {
+            wc_curve25519_import_private_ex(
+                clamped.as_ptr(),
+                clamped.len() as word32,
+                key_object.as_ptr(),
+                EC25519_LITTLE_ENDIAN as i32,
+            )
+        };
+        check_if_zero(ret).unwrap();
+
+        assert_eq!(u[31] & 0x80, 0x80);
+        let secret = shared_secret(&key_object, &u).unwrap();
+        assert_eq!(secret.secret_bytes(), &expected);
+
+        // Same thing with the bit cleared.
+        let mut masked = u;
+        masked[31] &= 0x7f;
+        let secret = shared_secret(&key_object, &masked).unwrap();
+        assert_eq!(secret.secret_bytes(), &expected);
+
+        unsafe { wc_curve25519_free(key_object.as_ptr()) };
+    }
 }
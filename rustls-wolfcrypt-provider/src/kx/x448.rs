@@ -0,0 +1,255 @@
+use crate::random::PooledRng;
+use crate::{error::check_if_zero, types::*};
+use alloc::boxed::Box;
+use core::mem;
+use core::ptr;
+use foreign_types::ForeignType;
+use rustls::crypto::{CompletedKeyExchange, SharedSecret};
+use rustls::{NamedGroup, PeerMisbehaved};
+use wolfcrypt_rs::*;
+use zeroize::Zeroizing;
+
+/// The key pair is kept in the curve448_key it was generated in
+/// until the key exchange completes: the private key is never exported.
+/// On drop the key is freed and wiped.
+pub struct KeyExchangeX448 {
+    key: Box<curve448_key>,
+    pub_key_bytes: Box<[u8]>,
+}
+
+// The curve448_key is plain data, only ever touched through &mut self.
+unsafe impl Send for KeyExchangeX448 {}
+unsafe impl Sync for KeyExchangeX448 {}
+
+impl KeyExchangeX448 {
+    pub fn use_curve448() -> Result<Self, rustls::Error> {
+        let rng = PooledRng::acquire();
+        let mut ret;
+        let mut pub_key_raw: [u8; 56] = [0; 56];
+        let mut pub_key_raw_len: word32 = pub_key_raw.len() as word32;
+        let endian: u32 = EC448_LITTLE_ENDIAN;
+
+        // From here on, dropping the key exchange frees the key.
+        let mut kx = KeyExchangeX448 {
+            key: Box::new(unsafe { mem::zeroed() }),
+            pub_key_bytes: Box::new([]),
+        };
+        let key_object = Curve448KeyObject::new(&mut kx.key);
+
+        // We initialize the curve448 key object.
+        key_object.init();
+
+        // This function generates a Curve448 key using the given random number generator, rng,
+        // of the size given (keysize), and stores it in the given curve448_key structure.
+        ret = rng.check(unsafe { wc_curve448_make_key(rng.as_ptr(), 56, key_object.as_ptr()) });
+        check_if_zero(ret)
+            .map_err(|_| rustls::Error::General("wc_curve448_make_key failed".into()))?;
+
+        // Only the public half is exported, it's our key share.
+        ret = unsafe {
+            wc_curve448_export_public_ex(
+                key_object.as_ptr(),
+                pub_key_raw.as_mut_ptr(),
+                &mut pub_key_raw_len,
+                endian.try_into().unwrap(),
+            )
+        };
+        check_if_zero(ret)
+            .map_err(|_| rustls::Error::General("wc_curve448_export_public_ex failed".into()))?;
+
+        kx.pub_key_bytes = Box::new(pub_key_raw);
+
+        Ok(kx)
+    }
+
+    pub fn derive_shared_secret(
+        &mut self,
+        peer_pub_key: &[u8],
+    ) -> Result<SharedSecret, rustls::Error> {
+        let key_object = Curve448KeyObject::new(&mut self.key);
+
+        shared_secret(&key_object, peer_pub_key)
+    }
+
+    /// The server side, in one go: a fresh key is generated and used right
+    /// away on the peer's key share, without the private key ever leaving
+    /// the curve448_key it was generated in. The key exchange owns the key,
+    /// so it's freed and wiped on the way out, whether or not this succeeds.
+    pub fn start_and_complete(peer_pub_key: &[u8]) -> Result<CompletedKeyExchange, rustls::Error> {
+        Self::use_curve448()?.into_completed(peer_pub_key)
+    }
+
+    fn into_completed(
+        mut self,
+        peer_pub_key: &[u8],
+    ) -> Result<CompletedKeyExchange, rustls::Error> {
+        let secret = self.derive_shared_secret(peer_pub_key)?;
+
+        Ok(CompletedKeyExchange {
+            group: NamedGroup::X448,
+            pub_key: self.pub_key_bytes.to_vec(),
+            secret,
+        })
+    }
+}
+
+/// Validates the peer's public key and computes the shared secret with it,
+/// rejecting anything a well-behaved peer can't send (RFC 7748, 6.2):
+/// a key share of the wrong length, a u-coordinate that isn't a valid
+/// Curve448 public value, and points of low order, which give an all-zero
+/// shared secret whatever our private key is.
+fn shared_secret(
+    private_key_object: &Curve448KeyObject,
+    peer_pub_key: &[u8],
+) -> Result<SharedSecret, rustls::Error> {
+    let mut pub_key_provided: curve448_key = unsafe { mem::zeroed() };
+    let pub_key_provided_object = Curve448KeyObject::new(&mut pub_key_provided);
+    let mut out = Zeroizing::new([0u8; 56]);
+    let mut out_len: word32 = out.len() as word32;
+    let endian: u32 = EC448_LITTLE_ENDIAN;
+    let mut ret;
+
+    if peer_pub_key.len() != 56 {
+        return Err(rustls::Error::PeerMisbehaved(
+            PeerMisbehaved::InvalidKeyShare,
+        ));
+    }
+
+    // This function checks that a public key buffer holds a valid
+    // Curve448 key value given the endian ordering.
+    ret = unsafe {
+        wc_curve448_check_public(
+            peer_pub_key.as_ptr(),
+            peer_pub_key.len() as word32,
+            endian.try_into().unwrap(),
+        )
+    };
+    check_if_zero(ret)
+        .map_err(|_| rustls::Error::PeerMisbehaved(PeerMisbehaved::InvalidKeyShare))?;
+
+    // We initialize the curve448 key object before we import the public key in it.
+    pub_key_provided_object.init();
+
+    // This function imports a public key from the given input buffer
+    // and stores it in the curve448_key structure.
+    ret = unsafe {
+        wc_curve448_import_public_ex(
+            peer_pub_key.as_ptr(),
+            peer_pub_key.len() as word32,
+            pub_key_provided_object.as_ptr(),
+            endian.try_into().unwrap(),
+        )
+    };
+    check_if_zero(ret)
+        .map_err(|_| rustls::Error::PeerMisbehaved(PeerMisbehaved::InvalidKeyShare))?;
+
+    // This function computes a shared secret key given a secret private key and
+    // a received public key. Stores the generated secret in the buffer out.
+    ret = unsafe {
+        wc_curve448_shared_secret_ex(
+            private_key_object.as_ptr(),
+            pub_key_provided_object.as_ptr(),
+            out.as_mut_ptr(),
+            &mut out_len,
+            endian.try_into().unwrap(),
+        )
+    };
+    check_if_zero(ret)
+        .map_err(|_| rustls::Error::PeerMisbehaved(PeerMisbehaved::InvalidKeyShare))?;
+
+    // Recent wolfSSL versions already refuse to return an all-zero secret,
+    // but we don't want to depend on how it was configured. The check
+    // doesn't branch on the individual bytes.
+    if out.iter().fold(0u8, |acc, byte| acc | byte) == 0 {
+        return Err(rustls::Error::PeerMisbehaved(
+            PeerMisbehaved::InvalidKeyShare,
+        ));
+    }
+
+    Ok(SharedSecret::from(&out[..out_len as usize]))
+}
+
+impl Drop for KeyExchangeX448 {
+    fn drop(&mut self) {
+        unsafe {
+            wc_curve448_free(&mut *self.key);
+            ptr::write_volatile(&mut *self.key, mem::zeroed());
+        }
+    }
+}
+
+impl rustls::crypto::ActiveKeyExchange for KeyExchangeX448 {
+    fn complete(
+        mut self: Box<Self>,
+        peer_pub_key: &[u8],
+    ) -> Result<rustls::crypto::SharedSecret, rustls::Error> {
+        // We derive the shared secret with our private key and
+        // the received public key.
+        self.derive_shared_secret(peer_pub_key)
+    }
+
+    fn pub_key(&self) -> &[u8] {
+        &self.pub_key_bytes
+    }
+
+    fn group(&self) -> rustls::NamedGroup {
+        rustls::NamedGroup::X448
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+    use rustls::crypto::ActiveKeyExchange;
+
+    #[test]
+    fn test_curve448_kx() {
+        let mut alice = Box::new(KeyExchangeX448::use_curve448().unwrap());
+        let mut bob = Box::new(KeyExchangeX448::use_curve448().unwrap());
+        let alice_pub_key = alice.pub_key().to_vec();
+        let bob_pub_key = bob.pub_key().to_vec();
+
+        assert_eq!(
+            alice
+                .derive_shared_secret(&bob_pub_key)
+                .unwrap()
+                .secret_bytes(),
+            bob.derive_shared_secret(&alice_pub_key)
+                .unwrap()
+                .secret_bytes(),
+        )
+    }
+
+    #[test]
+    fn test_curve448_rejects_bad_pub_keys() {
+        let mut alice = Box::new(KeyExchangeX448::use_curve448().unwrap());
+        let alice_pub_key = alice.pub_key().to_vec();
+
+        // 0, 1 and p - 1, the u-coordinates of the points of low order.
+        let low_order = [
+            [0u8; 56],
+            hex!(
+                "0100000000000000000000000000000000000000000000000000000000000000"
+                "000000000000000000000000000000000000000000000000"
+            ),
+            hex!(
+                "fefffffffffffffffffffffffffffffffffffffffffffffffffffffffeffffff"
+                "ffffffffffffffffffffffffffffffffffffffffffffffff"
+            ),
+        ];
+        for peer_pub_key in low_order {
+            assert_eq!(
+                alice.derive_shared_secret(&peer_pub_key).err(),
+                Some(rustls::Error::PeerMisbehaved(
+                    PeerMisbehaved::InvalidKeyShare
+                )),
+            );
+            assert!(KeyExchangeX448::start_and_complete(&peer_pub_key).is_err());
+        }
+
+        assert!(alice.derive_shared_secret(&alice_pub_key[1..]).is_err());
+        assert!(alice.derive_shared_secret(&[]).is_err());
+        assert!(KeyExchangeX448::start_and_complete(&[0u8; 56 + 1]).is_err());
+    }
+}
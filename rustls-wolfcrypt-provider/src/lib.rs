@@ -1,6 +1,6 @@
 #![cfg_attr(not(test), no_std)]
 
-#[cfg(test)]
+#[cfg(any(test, feature = "std"))]
 extern crate std;
 
 extern crate alloc;
@@ -11,25 +11,36 @@ use alloc::vec;
 use alloc::vec::Vec;
 use rustls::crypto::CryptoProvider;
 use rustls::pki_types::PrivateKeyDer;
+#[cfg(feature = "compress")]
+pub mod compress;
 pub mod error;
 mod hkdf;
-mod kx;
+pub mod hpke;
+pub mod kx;
 mod prf;
+mod quic;
 mod random;
+pub mod ticketer;
 mod verify;
 use crate::hkdf::WCHkdfUsingHmac;
 use crate::prf::WCPrfUsingHmac;
 pub mod aead {
+    pub mod aes128ccm;
     pub mod aes128gcm;
+    pub mod aes256ccm;
     pub mod aes256gcm;
     pub mod chacha20;
+    pub(crate) mod context;
+    #[cfg(feature = "integrity-only")]
+    pub mod integrity;
 }
 pub mod sign {
     pub mod ecdsa;
     pub mod eddsa;
+    pub mod mldsa;
     pub mod rsa;
 }
-use crate::aead::{aes128gcm, aes256gcm, chacha20};
+use crate::aead::{aes128ccm, aes128gcm, aes256ccm, aes256gcm, chacha20};
 
 pub mod hash {
     pub mod sha256;
@@ -95,6 +106,8 @@ impl rustls::crypto::KeyProvider for Provider {
             Box::new(|key| sign::ecdsa::EcdsaSigningKey::try_from(key).map(|x| Arc::new(x) as _)),
             Box::new(|key| sign::rsa::RsaPrivateKey::try_from(key).map(|x| Arc::new(x) as _)),
             Box::new(|key| sign::eddsa::Ed25519PrivateKey::try_from(key).map(|x| Arc::new(x) as _)),
+            Box::new(|key| sign::eddsa::Ed448PrivateKey::try_from(key).map(|x| Arc::new(x) as _)),
+            Box::new(|key| sign::mldsa::MlDsaPrivateKey::try_from(key).map(|x| Arc::new(x) as _)),
         ];
 
         for algorithm in algorithms {
@@ -111,16 +124,25 @@ impl rustls::crypto::KeyProvider for Provider {
     }
 }
 
+/// The suites `provider()` offers. The CCM_8 suites aren't among them: their
+/// 8 byte tag is too short for general use (RFC 8446, B.4), so they have to be
+/// put in `CryptoProvider::cipher_suites` explicitly.
 static ALL_CIPHER_SUITES: &[rustls::SupportedCipherSuite] = &[
     TLS13_CHACHA20_POLY1305_SHA256,
     TLS13_AES_128_GCM_SHA256,
     TLS13_AES_256_GCM_SHA384,
+    TLS13_AES_128_CCM_SHA256,
     TLS12_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256,
     TLS12_ECDHE_RSA_WITH_AES_128_GCM_SHA256,
     TLS12_ECDHE_RSA_WITH_AES_256_GCM_SHA384,
     TLS12_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256,
     TLS12_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256,
     TLS12_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384,
+    TLS12_ECDHE_ECDSA_WITH_AES_128_CCM,
+    TLS12_ECDHE_ECDSA_WITH_AES_256_CCM,
+    TLS12_DHE_RSA_WITH_CHACHA20_POLY1305_SHA256,
+    TLS12_DHE_RSA_WITH_AES_128_GCM_SHA256,
+    TLS12_DHE_RSA_WITH_AES_256_GCM_SHA384,
 ];
 
 static ALL_RSA_SCHEMES: &[rustls::SignatureScheme] = &[
@@ -137,6 +159,7 @@ static ALL_ECDSA_SCHEMES: &[rustls::SignatureScheme] = &[
     rustls::SignatureScheme::ECDSA_NISTP384_SHA384,
     rustls::SignatureScheme::ECDSA_NISTP521_SHA512,
     rustls::SignatureScheme::ED25519,
+    rustls::SignatureScheme::ED448,
 ];
 
 pub static TLS13_CHACHA20_POLY1305_SHA256: rustls::SupportedCipherSuite =
@@ -148,7 +171,7 @@ pub static TLS13_CHACHA20_POLY1305_SHA256: rustls::SupportedCipherSuite =
         },
         hkdf_provider: &WCHkdfUsingHmac(WCShaHmac::Sha256),
         aead_alg: &chacha20::Chacha20Poly1305,
-        quic: None,
+        quic: Some(&quic::CHACHA20_POLY1305),
     });
 
 pub static TLS13_AES_128_GCM_SHA256: rustls::SupportedCipherSuite =
@@ -160,7 +183,7 @@ pub static TLS13_AES_128_GCM_SHA256: rustls::SupportedCipherSuite =
         },
         hkdf_provider: &WCHkdfUsingHmac(WCShaHmac::Sha256),
         aead_alg: &aes128gcm::Aes128Gcm,
-        quic: None,
+        quic: Some(&quic::AES128_GCM),
     });
 
 pub static TLS13_AES_256_GCM_SHA384: rustls::SupportedCipherSuite =
@@ -172,6 +195,46 @@ pub static TLS13_AES_256_GCM_SHA384: rustls::SupportedCipherSuite =
         },
         hkdf_provider: &WCHkdfUsingHmac(WCShaHmac::Sha384),
         aead_alg: &aes256gcm::Aes256Gcm,
+        quic: Some(&quic::AES256_GCM),
+    });
+
+pub static TLS13_AES_128_CCM_SHA256: rustls::SupportedCipherSuite =
+    rustls::SupportedCipherSuite::Tls13(&rustls::Tls13CipherSuite {
+        common: rustls::crypto::CipherSuiteCommon {
+            suite: rustls::CipherSuite::TLS13_AES_128_CCM_SHA256,
+            hash_provider: &sha256::WCSha256,
+            confidentiality_limit: 1 << 23,
+        },
+        hkdf_provider: &WCHkdfUsingHmac(WCShaHmac::Sha256),
+        aead_alg: &aes128ccm::Aes128Ccm,
+        quic: None,
+    });
+
+pub static TLS13_AES_128_CCM_8_SHA256: rustls::SupportedCipherSuite =
+    rustls::SupportedCipherSuite::Tls13(&rustls::Tls13CipherSuite {
+        common: rustls::crypto::CipherSuiteCommon {
+            suite: rustls::CipherSuite::TLS13_AES_128_CCM_8_SHA256,
+            hash_provider: &sha256::WCSha256,
+            confidentiality_limit: 1 << 23,
+        },
+        hkdf_provider: &WCHkdfUsingHmac(WCShaHmac::Sha256),
+        aead_alg: &aes128ccm::Aes128Ccm8,
+        quic: None,
+    });
+
+/// Integrity-only suite from RFC 9150: records are authenticated with
+/// HMAC-SHA256 but sent in the clear.
+/// It's not part of the default provider, add it to `cipher_suites` explicitly.
+#[cfg(feature = "integrity-only")]
+pub static TLS_SHA256_SHA256: rustls::SupportedCipherSuite =
+    rustls::SupportedCipherSuite::Tls13(&rustls::Tls13CipherSuite {
+        common: rustls::crypto::CipherSuiteCommon {
+            suite: rustls::CipherSuite::Unknown(0xc0b4),
+            hash_provider: &sha256::WCSha256,
+            confidentiality_limit: u64::MAX,
+        },
+        hkdf_provider: &WCHkdfUsingHmac(WCShaHmac::Sha256),
+        aead_alg: &aead::integrity::Sha256Hmac,
         quic: None,
     });
 
@@ -252,3 +315,95 @@ pub static TLS12_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384: rustls::SupportedCipherSui
         kx: rustls::crypto::KeyExchangeAlgorithm::ECDHE,
         sign: ALL_ECDSA_SCHEMES,
     });
+
+// RFC 7251 uses the SHA-256 PRF for all the CCM suites, AES-256 included.
+pub static TLS12_ECDHE_ECDSA_WITH_AES_128_CCM: rustls::SupportedCipherSuite =
+    rustls::SupportedCipherSuite::Tls12(&rustls::Tls12CipherSuite {
+        common: rustls::crypto::CipherSuiteCommon {
+            suite: rustls::CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_128_CCM,
+            hash_provider: &sha256::WCSha256,
+            confidentiality_limit: 1 << 23,
+        },
+        aead_alg: &aes128ccm::Aes128Ccm,
+        prf_provider: &WCPrfUsingHmac(WCShaHmac::Sha256),
+        kx: rustls::crypto::KeyExchangeAlgorithm::ECDHE,
+        sign: ALL_ECDSA_SCHEMES,
+    });
+
+pub static TLS12_ECDHE_ECDSA_WITH_AES_256_CCM: rustls::SupportedCipherSuite =
+    rustls::SupportedCipherSuite::Tls12(&rustls::Tls12CipherSuite {
+        common: rustls::crypto::CipherSuiteCommon {
+            suite: rustls::CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_256_CCM,
+            hash_provider: &sha256::WCSha256,
+            confidentiality_limit: 1 << 23,
+        },
+        aead_alg: &aes256ccm::Aes256Ccm,
+        prf_provider: &WCPrfUsingHmac(WCShaHmac::Sha256),
+        kx: rustls::crypto::KeyExchangeAlgorithm::ECDHE,
+        sign: ALL_ECDSA_SCHEMES,
+    });
+
+pub static TLS12_ECDHE_ECDSA_WITH_AES_128_CCM_8: rustls::SupportedCipherSuite =
+    rustls::SupportedCipherSuite::Tls12(&rustls::Tls12CipherSuite {
+        common: rustls::crypto::CipherSuiteCommon {
+            suite: rustls::CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_128_CCM_8,
+            hash_provider: &sha256::WCSha256,
+            confidentiality_limit: 1 << 23,
+        },
+        aead_alg: &aes128ccm::Aes128Ccm8,
+        prf_provider: &WCPrfUsingHmac(WCShaHmac::Sha256),
+        kx: rustls::crypto::KeyExchangeAlgorithm::ECDHE,
+        sign: ALL_ECDSA_SCHEMES,
+    });
+
+pub static TLS12_ECDHE_ECDSA_WITH_AES_256_CCM_8: rustls::SupportedCipherSuite =
+    rustls::SupportedCipherSuite::Tls12(&rustls::Tls12CipherSuite {
+        common: rustls::crypto::CipherSuiteCommon {
+            suite: rustls::CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_256_CCM_8,
+            hash_provider: &sha256::WCSha256,
+            confidentiality_limit: 1 << 23,
+        },
+        aead_alg: &aes256ccm::Aes256Ccm8,
+        prf_provider: &WCPrfUsingHmac(WCShaHmac::Sha256),
+        kx: rustls::crypto::KeyExchangeAlgorithm::ECDHE,
+        sign: ALL_ECDSA_SCHEMES,
+    });
+
+pub static TLS12_DHE_RSA_WITH_CHACHA20_POLY1305_SHA256: rustls::SupportedCipherSuite =
+    rustls::SupportedCipherSuite::Tls12(&rustls::Tls12CipherSuite {
+        common: rustls::crypto::CipherSuiteCommon {
+            suite: rustls::CipherSuite::TLS_DHE_RSA_WITH_CHACHA20_POLY1305_SHA256,
+            hash_provider: &sha256::WCSha256,
+            confidentiality_limit: u64::MAX,
+        },
+        aead_alg: &chacha20::Chacha20Poly1305,
+        prf_provider: &WCPrfUsingHmac(WCShaHmac::Sha256),
+        kx: rustls::crypto::KeyExchangeAlgorithm::DHE,
+        sign: ALL_RSA_SCHEMES,
+    });
+
+pub static TLS12_DHE_RSA_WITH_AES_128_GCM_SHA256: rustls::SupportedCipherSuite =
+    rustls::SupportedCipherSuite::Tls12(&rustls::Tls12CipherSuite {
+        common: rustls::crypto::CipherSuiteCommon {
+            suite: rustls::CipherSuite::TLS_DHE_RSA_WITH_AES_128_GCM_SHA256,
+            hash_provider: &sha256::WCSha256,
+            confidentiality_limit: 1 << 23,
+        },
+        aead_alg: &aes128gcm::Aes128Gcm,
+        prf_provider: &WCPrfUsingHmac(WCShaHmac::Sha256),
+        kx: rustls::crypto::KeyExchangeAlgorithm::DHE,
+        sign: ALL_RSA_SCHEMES,
+    });
+
+pub static TLS12_DHE_RSA_WITH_AES_256_GCM_SHA384: rustls::SupportedCipherSuite =
+    rustls::SupportedCipherSuite::Tls12(&rustls::Tls12CipherSuite {
+        common: rustls::crypto::CipherSuiteCommon {
+            suite: rustls::CipherSuite::TLS_DHE_RSA_WITH_AES_256_GCM_SHA384,
+            hash_provider: &sha384::WCSha384,
+            confidentiality_limit: 1 << 23,
+        },
+        aead_alg: &aes256gcm::Aes256Gcm,
+        prf_provider: &WCPrfUsingHmac(WCShaHmac::Sha384),
+        kx: rustls::crypto::KeyExchangeAlgorithm::DHE,
+        sign: ALL_RSA_SCHEMES,
+    });
@@ -0,0 +1,547 @@
+use crate::aead::context::{
+    AeadContext, AesBlockContext, AesGcmContext, ChaCha20Context, ChaCha20Poly1305Context, Locked,
+    AES_BLOCK_LENGTH,
+};
+use alloc::boxed::Box;
+use rustls::crypto::cipher::{AeadKey, Iv, Nonce};
+use rustls::quic;
+use rustls::Error;
+
+const TAG_LENGTH: usize = 16;
+const SAMPLE_LENGTH: usize = 16;
+
+/// The packet protection AEAD, and with it the header protection cipher
+/// (RFC 9001, 5.4.3 and 5.4.4).
+#[derive(Clone, Copy)]
+enum Cipher {
+    AesGcm,
+    ChaCha20Poly1305,
+}
+
+pub struct KeyBuilder {
+    cipher: Cipher,
+    key_len: usize,
+    confidentiality_limit: u64,
+    integrity_limit: u64,
+}
+
+// Limits from RFC 9001, 6.6.
+pub static AES128_GCM: KeyBuilder = KeyBuilder {
+    cipher: Cipher::AesGcm,
+    key_len: 16,
+    confidentiality_limit: 1 << 23,
+    integrity_limit: 1 << 52,
+};
+
+pub static AES256_GCM: KeyBuilder = KeyBuilder {
+    cipher: Cipher::AesGcm,
+    key_len: 32,
+    confidentiality_limit: 1 << 23,
+    integrity_limit: 1 << 52,
+};
+
+pub static CHACHA20_POLY1305: KeyBuilder = KeyBuilder {
+    cipher: Cipher::ChaCha20Poly1305,
+    key_len: 32,
+    confidentiality_limit: u64::MAX,
+    integrity_limit: 1 << 36,
+};
+
+impl quic::Algorithm for KeyBuilder {
+    fn packet_key(&self, key: AeadKey, iv: Iv) -> Box<dyn quic::PacketKey> {
+        Box::new(PacketKey::new(self, key.as_ref(), iv))
+    }
+
+    fn header_protection_key(&self, key: AeadKey) -> Box<dyn quic::HeaderProtectionKey> {
+        Box::new(HeaderProtectionKey::new(self, key.as_ref()))
+    }
+
+    fn aead_key_len(&self) -> usize {
+        self.key_len
+    }
+}
+
+/// The header protection cipher, keyed once when the key is installed.
+enum HeaderProtectionContext {
+    Aes(AesBlockContext),
+    ChaCha20(ChaCha20Context),
+}
+
+pub struct HeaderProtectionKey(Locked<HeaderProtectionContext>);
+
+impl HeaderProtectionKey {
+    fn new(builder: &KeyBuilder, key: &[u8]) -> Self {
+        let context = match builder.cipher {
+            Cipher::AesGcm => HeaderProtectionContext::Aes(AesBlockContext::new(key)),
+            Cipher::ChaCha20Poly1305 => {
+                HeaderProtectionContext::ChaCha20(ChaCha20Context::new(key))
+            }
+        };
+
+        HeaderProtectionKey(Locked::new(context))
+    }
+
+    /// Computes the 5 byte mask out of the sample, as per RFC 9001, 5.4.3 (AES)
+    /// and 5.4.4 (ChaCha20).
+    fn new_mask(&self, sample: &[u8]) -> Result<[u8; 5], Error> {
+        if sample.len() != SAMPLE_LENGTH {
+            return Err(Error::General("sample of invalid length".into()));
+        }
+
+        let mut mask = [0u8; 5];
+
+        match &mut *self.0.lock() {
+            HeaderProtectionContext::Aes(context) => {
+                // mask = AES-ECB(hp_key, sample), a single block.
+                let mut block = [0u8; AES_BLOCK_LENGTH];
+                block.copy_from_slice(sample);
+
+                context
+                    .encrypt_block(&mut block)
+                    .map_err(|_| Error::General("wc_AesEncryptDirect failed".into()))?;
+
+                mask.copy_from_slice(&block[..5]);
+            }
+            HeaderProtectionContext::ChaCha20(context) => {
+                // The first 4 bytes of the sample are the block counter
+                // (little endian), the other 12 the nonce.
+                // mask = ChaCha20(hp_key, counter, nonce, {0,0,0,0,0})
+                let counter = u32::from_le_bytes(sample[..4].try_into().unwrap());
+
+                context
+                    .apply_keystream(&sample[4..], counter, &mut mask)
+                    .map_err(|_| Error::General("wc_Chacha_Process failed".into()))?;
+            }
+        }
+
+        Ok(mask)
+    }
+
+    /// "Header Protection Application" from RFC 9001, 5.4.1.
+    fn xor_in_place(
+        &self,
+        sample: &[u8],
+        first: &mut u8,
+        packet_number: &mut [u8],
+        masked: bool,
+    ) -> Result<(), Error> {
+        let mask = self.new_mask(sample)?;
+        let (first_mask, pn_mask) = mask.split_first().unwrap();
+
+        if packet_number.len() > pn_mask.len() {
+            return Err(Error::General("packet number too long".into()));
+        }
+
+        const LONG_HEADER_FORM: u8 = 0x80;
+        let bits = match *first & LONG_HEADER_FORM == LONG_HEADER_FORM {
+            true => 0x0f,  // Long header: 4 bits masked
+            false => 0x1f, // Short header: 5 bits masked
+        };
+
+        // The packet number length is only readable
+        // once the first byte is unprotected.
+        let first_plain = match masked {
+            true => *first ^ (first_mask & bits),
+            false => *first,
+        };
+        let pn_len = (first_plain & 0x03) as usize + 1;
+
+        *first ^= first_mask & bits;
+        for (dst, m) in packet_number.iter_mut().zip(pn_mask).take(pn_len) {
+            *dst ^= m;
+        }
+
+        Ok(())
+    }
+}
+
+impl quic::HeaderProtectionKey for HeaderProtectionKey {
+    fn encrypt_in_place(
+        &self,
+        sample: &[u8],
+        first: &mut u8,
+        packet_number: &mut [u8],
+    ) -> Result<(), Error> {
+        self.xor_in_place(sample, first, packet_number, false)
+    }
+
+    fn decrypt_in_place(
+        &self,
+        sample: &[u8],
+        first: &mut u8,
+        packet_number: &mut [u8],
+    ) -> Result<(), Error> {
+        self.xor_in_place(sample, first, packet_number, true)
+    }
+
+    fn sample_len(&self) -> usize {
+        SAMPLE_LENGTH
+    }
+}
+
+pub struct PacketKey {
+    context: Locked<AeadContext>,
+    iv: Iv,
+    confidentiality_limit: u64,
+    integrity_limit: u64,
+}
+
+impl PacketKey {
+    fn new(builder: &KeyBuilder, key: &[u8], iv: Iv) -> Self {
+        let context = match builder.cipher {
+            Cipher::AesGcm => AeadContext::AesGcm(AesGcmContext::new(key)),
+            Cipher::ChaCha20Poly1305 => {
+                AeadContext::ChaCha20Poly1305(ChaCha20Poly1305Context::new(key))
+            }
+        };
+
+        PacketKey {
+            context: Locked::new(context),
+            iv,
+            confidentiality_limit: builder.confidentiality_limit,
+            integrity_limit: builder.integrity_limit,
+        }
+    }
+}
+
+impl quic::PacketKey for PacketKey {
+    fn encrypt_in_place(
+        &self,
+        packet_number: u64,
+        header: &[u8],
+        payload: &mut [u8],
+    ) -> Result<quic::Tag, Error> {
+        let nonce = Nonce::new(&self.iv, packet_number);
+        let mut auth_tag = [0u8; TAG_LENGTH];
+
+        // The header is the additional data.
+        self.context
+            .lock()
+            .seal_in_place(&nonce.0, header, payload, &mut auth_tag)
+            .map_err(|_| Error::EncryptError)?;
+
+        Ok(quic::Tag::from(&auth_tag[..]))
+    }
+
+    fn decrypt_in_place<'a>(
+        &self,
+        packet_number: u64,
+        header: &[u8],
+        payload: &'a mut [u8],
+    ) -> Result<&'a [u8], Error> {
+        if payload.len() < TAG_LENGTH {
+            return Err(Error::DecryptError);
+        }
+
+        let nonce = Nonce::new(&self.iv, packet_number);
+        let plain_len = payload.len() - TAG_LENGTH;
+        let (cipher_text, auth_tag) = payload.split_at_mut(plain_len);
+
+        self.context
+            .lock()
+            .open_in_place(&nonce.0, header, cipher_text, auth_tag)
+            .map_err(|_| Error::DecryptError)?;
+
+        Ok(&payload[..plain_len])
+    }
+
+    fn tag_len(&self) -> usize {
+        TAG_LENGTH
+    }
+
+    fn confidentiality_limit(&self) -> u64 {
+        self.confidentiality_limit
+    }
+
+    fn integrity_limit(&self) -> u64 {
+        self.integrity_limit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use hex_literal::hex;
+    use rustls::quic::{HeaderProtectionKey as _, PacketKey as _};
+
+    // Outside of rustls an AeadKey can only be built out of 32 bytes,
+    // so for AES-128 the keys are put together by hand.
+    fn packet_key(builder: &KeyBuilder, key: &[u8], iv: [u8; 12]) -> PacketKey {
+        PacketKey::new(builder, key, Iv::new(iv))
+    }
+
+    fn header_protection_key(builder: &KeyBuilder, key: &[u8]) -> HeaderProtectionKey {
+        HeaderProtectionKey::new(builder, key)
+    }
+
+    /*
+     * RFC 9001, A.2: the client Initial header protection.
+     * */
+    #[test]
+    fn test_rfc9001_a2_client_initial_header_protection() {
+        let hp = header_protection_key(&AES128_GCM, &hex!("9f50449e04a0e810283a1e9933adedd2"));
+        let sample = hex!("d1b1c98dd7689fb8ec11d242b123dc9b");
+
+        let mut first = 0xc3;
+        let mut packet_number = hex!("00000002");
+        hp.encrypt_in_place(&sample, &mut first, &mut packet_number)
+            .unwrap();
+
+        assert_eq!(first, 0xc0);
+        assert_eq!(packet_number, hex!("7b9aec34"));
+
+        hp.decrypt_in_place(&sample, &mut first, &mut packet_number)
+            .unwrap();
+
+        assert_eq!(first, 0xc3);
+        assert_eq!(packet_number, hex!("00000002"));
+    }
+
+    /*
+     * RFC 9001, A.2: the whole client Initial, the CRYPTO frame
+     * padded to 1162 bytes, packet and header protection.
+     * */
+    #[test]
+    fn test_rfc9001_a2_client_initial() {
+        let key = hex!("1f369613dd76d5467730efcbe3b1a22d");
+        let iv = hex!("fa044b2f42a3fd3b46fb255c");
+        let hp_key = hex!("9f50449e04a0e810283a1e9933adedd2");
+        let crypto_frame = hex!(
+            "060040f1010000ed0303ebf8fa56f12939b9584a3896472ec40bb863cfd3e868"
+            "04fe3a47f06a2b69484c00000413011302010000c000000010000e00000b6578"
+            "616d706c652e636f6dff01000100000a00080006001d00170018001000070005"
+            "04616c706e000500050100000000003300260024001d00209370b2c9caa47fba"
+            "baf4559fedba753de171fa71f50f1ce15d43e994ec74d748002b000302030400"
+            "0d0010000e0403050306030203080408050806002d00020101001c0002400100"
+            "3900320408ffffffffffffffff05048000ffff07048000ffff08011001048000"
+            "75300901100f088394c8f03e51570806048000ffff"
+        );
+        let header = hex!("c300000001088394c8f03e5157080000449e00000002");
+        let protected = hex!(
+            "c000000001088394c8f03e5157080000449e7b9aec34d1b1c98dd7689fb8ec11"
+            "d242b123dc9bd8bab936b47d92ec356c0bab7df5976d27cd449f63300099f399"
+            "1c260ec4c60d17b31f8429157bb35a1282a643a8d2262cad67500cadb8e7378c"
+            "8eb7539ec4d4905fed1bee1fc8aafba17c750e2c7ace01e6005f80fcb7df6212"
+            "30c83711b39343fa028cea7f7fb5ff89eac2308249a02252155e2347b63d58c5"
+            "457afd84d05dfffdb20392844ae812154682e9cf012f9021a6f0be17ddd0c208"
+            "4dce25ff9b06cde535d0f920a2db1bf362c23e596d11a4f5a6cf3948838a3aec"
+            "4e15daf8500a6ef69ec4e3feb6b1d98e610ac8b7ec3faf6ad760b7bad1db4ba3"
+            "485e8a94dc250ae3fdb41ed15fb6a8e5eba0fc3dd60bc8e30c5c4287e53805db"
+            "059ae0648db2f64264ed5e39be2e20d82df566da8dd5998ccabdae053060ae6c"
+            "7b4378e846d29f37ed7b4ea9ec5d82e7961b7f25a9323851f681d582363aa5f8"
+            "9937f5a67258bf63ad6f1a0b1d96dbd4faddfcefc5266ba6611722395c906556"
+            "be52afe3f565636ad1b17d508b73d8743eeb524be22b3dcbc2c7468d54119c74"
+            "68449a13d8e3b95811a198f3491de3e7fe942b330407abf82a4ed7c1b311663a"
+            "c69890f4157015853d91e923037c227a33cdd5ec281ca3f79c44546b9d90ca00"
+            "f064c99e3dd97911d39fe9c5d0b23a229a234cb36186c4819e8b9c5927726632"
+            "291d6a418211cc2962e20fe47feb3edf330f2c603a9d48c0fcb5699dbfe58964"
+            "25c5bac4aee82e57a85aaf4e2513e4f05796b07ba2ee47d80506f8d2c25e50fd"
+            "14de71e6c418559302f939b0e1abd576f279c4b2e0feb85c1f28ff18f58891ff"
+            "ef132eef2fa09346aee33c28eb130ff28f5b766953334113211996d20011a198"
+            "e3fc433f9f2541010ae17c1bf202580f6047472fb36857fe843b19f5984009dd"
+            "c324044e847a4f4a0ab34f719595de37252d6235365e9b84392b061085349d73"
+            "203a4a13e96f5432ec0fd4a1ee65accdd5e3904df54c1da510b0ff20dcc0c77f"
+            "cb2c0e0eb605cb0504db87632cf3d8b4dae6e705769d1de354270123cb11450e"
+            "fc60ac47683d7b8d0f811365565fd98c4c8eb936bcab8d069fc33bd801b03ade"
+            "a2e1fbc5aa463d08ca19896d2bf59a071b851e6c239052172f296bfb5e724047"
+            "90a2181014f3b94a4e97d117b438130368cc39dbb2d198065ae3986547926cd2"
+            "162f40a29f0c3c8745c0f50fba3852e566d44575c29d39a03f0cda721984b6f4"
+            "40591f355e12d439ff150aab7613499dbd49adabc8676eef023b15b65bfc5ca0"
+            "6948109f23f350db82123535eb8a7433bdabcb909271a6ecbcb58b936a88cd4e"
+            "8f2e6ff5800175f113253d8fa9ca8885c2f552e657dc603f252e1a8e308f76f0"
+            "be79e2fb8f5d5fbbe2e30ecadd220723c8c0aea8078cdfcb3868263ff8f09400"
+            "54da48781893a7e49ad5aff4af300cd804a6b6279ab3ff3afb64491c85194aab"
+            "760d58a606654f9f4400e8b38591356fbf6425aca26dc85244259ff2b19c41b9"
+            "f96f3ca9ec1dde434da7d2d392b905ddf3d1f9af93d1af5950bd493f5aa731b4"
+            "056df31bd267b6b90a079831aaf579be0a39013137aac6d404f518cfd4684064"
+            "7e78bfe706ca4cf5e9c5453e9f7cfd2b8b4c8d169a44e55c88d4a9a7f9474241"
+            "e221af44860018ab0856972e194cd934"
+        );
+
+        let packet_key = packet_key(&AES128_GCM, &key, iv);
+        let hp = header_protection_key(&AES128_GCM, &hp_key);
+
+        let mut plain = crypto_frame.to_vec();
+        plain.resize(1162, 0); // PADDING
+
+        let mut packet = header.to_vec();
+        let mut payload = plain.clone();
+        let tag = packet_key
+            .encrypt_in_place(2, &header, &mut payload)
+            .unwrap();
+        packet.extend_from_slice(&payload);
+        packet.extend_from_slice(tag.as_ref());
+
+        // The packet number is the last 4 bytes of the header.
+        let pn_offset = header.len() - 4;
+        let sample = packet[pn_offset + 4..pn_offset + 20].to_vec();
+        assert_eq!(sample, hex!("d1b1c98dd7689fb8ec11d242b123dc9b"));
+
+        let (first, rest) = packet.split_first_mut().unwrap();
+        hp.encrypt_in_place(&sample, first, &mut rest[pn_offset - 1..pn_offset + 3])
+            .unwrap();
+
+        assert_eq!(packet.len(), 1200);
+        assert_eq!(packet, protected);
+
+        // And back.
+        let (first, rest) = packet.split_first_mut().unwrap();
+        hp.decrypt_in_place(&sample, first, &mut rest[pn_offset - 1..pn_offset + 3])
+            .unwrap();
+        assert_eq!(&packet[..header.len()], &header);
+
+        let (aad, payload) = packet.split_at_mut(header.len());
+        let decrypted = packet_key.decrypt_in_place(2, aad, payload).unwrap();
+        assert_eq!(decrypted, &plain);
+    }
+
+    /*
+     * RFC 9001, A.4: the Retry integrity tag is AES-128-GCM over
+     * the Retry pseudo-packet with an empty plain text, which is
+     * a packet key with the Retry nonce as IV and packet number 0.
+     * */
+    #[test]
+    fn test_rfc9001_a4_retry_integrity_tag() {
+        let key = hex!("be0c690b9f66575a1d766b54e368c84e");
+        let nonce = hex!("461599d35d632bf2239825bb");
+        let odcid = hex!("8394c8f03e515708");
+        let retry =
+            hex!("ff000000010008f067a5502a4262b5746f6b656e04a265ba2eff4d829058fb3f0f2496ba");
+        let (retry_without_tag, expected_tag) = retry.split_at(retry.len() - TAG_LENGTH);
+
+        let mut pseudo_packet = vec![odcid.len() as u8];
+        pseudo_packet.extend_from_slice(&odcid);
+        pseudo_packet.extend_from_slice(retry_without_tag);
+
+        let packet_key = packet_key(&AES128_GCM, &key, nonce);
+        let tag = packet_key
+            .encrypt_in_place(0, &pseudo_packet, &mut [])
+            .unwrap();
+        assert_eq!(tag.as_ref(), expected_tag);
+
+        let mut tag = expected_tag.to_vec();
+        assert!(packet_key
+            .decrypt_in_place(0, &pseudo_packet, &mut tag)
+            .unwrap()
+            .is_empty());
+    }
+
+    /*
+     * RFC 9001, A.3: the server Initial, packet and header protection.
+     * */
+    #[test]
+    fn test_rfc9001_a3_server_initial() {
+        let key = hex!("cf3a5331653c364c88f0f379b6067e37");
+        let iv = hex!("0ac1493ca1905853b0bba03e");
+        let hp_key = hex!("c206b8d9b9f0f37644430b490eeaa314");
+        let plain = hex!(
+            "02000000000600405a020000560303eefce7f7b37ba1d1632e96677825ddf73988cfc79825df566dc5430b9a045a1200130100002e00330024001d00209d3c940d89690b84d08a60993c144eca684d1081287c834d5311bcf32bb9da1a002b00020304"
+        );
+        let header = hex!("c1000000010008f067a5502a4262b50040750001");
+        let protected = hex!(
+            "cf000000010008f067a5502a4262b5004075c0d95a482cd0991cd25b0aac406a5816b6394100f37a1c69797554780bb38cc5a99f5ede4cf73c3ec2493a1839b3dbcba3f6ea46c5b7684df3548e7ddeb9c3bf9c73cc3f3bded74b562bfb19fb84022f8ef4cdd93795d77d06edbb7aaf2f58891850abbdca3d20398c276456cbc42158407dd074ee"
+        );
+
+        let packet_key = packet_key(&AES128_GCM, &key, iv);
+        let hp = header_protection_key(&AES128_GCM, &hp_key);
+
+        let mut packet = header.to_vec();
+        let mut payload = plain.to_vec();
+        let tag = packet_key
+            .encrypt_in_place(1, &header, &mut payload)
+            .unwrap();
+        packet.extend_from_slice(&payload);
+        packet.extend_from_slice(tag.as_ref());
+
+        // The packet number is the last 2 bytes of the header.
+        let pn_offset = header.len() - 2;
+        let sample = packet[pn_offset + 4..pn_offset + 20].to_vec();
+        let (first, rest) = packet.split_first_mut().unwrap();
+        hp.encrypt_in_place(&sample, first, &mut rest[pn_offset - 1..pn_offset + 1])
+            .unwrap();
+
+        assert_eq!(packet, protected);
+
+        // And back.
+        let (first, rest) = packet.split_first_mut().unwrap();
+        hp.decrypt_in_place(&sample, first, &mut rest[pn_offset - 1..pn_offset + 1])
+            .unwrap();
+        assert_eq!(&packet[..header.len()], &header);
+
+        let (aad, payload) = packet.split_at_mut(header.len());
+        let decrypted = packet_key.decrypt_in_place(1, aad, payload).unwrap();
+        assert_eq!(decrypted, &plain);
+    }
+
+    /*
+     * RFC 9001, A.5: ChaCha20-Poly1305 short header packet.
+     * */
+    #[test]
+    fn test_rfc9001_a5_chacha20_short_header() {
+        let key = hex!("c6d98ff3441c3fe1b2182094f69caa2ed4b716b65488960a7a984979fb23e1c8");
+        let iv = hex!("e0459b3474bdd0e44a41c144");
+        let hp_key = hex!("25a282b9e82f06f21f488917a4fc8f1b73573685608597d0efcb076b0ab7a7a4");
+        let packet_number = 654360564;
+        let header = hex!("4200bff4");
+        let protected = hex!("4cfe4189655e5cd55c41f69080575d7999c25a5bfb");
+
+        let packet_key = packet_key(&CHACHA20_POLY1305, &key, iv);
+        let hp = header_protection_key(&CHACHA20_POLY1305, &hp_key);
+
+        let mut packet = header.to_vec();
+        let mut payload = [0x01]; // PING
+        let tag = packet_key
+            .encrypt_in_place(packet_number, &header, &mut payload)
+            .unwrap();
+        packet.extend_from_slice(&payload);
+        packet.extend_from_slice(tag.as_ref());
+
+        let sample = packet[5..21].to_vec();
+        let (first, rest) = packet.split_first_mut().unwrap();
+        hp.encrypt_in_place(&sample, first, &mut rest[..3]).unwrap();
+
+        assert_eq!(packet, protected);
+
+        let (first, rest) = packet.split_first_mut().unwrap();
+        hp.decrypt_in_place(&sample, first, &mut rest[..3]).unwrap();
+        assert_eq!(&packet[..4], &header);
+
+        let (aad, payload) = packet.split_at_mut(header.len());
+        assert_eq!(
+            packet_key
+                .decrypt_in_place(packet_number, aad, payload)
+                .unwrap(),
+            &[0x01]
+        );
+    }
+
+    #[test]
+    fn test_aes256_gcm_packet_round_trip() {
+        let key = [0x42; 32];
+        let packet_key = packet_key(&AES256_GCM, &key, [0x24; 12]);
+        let header = [0x40, 0x01];
+        let mut payload = *b"hello quic";
+
+        let tag = packet_key
+            .encrypt_in_place(1, &header, &mut payload)
+            .unwrap();
+        assert_ne!(&payload, b"hello quic");
+
+        let mut packet = payload.to_vec();
+        packet.extend_from_slice(tag.as_ref());
+
+        let mut tampered = packet.clone();
+        tampered[0] ^= 1;
+        assert!(packet_key
+            .decrypt_in_place(1, &header, &mut tampered)
+            .is_err());
+
+        assert_eq!(
+            packet_key
+                .decrypt_in_place(1, &header, &mut packet)
+                .unwrap(),
+            b"hello quic"
+        );
+    }
+}
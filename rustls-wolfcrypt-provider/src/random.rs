@@ -1,30 +1,178 @@
 use crate::error::*;
-use crate::types::*;
+use alloc::boxed::Box;
+use core::cell::UnsafeCell;
 use core::mem;
-use foreign_types::ForeignType;
+use core::sync::atomic::{AtomicBool, Ordering};
 use wolfcrypt_rs::*;
 
+/// How many DRBGs are kept around. Past that many concurrent users,
+/// a temporary one is instantiated (and freed) for the call.
+const RNG_POOL_SIZE: usize = 16;
+
+/// A DRBG (`WC_RNG`) that gets seeded from the OS the first time it's used,
+/// and then stays instantiated for the lifetime of the process. wolfCrypt
+/// reseeds it on its own schedule (`RESEED_INTERVAL` generate calls),
+/// so there's no need to instantiate a new one for every operation.
+///
+/// The slots never move, which wolfCrypt builds without malloc rely on
+/// (the DRBG state then lives inside the `WC_RNG`).
+///
+/// A process forked after the DRBGs were seeded would otherwise share their
+/// state with its parent, so each slot remembers which process seeded it,
+/// and a child seeds its own DRBG before using it. Where there's no telling
+/// which process we're in, the pool isn't used at all.
+struct RngSlot {
+    busy: AtomicBool,
+    seeded: UnsafeCell<bool>,
+    seeded_by: UnsafeCell<u32>,
+    rng: UnsafeCell<WC_RNG>,
+}
+
+// The cells are only touched by whoever set `busy`.
+unsafe impl Sync for RngSlot {}
+
+impl RngSlot {
+    /// Makes sure the DRBG is seeded, and by `process`.
+    /// Only to be called by whoever set `busy`.
+    unsafe fn seed(&self, process: u32) {
+        if *self.seeded.get() && *self.seeded_by.get() != process {
+            // Seeded before a fork: the parent has the same state.
+            wc_FreeRng(self.rng.get());
+            *self.seeded.get() = false;
+        }
+
+        if !*self.seeded.get() {
+            // Gets the seed (from OS) and key cipher for rng.
+            // This is a blocking operation.
+            check_if_zero(wc_InitRng(self.rng.get())).unwrap();
+            *self.seeded.get() = true;
+            *self.seeded_by.get() = process;
+        }
+    }
+}
+
+static RNG_POOL: [RngSlot; RNG_POOL_SIZE] = [const {
+    RngSlot {
+        busy: AtomicBool::new(false),
+        seeded: UnsafeCell::new(false),
+        seeded_by: UnsafeCell::new(0),
+        rng: UnsafeCell::new(unsafe { mem::zeroed() }),
+    }
+}; RNG_POOL_SIZE];
+
+/// The process the DRBGs are used in. On unix that's getpid() from libc,
+/// which wolfSSL is linked against anyway, so it doesn't take `std`.
+#[cfg(unix)]
+fn current_process() -> Option<u32> {
+    extern "C" {
+        fn getpid() -> i32;
+    }
+
+    Some(unsafe { getpid() } as u32)
+}
+
+/// Elsewhere `std` knows, and there's no fork to worry about.
+#[cfg(all(not(unix), any(test, feature = "std")))]
+fn current_process() -> Option<u32> {
+    Some(std::process::id())
+}
+
+/// A no_std target other than unix: we can't tell if we were forked.
+#[cfg(all(not(unix), not(any(test, feature = "std"))))]
+fn current_process() -> Option<u32> {
+    None
+}
+
+/// A DRBG taken from the pool for as long as this is alive;
+/// pass `as_ptr()` wherever wolfCrypt wants a `WC_RNG`.
+/// Only one user at a time gets a given DRBG, no lock is held meanwhile.
+pub(crate) struct PooledRng(Lease);
+
+enum Lease {
+    Pooled(&'static RngSlot),
+    /// All the pooled ones were taken.
+    Temporary(Box<UnsafeCell<WC_RNG>>),
+}
+
+impl PooledRng {
+    pub(crate) fn acquire() -> Self {
+        // If we can't tell which process we're in, a pooled DRBG may have
+        // been seeded by our parent: every call gets a fresh one instead.
+        if let Some(process) = current_process() {
+            for slot in RNG_POOL.iter() {
+                if slot
+                    .busy
+                    .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+                {
+                    unsafe { slot.seed(process) };
+
+                    return PooledRng(Lease::Pooled(slot));
+                }
+            }
+        }
+
+        let rng: Box<UnsafeCell<WC_RNG>> = Box::new(UnsafeCell::new(unsafe { mem::zeroed() }));
+        check_if_zero(unsafe { wc_InitRng(rng.get()) }).unwrap();
+
+        PooledRng(Lease::Temporary(rng))
+    }
+
+    pub(crate) fn as_ptr(&self) -> *mut WC_RNG {
+        match &self.0 {
+            Lease::Pooled(slot) => slot.rng.get(),
+            Lease::Temporary(rng) => rng.get(),
+        }
+    }
+
+    /// Passes through the return value of a wolfCrypt call that used this
+    /// DRBG. If it's an RNG failure the DRBG may be stuck in its error state,
+    /// so rather than going back to the pool as is, it's freed and
+    /// instantiated again on its next use.
+    pub(crate) fn check(&self, ret: i32) -> i32 {
+        if let Err(WCError::RandomError) = check_if_zero(ret) {
+            self.discard();
+        }
+
+        ret
+    }
+
+    fn discard(&self) {
+        if let Lease::Pooled(slot) = &self.0 {
+            unsafe {
+                wc_FreeRng(slot.rng.get());
+                *slot.seeded.get() = false;
+            }
+        }
+    }
+}
+
+impl Drop for PooledRng {
+    fn drop(&mut self) {
+        match &self.0 {
+            Lease::Pooled(slot) => slot.busy.store(false, Ordering::Release),
+            Lease::Temporary(rng) => unsafe {
+                wc_FreeRng(rng.get());
+            },
+        }
+    }
+}
+
 pub fn wolfcrypt_random_buffer_generator(buff: &mut [u8]) -> WCResult {
-    let mut rng_c_type: WC_RNG = unsafe { mem::zeroed() };
-    let rng_object = WCRngObject::new(&mut rng_c_type);
+    let rng = PooledRng::acquire();
     let buff_length: word32 = buff.len() as word32;
 
-    // Gets the seed (from OS) and key cipher for rng.
-    // rng->drbg (deterministic random bit generator) allocated
-    // (should be deallocated with wc_FreeRng).
-    // This is a blocking operation.
-    rng_object.init();
-
     // Copies a sz bytes of pseudorandom data to output.
     // Will reseed rng if needed (blocking).
-    let ret = unsafe { wc_RNG_GenerateBlock(rng_object.as_ptr(), buff.as_mut_ptr(), buff_length) };
+    let ret = unsafe { wc_RNG_GenerateBlock(rng.as_ptr(), buff.as_mut_ptr(), buff_length) };
 
-    check_if_zero(ret)
+    check_if_zero(rng.check(ret))
 }
 
 #[cfg(test)]
 mod tests {
-    use super::wolfcrypt_random_buffer_generator;
+    use super::*;
+    use alloc::vec::Vec;
 
     #[test]
     fn test_random() {
@@ -36,4 +184,66 @@ mod tests {
 
         assert_ne!(buff_1, buff_2);
     }
+
+    #[test]
+    fn test_pooled_rng() {
+        // More at once than there are in the pool: each user still
+        // gets a DRBG of its own.
+        let rngs: Vec<PooledRng> = (0..RNG_POOL_SIZE + 2)
+            .map(|_| PooledRng::acquire())
+            .collect();
+        let ptrs: Vec<*mut WC_RNG> = rngs.iter().map(|rng| rng.as_ptr()).collect();
+
+        for (i, ptr) in ptrs.iter().enumerate() {
+            assert!(!ptrs[i + 1..].contains(ptr));
+        }
+        assert!(matches!(rngs[RNG_POOL_SIZE].0, Lease::Temporary(_)));
+
+        let mut buff = [0u8; 32];
+        for rng in &rngs {
+            let ret = unsafe { wc_RNG_GenerateBlock(rng.as_ptr(), buff.as_mut_ptr(), 32) };
+            check_if_zero(ret).unwrap();
+        }
+        drop(rngs);
+
+        // Once given back, they get reused rather than instantiated again.
+        let rng = PooledRng::acquire();
+        assert!(matches!(rng.0, Lease::Pooled(slot) if unsafe { *slot.seeded.get() }));
+    }
+
+    #[test]
+    fn test_pooled_rng_reseeds() {
+        // test_pooled_rng may hold all of them for a moment.
+        let rng = loop {
+            let rng = PooledRng::acquire();
+            if let Lease::Pooled(_) = rng.0 {
+                break rng;
+            }
+        };
+        let Lease::Pooled(slot) = rng.0 else {
+            unreachable!()
+        };
+        let mut buff = [0u8; 32];
+
+        // Other errors leave the DRBG alone.
+        assert_eq!(rng.check(-173), -173);
+        assert!(unsafe { *slot.seeded.get() });
+
+        // An RNG failure gets it instantiated again before it's used next.
+        assert_eq!(rng.check(-199), -199);
+        assert!(!unsafe { *slot.seeded.get() });
+        unsafe { slot.seed(current_process().unwrap()) };
+        let ret = unsafe { wc_RNG_GenerateBlock(rng.as_ptr(), buff.as_mut_ptr(), 32) };
+        check_if_zero(ret).unwrap();
+
+        // So does one seeded in another process, as after a fork.
+        let process = current_process().unwrap();
+        unsafe {
+            *slot.seeded_by.get() = process.wrapping_add(1);
+            slot.seed(process);
+            assert_eq!(*slot.seeded_by.get(), process);
+        }
+        let ret = unsafe { wc_RNG_GenerateBlock(rng.as_ptr(), buff.as_mut_ptr(), 32) };
+        check_if_zero(ret).unwrap();
+    }
 }
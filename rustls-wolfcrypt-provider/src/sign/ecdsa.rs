@@ -1,5 +1,6 @@
 use crate::alloc::string::ToString;
 use crate::error::*;
+use crate::random::PooledRng;
 use crate::types::*;
 use alloc::boxed::Box;
 use alloc::format;
@@ -15,7 +16,16 @@ use rustls::{SignatureAlgorithm, SignatureScheme};
 
 use wolfcrypt_rs::*;
 
-/// A unified ECDSA signing key that supports P-256, P-384, P-521.
+/// ecdsa_brainpoolP256r1tls13_sha256 from RFC 8734.
+/// rustls doesn't have a name for the brainpool schemes, so we carry the codepoints ourselves.
+pub const ECDSA_BRAINPOOLP256R1TLS13_SHA256: SignatureScheme = SignatureScheme::Unknown(0x081a);
+/// ecdsa_brainpoolP384r1tls13_sha384 from RFC 8734.
+pub const ECDSA_BRAINPOOLP384R1TLS13_SHA384: SignatureScheme = SignatureScheme::Unknown(0x081b);
+/// ecdsa_brainpoolP512r1tls13_sha512 from RFC 8734.
+pub const ECDSA_BRAINPOOLP512R1TLS13_SHA512: SignatureScheme = SignatureScheme::Unknown(0x081c);
+
+/// A unified ECDSA signing key that supports P-256, P-384, P-521
+/// and brainpool P-256, P-384, P-512.
 /// Internally, we store the raw private key bytes plus
 /// which scheme we should use (determined by WolfSSL after decode).
 #[derive(Clone, Debug)]
@@ -85,8 +95,12 @@ impl TryFrom<&PrivateKeyDer<'_>> for EcdsaSigningKey {
 
         priv_key_bytes.truncate(priv_key_bytes_len as usize);
 
+        // The key size alone can't tell P-256 and brainpool P-256 apart,
+        // so we look at the curve the key was decoded with.
+        let curve_id = unsafe { wc_ecc_get_curve_id((*ecc_key_object.as_ptr()).idx) };
+
         let scheme =
-            curve_id_to_scheme(key_size).map_err(|e| rustls::Error::General(e.to_string()))?;
+            curve_id_to_scheme(curve_id).map_err(|e| rustls::Error::General(e.to_string()))?;
 
         Ok(Self {
             key: Arc::new(priv_key_bytes),
@@ -95,13 +109,17 @@ impl TryFrom<&PrivateKeyDer<'_>> for EcdsaSigningKey {
     }
 }
 
-/// Converts a key size to a `SignatureScheme` (e.g. 32 -> ECDSA_NISTP256_SHA256).
-fn curve_id_to_scheme(key_size: i32) -> Result<SignatureScheme, &'static str> {
-    match key_size {
-        32 => Ok(SignatureScheme::ECDSA_NISTP256_SHA256),
-        48 => Ok(SignatureScheme::ECDSA_NISTP384_SHA384),
-        66 => Ok(SignatureScheme::ECDSA_NISTP521_SHA512),
-        _ => Err("Unsupported ECC key size"),
+/// Converts a WolfSSL curve id to a `SignatureScheme`
+/// (e.g. ecc_curve_id_ECC_SECP256R1 -> ECDSA_NISTP256_SHA256).
+fn curve_id_to_scheme(curve_id: i32) -> Result<SignatureScheme, &'static str> {
+    match curve_id {
+        ecc_curve_id_ECC_SECP256R1 => Ok(SignatureScheme::ECDSA_NISTP256_SHA256),
+        ecc_curve_id_ECC_SECP384R1 => Ok(SignatureScheme::ECDSA_NISTP384_SHA384),
+        ecc_curve_id_ECC_SECP521R1 => Ok(SignatureScheme::ECDSA_NISTP521_SHA512),
+        ecc_curve_id_ECC_BRAINPOOLP256R1 => Ok(ECDSA_BRAINPOOLP256R1TLS13_SHA256),
+        ecc_curve_id_ECC_BRAINPOOLP384R1 => Ok(ECDSA_BRAINPOOLP384R1TLS13_SHA384),
+        ecc_curve_id_ECC_BRAINPOOLP512R1 => Ok(ECDSA_BRAINPOOLP512R1TLS13_SHA512),
+        _ => Err("Unsupported ECC curve"),
     }
 }
 
@@ -125,9 +143,7 @@ impl Signer for EcdsaSigningKey {
         let digest = hash_message_for_scheme(self.scheme, message)
             .map_err(|_| rustls::Error::General("hash failed".into()))?;
 
-        let mut rng: WC_RNG = unsafe { mem::zeroed() };
-        let rng_object: WCRngObject = WCRngObject::new(&mut rng);
-        rng_object.init();
+        let rng = PooledRng::acquire();
 
         let mut ecc_c_type: ecc_key = unsafe { mem::zeroed() };
         let ecc_key_object = ECCKeyObject::new(&mut ecc_c_type);
@@ -156,16 +172,16 @@ impl Signer for EcdsaSigningKey {
         let mut sig = [0u8; ECC_MAX_SIG_SIZE as usize];
         let mut sig_sz: word32 = sig.len() as word32;
 
-        let ret = unsafe {
+        let ret = rng.check(unsafe {
             wc_ecc_sign_hash(
                 digest.as_ptr() as *mut u8,
                 digest.len() as word32,
                 sig.as_mut_ptr(),
                 &mut sig_sz,
-                rng_object.as_ptr(),
+                rng.as_ptr(),
                 ecc_key_object.as_ptr(),
             )
-        };
+        });
         check_if_zero(ret).map_err(|_| rustls::Error::General("wc_ecc_sign_hash failed".into()))?;
 
         // truncate to actual sig size
@@ -187,7 +203,7 @@ fn hash_message_for_scheme(
     message: &[u8],
 ) -> Result<Vec<u8>, &'static str> {
     match scheme {
-        SignatureScheme::ECDSA_NISTP256_SHA256 => {
+        SignatureScheme::ECDSA_NISTP256_SHA256 | ECDSA_BRAINPOOLP256R1TLS13_SHA256 => {
             let mut digest = vec![0u8; WC_SHA256_DIGEST_SIZE as usize];
             let ret = unsafe {
                 wc_Sha256Hash(
@@ -201,7 +217,7 @@ fn hash_message_for_scheme(
             }
             Ok(digest)
         }
-        SignatureScheme::ECDSA_NISTP384_SHA384 => {
+        SignatureScheme::ECDSA_NISTP384_SHA384 | ECDSA_BRAINPOOLP384R1TLS13_SHA384 => {
             let mut digest = vec![0u8; WC_SHA384_DIGEST_SIZE as usize];
             let ret = unsafe {
                 wc_Sha384Hash(
@@ -215,7 +231,7 @@ fn hash_message_for_scheme(
             }
             Ok(digest)
         }
-        SignatureScheme::ECDSA_NISTP521_SHA512 => {
+        SignatureScheme::ECDSA_NISTP521_SHA512 | ECDSA_BRAINPOOLP512R1TLS13_SHA512 => {
             let mut digest = vec![0u8; WC_SHA512_DIGEST_SIZE as usize];
             let ret = unsafe {
                 wc_Sha512Hash(
@@ -239,6 +255,9 @@ fn scheme_to_curve_id(scheme: SignatureScheme) -> Result<i32, &'static str> {
         SignatureScheme::ECDSA_NISTP256_SHA256 => Ok(ecc_curve_id_ECC_SECP256R1),
         SignatureScheme::ECDSA_NISTP384_SHA384 => Ok(ecc_curve_id_ECC_SECP384R1),
         SignatureScheme::ECDSA_NISTP521_SHA512 => Ok(ecc_curve_id_ECC_SECP521R1),
-        _ => Err("Not an ECDSA_NISTPxxx_SHAxxx scheme"),
+        ECDSA_BRAINPOOLP256R1TLS13_SHA256 => Ok(ecc_curve_id_ECC_BRAINPOOLP256R1),
+        ECDSA_BRAINPOOLP384R1TLS13_SHA384 => Ok(ecc_curve_id_ECC_BRAINPOOLP384R1),
+        ECDSA_BRAINPOOLP512R1TLS13_SHA512 => Ok(ecc_curve_id_ECC_BRAINPOOLP512R1),
+        _ => Err("Not an ECDSA scheme"),
     }
 }
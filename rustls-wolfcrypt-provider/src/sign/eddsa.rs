@@ -4,6 +4,7 @@ use alloc::boxed::Box;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::mem;
+use core::ptr;
 use foreign_types::ForeignType;
 use rustls::pki_types::PrivateKeyDer;
 use rustls::sign::{Signer, SigningKey};
@@ -11,8 +12,6 @@ use rustls::{SignatureAlgorithm, SignatureScheme};
 
 use wolfcrypt_rs::*;
 
-const ALL_EDDSA_SCHEMES: &[SignatureScheme] = &[SignatureScheme::ED25519, SignatureScheme::ED448];
-
 #[derive(Clone, Debug)]
 pub struct Ed25519PrivateKey {
     priv_key: Arc<Vec<u8>>,
@@ -91,18 +90,16 @@ impl TryFrom<&PrivateKeyDer<'_>> for Ed25519PrivateKey {
 
 impl SigningKey for Ed25519PrivateKey {
     fn choose_scheme(&self, offered: &[SignatureScheme]) -> Option<Box<dyn Signer>> {
-        // Iterate through all ECDSA schemes and check if any is in the offered list
-        ALL_EDDSA_SCHEMES.iter().find_map(|&scheme| {
-            if offered.contains(&scheme) {
-                Some(Box::new(Ed25519Signer {
-                    priv_key: self.priv_key.clone(),
-                    pub_key: self.pub_key.clone(),
-                    scheme,
-                }) as Box<dyn Signer>)
-            } else {
-                None
-            }
-        })
+        // An Ed25519 key can only ever produce ED25519 signatures.
+        if offered.contains(&SignatureScheme::ED25519) {
+            Some(Box::new(Ed25519Signer {
+                priv_key: self.priv_key.clone(),
+                pub_key: self.pub_key.clone(),
+                scheme: SignatureScheme::ED25519,
+            }))
+        } else {
+            None
+        }
     }
 
     fn algorithm(&self) -> SignatureAlgorithm {
@@ -165,3 +162,153 @@ impl Signer for Ed25519Signer {
         self.scheme
     }
 }
+
+#[derive(Clone, Debug)]
+pub struct Ed448PrivateKey {
+    priv_key: Arc<Vec<u8>>,
+    pub_key: Arc<Vec<u8>>,
+    algo: SignatureAlgorithm,
+}
+
+impl TryFrom<&PrivateKeyDer<'_>> for Ed448PrivateKey {
+    type Error = rustls::Error;
+
+    fn try_from(value: &PrivateKeyDer<'_>) -> Result<Self, Self::Error> {
+        match value {
+            PrivateKeyDer::Pkcs8(der) => {
+                let mut ed448_c_type: ed448_key = unsafe { mem::zeroed() };
+                let ed448_key_object = ED448KeyObject::new(&mut ed448_c_type);
+                let mut priv_key_raw: [u8; ED448_KEY_SIZE as usize] = [0; ED448_KEY_SIZE as usize];
+                let mut priv_key_raw_len: word32 = priv_key_raw.len() as word32;
+                let mut pub_key_raw: [u8; ED448_PUB_KEY_SIZE as usize] =
+                    [0; ED448_PUB_KEY_SIZE as usize];
+                let pub_key_raw_len: word32 = pub_key_raw.len() as word32;
+                let pkcs8: &[u8] = der.secret_pkcs8_der();
+                let pkcs8_sz: word32 = pkcs8.len() as word32;
+                let mut ret;
+
+                // This function initiliazes an ed448_key object for
+                // using it to sign a message.
+                ed448_key_object.init();
+
+                let mut idx: u32 = 0;
+
+                // This function reads in an ED448 private key from the input buffer, input,
+                // parses the private key, and uses it to generate an ed448_key object,
+                // which it stores in key.
+                ret = unsafe {
+                    wc_Ed448PrivateKeyDecode(
+                        pkcs8.as_ptr() as *mut u8,
+                        &mut idx,
+                        ed448_key_object.as_ptr(),
+                        pkcs8_sz,
+                    )
+                };
+                check_if_zero(ret)
+                    .map_err(|_| rustls::Error::General("FFI function failed".into()))?;
+
+                ret = unsafe {
+                    wc_ed448_make_public(
+                        ed448_key_object.as_ptr(),
+                        pub_key_raw.as_mut_ptr(),
+                        pub_key_raw_len,
+                    )
+                };
+                check_if_zero(ret)
+                    .map_err(|_| rustls::Error::General("FFI function failed".into()))?;
+
+                ret = unsafe {
+                    wc_ed448_export_private_only(
+                        ed448_key_object.as_ptr(),
+                        priv_key_raw.as_mut_ptr(),
+                        &mut priv_key_raw_len,
+                    )
+                };
+                check_if_zero(ret)
+                    .map_err(|_| rustls::Error::General("FFI function failed".into()))?;
+
+                Ok(Self {
+                    priv_key: Arc::new(priv_key_raw.to_vec()),
+                    pub_key: Arc::new(pub_key_raw.to_vec()),
+                    algo: SignatureAlgorithm::ED448,
+                })
+            }
+            _ => Err(rustls::Error::General(
+                "Unsupported private key format".into(),
+            )),
+        }
+    }
+}
+
+impl SigningKey for Ed448PrivateKey {
+    fn choose_scheme(&self, offered: &[SignatureScheme]) -> Option<Box<dyn Signer>> {
+        if offered.contains(&SignatureScheme::ED448) {
+            Some(Box::new(Ed448Signer {
+                priv_key: self.priv_key.clone(),
+                pub_key: self.pub_key.clone(),
+            }))
+        } else {
+            None
+        }
+    }
+
+    fn algorithm(&self) -> SignatureAlgorithm {
+        self.algo
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Ed448Signer {
+    priv_key: Arc<Vec<u8>>,
+    pub_key: Arc<Vec<u8>>,
+}
+
+impl Signer for Ed448Signer {
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, rustls::Error> {
+        let mut ret;
+        let message_length: word32 = message.len() as word32;
+        let mut sig: [u8; ED448_SIG_SIZE as usize] = [0; ED448_SIG_SIZE as usize];
+        let mut sig_sz: word32 = sig.len() as word32;
+        let mut ed448_c_type: ed448_key = unsafe { mem::zeroed() };
+        let ed448_key_object = ED448KeyObject::new(&mut ed448_c_type);
+
+        ed448_key_object.init();
+
+        ret = unsafe {
+            wc_ed448_import_private_key(
+                self.priv_key.as_ptr(),
+                self.priv_key.len() as word32,
+                self.pub_key.as_ptr(),
+                self.pub_key.len() as word32,
+                ed448_key_object.as_ptr(),
+            )
+        };
+        check_if_zero(ret)
+            .map_err(|_| rustls::Error::General("wc_ed448_import_private_key failed".into()))?;
+
+        // Pure Ed448 (RFC 8032) with an empty context, as TLS uses it.
+        ret = unsafe {
+            wc_ed448_sign_msg(
+                message.as_ptr(),
+                message_length,
+                sig.as_mut_ptr(),
+                &mut sig_sz,
+                ed448_key_object.as_ptr(),
+                ptr::null(),
+                0,
+            )
+        };
+        check_if_zero(ret)
+            .map_err(|_| rustls::Error::General("wc_ed448_sign_msg failed".into()))?;
+
+        let mut sig_vec = sig.to_vec();
+
+        sig_vec.truncate(sig_sz as usize);
+
+        Ok(sig_vec)
+    }
+
+    fn scheme(&self) -> SignatureScheme {
+        SignatureScheme::ED448
+    }
+}
@@ -0,0 +1,150 @@
+use crate::error::*;
+use crate::random::PooledRng;
+use crate::types::*;
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::mem;
+use foreign_types::ForeignType;
+use rustls::pki_types::PrivateKeyDer;
+use rustls::sign::{Signer, SigningKey};
+use rustls::{SignatureAlgorithm, SignatureScheme};
+
+use wolfcrypt_rs::*;
+
+/// The parameter sets along with their draft-ietf-tls-mldsa schemes.
+const ALL_MLDSA_LEVELS: &[(u8, SignatureScheme)] = &[
+    (WC_ML_DSA_44 as u8, SignatureScheme::ML_DSA_44),
+    (WC_ML_DSA_65 as u8, SignatureScheme::ML_DSA_65),
+    (WC_ML_DSA_87 as u8, SignatureScheme::ML_DSA_87),
+];
+
+/// An ML-DSA (FIPS 204) signing key.
+/// We keep the PKCS#8 encoding around and decode it for every signature,
+/// along with the parameter set (level) it was decoded with.
+#[derive(Clone, Debug)]
+pub struct MlDsaPrivateKey {
+    key_der: Arc<Vec<u8>>,
+    level: u8,
+    scheme: SignatureScheme,
+}
+
+impl TryFrom<&PrivateKeyDer<'_>> for MlDsaPrivateKey {
+    type Error = rustls::Error;
+
+    fn try_from(value: &PrivateKeyDer<'_>) -> Result<Self, Self::Error> {
+        match value {
+            PrivateKeyDer::Pkcs8(der) => {
+                let pkcs8: &[u8] = der.secret_pkcs8_der();
+
+                // The OID in the PKCS#8 envelope has to match the level
+                // the key object is set to, so we try each one in turn.
+                for &(level, scheme) in ALL_MLDSA_LEVELS {
+                    let mut dilithium_c_type: dilithium_key = unsafe { mem::zeroed() };
+                    let dilithium_key_object = DilithiumKeyObject::new(&mut dilithium_c_type);
+
+                    if decode_private_key(&dilithium_key_object, level, pkcs8).is_ok() {
+                        return Ok(Self {
+                            key_der: Arc::new(pkcs8.to_vec()),
+                            level,
+                            scheme,
+                        });
+                    }
+                }
+
+                Err(rustls::Error::General(
+                    "Unsupported ML-DSA private key".into(),
+                ))
+            }
+            _ => Err(rustls::Error::General(
+                "Unsupported private key format".into(),
+            )),
+        }
+    }
+}
+
+/// This function initializes a dilithium_key object, sets its
+/// parameter set and decodes a PKCS#8 ML-DSA private key into it.
+fn decode_private_key(
+    dilithium_key_object: &DilithiumKeyObject,
+    level: u8,
+    pkcs8: &[u8],
+) -> Result<(), rustls::Error> {
+    let mut ret;
+    let mut idx: u32 = 0;
+
+    dilithium_key_object.init();
+
+    ret = unsafe { wc_dilithium_set_level(dilithium_key_object.as_ptr(), level) };
+    check_if_zero(ret)
+        .map_err(|_| rustls::Error::General("wc_dilithium_set_level failed".into()))?;
+
+    ret = unsafe {
+        wc_Dilithium_PrivateKeyDecode(
+            pkcs8.as_ptr(),
+            &mut idx,
+            dilithium_key_object.as_ptr(),
+            pkcs8.len() as word32,
+        )
+    };
+    check_if_zero(ret)
+        .map_err(|_| rustls::Error::General("wc_Dilithium_PrivateKeyDecode failed".into()))
+}
+
+impl SigningKey for MlDsaPrivateKey {
+    fn choose_scheme(&self, offered: &[SignatureScheme]) -> Option<Box<dyn Signer>> {
+        if offered.contains(&self.scheme) {
+            Some(Box::new(self.clone()))
+        } else {
+            None
+        }
+    }
+
+    // rustls has no SignatureAlgorithm for ML-DSA, and this is
+    // only looked at for TLS 1.2, where ML-DSA isn't defined.
+    fn algorithm(&self) -> SignatureAlgorithm {
+        SignatureAlgorithm::Unknown(0)
+    }
+}
+
+impl Signer for MlDsaPrivateKey {
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, rustls::Error> {
+        let mut dilithium_c_type: dilithium_key = unsafe { mem::zeroed() };
+        let dilithium_key_object = DilithiumKeyObject::new(&mut dilithium_c_type);
+        let rng = PooledRng::acquire();
+
+        decode_private_key(&dilithium_key_object, self.level, &self.key_der)?;
+
+        // This function returns the signature size for the key's parameter set.
+        let sig_size = unsafe { wc_dilithium_sig_size(dilithium_key_object.as_ptr()) };
+        check_if_greater_than_zero(sig_size)
+            .map_err(|_| rustls::Error::General("wc_dilithium_sig_size failed".into()))?;
+
+        let mut sig = vec![0u8; sig_size as usize];
+        let mut sig_sz: word32 = sig.len() as word32;
+
+        // This function signs the message (pure ML-DSA, empty context)
+        // using the hedged variant, with randomness drawn from rng.
+        let ret = rng.check(unsafe {
+            wc_dilithium_sign_msg(
+                message.as_ptr(),
+                message.len() as word32,
+                sig.as_mut_ptr(),
+                &mut sig_sz,
+                dilithium_key_object.as_ptr(),
+                rng.as_ptr(),
+            )
+        });
+        check_if_zero(ret)
+            .map_err(|_| rustls::Error::General("wc_dilithium_sign_msg failed".into()))?;
+
+        sig.truncate(sig_sz as usize);
+
+        Ok(sig)
+    }
+
+    fn scheme(&self) -> SignatureScheme {
+        self.scheme
+    }
+}
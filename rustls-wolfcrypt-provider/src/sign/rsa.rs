@@ -1,4 +1,5 @@
 use crate::error::*;
+use crate::random::PooledRng;
 use crate::types::*;
 use alloc::boxed::Box;
 use alloc::sync::Arc;
@@ -154,10 +155,8 @@ impl Signer for RsaSigner {
         let rsa_key_arc = self.get_key();
         let rsa_key_object = rsa_key_arc.as_ref();
 
-        // Prepare a random generator
-        let mut rng: WC_RNG = unsafe { mem::zeroed() };
-        let rng_object = WCRngObject::new(&mut rng);
-        rng_object.init();
+        // Take a random generator from the pool
+        let rng = PooledRng::acquire();
 
         // Allocate enough space for the signature
         let mut sig_buf = [0u8; MAX_RSA_SIG_SIZE];
@@ -211,7 +210,7 @@ impl Signer for RsaSigner {
                     .map_err(|_| rustls::Error::General("Failed to hash for PSS".into()))?;
 
                 // 3) Sign with wc_RsaPSS_Sign
-                let ret = unsafe {
+                let ret = rng.check(unsafe {
                     wc_RsaPSS_Sign(
                         digest.as_ptr(),
                         digest_len,
@@ -220,9 +219,9 @@ impl Signer for RsaSigner {
                         hash_ty,
                         mgf_ty.try_into().unwrap(),
                         rsa_key_object.as_ptr(),
-                        rng_object.as_ptr(),
+                        rng.as_ptr(),
                     )
-                };
+                });
                 check_if_greater_than_zero(ret)
                     .map_err(|_| rustls::Error::General("wc_RsaPSS_Sign failed".into()))?;
 
@@ -250,7 +249,7 @@ impl Signer for RsaSigner {
 
                 // wc_SignatureGenerate will produce a PKCS#1 signature, including hashing.
                 let deref_rsa_key_c_type = unsafe { *(rsa_key_object.as_ptr()) };
-                let ret = unsafe {
+                let ret = rng.check(unsafe {
                     wc_SignatureGenerate(
                         hash_ty,
                         wc_SignatureType_WC_SIGNATURE_TYPE_RSA_W_ENC,
@@ -260,9 +259,9 @@ impl Signer for RsaSigner {
                         &mut sig_len,
                         rsa_key_object.as_ptr() as *const core::ffi::c_void,
                         mem::size_of_val(&deref_rsa_key_c_type).try_into().unwrap(),
-                        rng_object.as_ptr(),
+                        rng.as_ptr(),
                     )
-                };
+                });
                 check_if_zero(ret)
                     .map_err(|_| rustls::Error::General("wc_SignatureGenerate failed".into()))?;
 
@@ -0,0 +1,219 @@
+use crate::aead::context::{AeadContext, AesGcmContext, ChaCha20Poly1305Context, Locked};
+use crate::random::wolfcrypt_random_buffer_generator;
+use alloc::boxed::Box;
+#[cfg(feature = "std")]
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use rustls::crypto::GetRandomFailed;
+use rustls::server::ProducesTickets;
+use zeroize::Zeroizing;
+
+const KEY_LENGTH: usize = 32;
+const KEY_NAME_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 12;
+const TAG_LENGTH: usize = 16;
+
+/// The AEAD used to seal the tickets.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TicketCipher {
+    Aes256Gcm,
+    ChaCha20Poly1305,
+}
+
+impl TicketCipher {
+    /// Makes single key ticketers for this cipher, for use with
+    /// `rustls::TicketSwitcher` where `Ticketer` isn't available (no `std`).
+    pub fn generator(self) -> fn() -> Result<Box<dyn ProducesTickets>, GetRandomFailed> {
+        match self {
+            TicketCipher::Aes256Gcm => make_aes256gcm_ticketer,
+            TicketCipher::ChaCha20Poly1305 => make_chacha20poly1305_ticketer,
+        }
+    }
+}
+
+/// Session ticket encryption backed by wolfCrypt.
+///
+/// Each key is used to issue tickets for `lifetime` seconds, then it's
+/// replaced by a freshly generated one; the previous key is kept around
+/// for another `lifetime` so that its tickets can still be redeemed.
+pub struct Ticketer {}
+
+impl Ticketer {
+    /// AES-256-GCM tickets with a 12 hour life (keys rotate every 6 hours),
+    /// same defaults as the ticketers shipped with rustls.
+    #[cfg(feature = "std")]
+    pub fn new() -> Result<Arc<dyn ProducesTickets>, rustls::Error> {
+        Self::with_cipher(TicketCipher::Aes256Gcm, 6 * 60 * 60)
+    }
+
+    /// Tickets sealed with `cipher`, with keys rotated every `lifetime` seconds.
+    #[cfg(feature = "std")]
+    pub fn with_cipher(
+        cipher: TicketCipher,
+        lifetime: u32,
+    ) -> Result<Arc<dyn ProducesTickets>, rustls::Error> {
+        Ok(Arc::new(rustls::TicketRotator::new(
+            lifetime,
+            cipher.generator(),
+        )?))
+    }
+}
+
+fn make_aes256gcm_ticketer() -> Result<Box<dyn ProducesTickets>, GetRandomFailed> {
+    Ok(Box::new(AeadTicketer::new(TicketCipher::Aes256Gcm)?))
+}
+
+fn make_chacha20poly1305_ticketer() -> Result<Box<dyn ProducesTickets>, GetRandomFailed> {
+    Ok(Box::new(AeadTicketer::new(TicketCipher::ChaCha20Poly1305)?))
+}
+
+/// A single key ticketer, the rotation is left to rustls.
+///
+/// A ticket is: key_name (16 bytes) || nonce (12 bytes) || cipher text || tag (16 bytes),
+/// the key name being the additional data.
+///
+/// The AEAD context is keyed once here, and wiped along with the key on drop.
+struct AeadTicketer {
+    cipher: TicketCipher,
+    context: Locked<AeadContext>,
+    key_name: [u8; KEY_NAME_LENGTH],
+}
+
+impl AeadTicketer {
+    fn new(cipher: TicketCipher) -> Result<Self, GetRandomFailed> {
+        let mut key = Zeroizing::new([0u8; KEY_LENGTH]);
+        let mut key_name = [0u8; KEY_NAME_LENGTH];
+
+        wolfcrypt_random_buffer_generator(&mut *key).map_err(|_| GetRandomFailed)?;
+        wolfcrypt_random_buffer_generator(&mut key_name).map_err(|_| GetRandomFailed)?;
+
+        let context = match cipher {
+            TicketCipher::Aes256Gcm => AeadContext::AesGcm(AesGcmContext::new(&*key)),
+            TicketCipher::ChaCha20Poly1305 => {
+                AeadContext::ChaCha20Poly1305(ChaCha20Poly1305Context::new(&*key))
+            }
+        };
+
+        Ok(AeadTicketer {
+            cipher,
+            context: Locked::new(context),
+            key_name,
+        })
+    }
+}
+
+impl ProducesTickets for AeadTicketer {
+    fn enabled(&self) -> bool {
+        true
+    }
+
+    // The rotation wrapper reports its own lifetime,
+    // this one is never looked at.
+    fn lifetime(&self) -> u32 {
+        0
+    }
+
+    fn encrypt(&self, plain: &[u8]) -> Option<Vec<u8>> {
+        let mut nonce = [0u8; NONCE_LENGTH];
+        wolfcrypt_random_buffer_generator(&mut nonce).ok()?;
+
+        let header_len = KEY_NAME_LENGTH + NONCE_LENGTH;
+        let mut ticket = vec![0u8; header_len + plain.len() + TAG_LENGTH];
+        ticket[..KEY_NAME_LENGTH].copy_from_slice(&self.key_name);
+        ticket[KEY_NAME_LENGTH..header_len].copy_from_slice(&nonce);
+
+        let (cipher_text, tag) = ticket[header_len..].split_at_mut(plain.len());
+        cipher_text.copy_from_slice(plain);
+        self.context
+            .lock()
+            .seal_in_place(&nonce, &self.key_name, cipher_text, tag)
+            .ok()?;
+
+        Some(ticket)
+    }
+
+    fn decrypt(&self, ticket: &[u8]) -> Option<Vec<u8>> {
+        let header_len = KEY_NAME_LENGTH + NONCE_LENGTH;
+        if ticket.len() < header_len + TAG_LENGTH {
+            return None;
+        }
+
+        // Tickets sealed by another key (most likely the
+        // previous one) are not ours to open.
+        let (key_name, rest) = ticket.split_at(KEY_NAME_LENGTH);
+        if key_name != self.key_name {
+            return None;
+        }
+
+        let (nonce, rest) = rest.split_at(NONCE_LENGTH);
+        let (cipher_text, tag) = rest.split_at(rest.len() - TAG_LENGTH);
+        let mut plain = cipher_text.to_vec();
+
+        self.context
+            .lock()
+            .open_in_place(nonce, &self.key_name, &mut plain, tag)
+            .ok()?;
+
+        Some(plain)
+    }
+}
+
+impl fmt::Debug for AeadTicketer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The key stays out of the logs.
+        f.debug_struct("AeadTicketer")
+            .field("cipher", &self.cipher)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ticket_round_trip() {
+        for cipher in [TicketCipher::Aes256Gcm, TicketCipher::ChaCha20Poly1305] {
+            let ticketer = AeadTicketer::new(cipher).unwrap();
+            let ticket = ticketer.encrypt(b"session state").unwrap();
+
+            assert_eq!(
+                ticket.len(),
+                KEY_NAME_LENGTH + NONCE_LENGTH + 13 + TAG_LENGTH
+            );
+            assert_eq!(ticketer.decrypt(&ticket).unwrap(), b"session state");
+
+            // Fresh nonce for every ticket.
+            assert_ne!(ticketer.encrypt(b"session state").unwrap(), ticket);
+        }
+    }
+
+    #[test]
+    fn test_ticket_rejects_tampering() {
+        for cipher in [TicketCipher::Aes256Gcm, TicketCipher::ChaCha20Poly1305] {
+            let ticketer = AeadTicketer::new(cipher).unwrap();
+            let ticket = ticketer.encrypt(b"session state").unwrap();
+
+            for i in 0..ticket.len() {
+                let mut tampered = ticket.clone();
+                tampered[i] ^= 1;
+                assert!(ticketer.decrypt(&tampered).is_none());
+            }
+
+            assert!(ticketer.decrypt(&ticket[..ticket.len() - 1]).is_none());
+            assert!(ticketer.decrypt(&[]).is_none());
+        }
+    }
+
+    #[test]
+    fn test_ticket_from_another_key() {
+        let ticketer = AeadTicketer::new(TicketCipher::Aes256Gcm).unwrap();
+        let other = AeadTicketer::new(TicketCipher::Aes256Gcm).unwrap();
+
+        let ticket = other.encrypt(b"session state").unwrap();
+
+        assert!(ticketer.decrypt(&ticket).is_none());
+    }
+}
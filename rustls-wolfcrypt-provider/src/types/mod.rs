@@ -136,6 +136,19 @@ define_foreign_type!(
     curve25519_key,
     wc_curve25519_init
 );
+define_foreign_type!(
+    Curve448KeyObject,
+    Curve448KeyObjectRef,
+    curve448_key,
+    wc_curve448_init
+);
+define_foreign_type!(
+    DhKeyObject,
+    DhKeyObjectRef,
+    DhKey,
+    drop(wc_FreeDhKey),
+    wc_InitDhKey
+);
 define_foreign_type!(ECCKeyObject, ECCKeyObjectRef, ecc_key, wc_ecc_init);
 define_foreign_type!(
     ED25519KeyObject,
@@ -145,6 +158,21 @@ define_foreign_type!(
 );
 define_foreign_type!(ED448KeyObject, ED448KeyObjectRef, ed448_key, wc_ed448_init);
 
+define_foreign_type!(
+    DilithiumKeyObject,
+    DilithiumKeyObjectRef,
+    dilithium_key,
+    drop(dilithium_free),
+    wc_dilithium_init
+);
+
+/// wc_dilithium_free doesn't return anything, while the Drop
+/// implementation expects a return code to check.
+unsafe fn dilithium_free(key: *mut dilithium_key) -> i32 {
+    wc_dilithium_free(key);
+    0
+}
+
 define_foreign_type_with_copy!(RsaKeyObject, RsaKeyObjectRef, RsaKey);
 define_foreign_type_with_copy!(HmacObject, HmacObjectRef, wolfcrypt_rs::Hmac);
 define_foreign_type_with_copy!(AesObject, AesObjectRef, Aes);
@@ -2,8 +2,14 @@ use rustls::crypto::WebPkiSupportedAlgorithms;
 use rustls::pki_types::SignatureVerificationAlgorithm;
 use rustls::SignatureScheme;
 
+use crate::sign::ecdsa::{
+    ECDSA_BRAINPOOLP256R1TLS13_SHA256, ECDSA_BRAINPOOLP384R1TLS13_SHA384,
+    ECDSA_BRAINPOOLP512R1TLS13_SHA512,
+};
+
 mod ecdsa;
 pub mod eddsa;
+mod mldsa;
 mod rsapkcs1;
 mod rsapss;
 
@@ -16,7 +22,14 @@ pub static ALGORITHMS: WebPkiSupportedAlgorithms = WebPkiSupportedAlgorithms {
         ECDSA_P256_SHA256,
         ECDSA_P384_SHA384,
         ECDSA_P521_SHA512,
+        ECDSA_BRAINPOOLP256_SHA256,
+        ECDSA_BRAINPOOLP384_SHA384,
+        ECDSA_BRAINPOOLP512_SHA512,
         ED25519,
+        ED448,
+        ML_DSA_44,
+        ML_DSA_65,
+        ML_DSA_87,
     ],
     mapping: &[
         (SignatureScheme::RSA_PSS_SHA256, &[RSA_PSS_SHA256]),
@@ -28,7 +41,23 @@ pub static ALGORITHMS: WebPkiSupportedAlgorithms = WebPkiSupportedAlgorithms {
         (SignatureScheme::ECDSA_NISTP256_SHA256, &[ECDSA_P256_SHA256]),
         (SignatureScheme::ECDSA_NISTP384_SHA384, &[ECDSA_P384_SHA384]),
         (SignatureScheme::ECDSA_NISTP521_SHA512, &[ECDSA_P521_SHA512]),
+        (
+            ECDSA_BRAINPOOLP256R1TLS13_SHA256,
+            &[ECDSA_BRAINPOOLP256_SHA256],
+        ),
+        (
+            ECDSA_BRAINPOOLP384R1TLS13_SHA384,
+            &[ECDSA_BRAINPOOLP384_SHA384],
+        ),
+        (
+            ECDSA_BRAINPOOLP512R1TLS13_SHA512,
+            &[ECDSA_BRAINPOOLP512_SHA512],
+        ),
         (SignatureScheme::ED25519, &[ED25519]),
+        (SignatureScheme::ED448, &[ED448]),
+        (SignatureScheme::ML_DSA_44, &[ML_DSA_44]),
+        (SignatureScheme::ML_DSA_65, &[ML_DSA_65]),
+        (SignatureScheme::ML_DSA_87, &[ML_DSA_87]),
     ],
 };
 
@@ -41,4 +70,14 @@ static RSA_PKCS1_SHA512: &dyn SignatureVerificationAlgorithm = &rsapkcs1::RsaPkc
 static ECDSA_P256_SHA256: &dyn SignatureVerificationAlgorithm = &ecdsa::EcdsaVerifier::P256_SHA256;
 static ECDSA_P384_SHA384: &dyn SignatureVerificationAlgorithm = &ecdsa::EcdsaVerifier::P384_SHA384;
 static ECDSA_P521_SHA512: &dyn SignatureVerificationAlgorithm = &ecdsa::EcdsaVerifier::P521_SHA512;
+static ECDSA_BRAINPOOLP256_SHA256: &dyn SignatureVerificationAlgorithm =
+    &ecdsa::EcdsaVerifier::BRAINPOOLP256_SHA256;
+static ECDSA_BRAINPOOLP384_SHA384: &dyn SignatureVerificationAlgorithm =
+    &ecdsa::EcdsaVerifier::BRAINPOOLP384_SHA384;
+static ECDSA_BRAINPOOLP512_SHA512: &dyn SignatureVerificationAlgorithm =
+    &ecdsa::EcdsaVerifier::BRAINPOOLP512_SHA512;
 static ED25519: &dyn SignatureVerificationAlgorithm = &eddsa::Ed25519;
+static ED448: &dyn SignatureVerificationAlgorithm = &eddsa::Ed448;
+static ML_DSA_44: &dyn SignatureVerificationAlgorithm = &mldsa::MlDsaVerifier::MLDSA44;
+static ML_DSA_65: &dyn SignatureVerificationAlgorithm = &mldsa::MlDsaVerifier::MLDSA65;
+static ML_DSA_87: &dyn SignatureVerificationAlgorithm = &mldsa::MlDsaVerifier::MLDSA87;
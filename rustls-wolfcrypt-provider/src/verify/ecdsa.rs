@@ -1,3 +1,7 @@
+use crate::sign::ecdsa::{
+    ECDSA_BRAINPOOLP256R1TLS13_SHA256, ECDSA_BRAINPOOLP384R1TLS13_SHA384,
+    ECDSA_BRAINPOOLP512R1TLS13_SHA512,
+};
 use crate::{
     error::{check_if_one, check_if_zero, WCError},
     types::*,
@@ -11,7 +15,47 @@ use rustls::SignatureScheme;
 use webpki::alg_id;
 use wolfcrypt_rs::*;
 
-/// A unified ECDSA verifier for P-256, P-384, and P-521.
+/// AlgorithmIdentifier for `id-ecPublicKey` with named curve `brainpoolP256r1`.
+///
+/// ```text
+/// # ecPublicKey
+/// OBJECT_IDENTIFIER { 1.2.840.10045.2.1 }
+/// # brainpoolP256r1
+/// OBJECT_IDENTIFIER { 1.3.36.3.3.2.8.1.1.7 }
+/// ```
+pub const ECDSA_BRAINPOOLP256R1: AlgorithmIdentifier = AlgorithmIdentifier::from_slice(&[
+    0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x09, 0x2b, 0x24, 0x03, 0x03, 0x02,
+    0x08, 0x01, 0x01, 0x07,
+]);
+
+/// AlgorithmIdentifier for `id-ecPublicKey` with named curve `brainpoolP384r1`.
+///
+/// ```text
+/// # ecPublicKey
+/// OBJECT_IDENTIFIER { 1.2.840.10045.2.1 }
+/// # brainpoolP384r1
+/// OBJECT_IDENTIFIER { 1.3.36.3.3.2.8.1.1.11 }
+/// ```
+pub const ECDSA_BRAINPOOLP384R1: AlgorithmIdentifier = AlgorithmIdentifier::from_slice(&[
+    0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x09, 0x2b, 0x24, 0x03, 0x03, 0x02,
+    0x08, 0x01, 0x01, 0x0b,
+]);
+
+/// AlgorithmIdentifier for `id-ecPublicKey` with named curve `brainpoolP512r1`.
+///
+/// ```text
+/// # ecPublicKey
+/// OBJECT_IDENTIFIER { 1.2.840.10045.2.1 }
+/// # brainpoolP512r1
+/// OBJECT_IDENTIFIER { 1.3.36.3.3.2.8.1.1.13 }
+/// ```
+pub const ECDSA_BRAINPOOLP512R1: AlgorithmIdentifier = AlgorithmIdentifier::from_slice(&[
+    0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x09, 0x2b, 0x24, 0x03, 0x03, 0x02,
+    0x08, 0x01, 0x01, 0x0d,
+]);
+
+/// A unified ECDSA verifier for P-256, P-384, P-521
+/// and brainpool P-256, P-384, P-512.
 /// We store the `SignatureScheme` and switch logic accordingly.
 #[derive(Debug)]
 pub struct EcdsaVerifier {
@@ -33,6 +77,21 @@ impl EcdsaVerifier {
     pub const P521_SHA512: Self = Self {
         scheme: SignatureScheme::ECDSA_NISTP521_SHA512,
     };
+
+    /// Constructor for brainpool P-256 / ECDSA_BRAINPOOLP256R1TLS13_SHA256
+    pub const BRAINPOOLP256_SHA256: Self = Self {
+        scheme: ECDSA_BRAINPOOLP256R1TLS13_SHA256,
+    };
+
+    /// Constructor for brainpool P-384 / ECDSA_BRAINPOOLP384R1TLS13_SHA384
+    pub const BRAINPOOLP384_SHA384: Self = Self {
+        scheme: ECDSA_BRAINPOOLP384R1TLS13_SHA384,
+    };
+
+    /// Constructor for brainpool P-512 / ECDSA_BRAINPOOLP512R1TLS13_SHA512
+    pub const BRAINPOOLP512_SHA512: Self = Self {
+        scheme: ECDSA_BRAINPOOLP512R1TLS13_SHA512,
+    };
 }
 
 impl SignatureVerificationAlgorithm for EcdsaVerifier {
@@ -41,15 +100,24 @@ impl SignatureVerificationAlgorithm for EcdsaVerifier {
             SignatureScheme::ECDSA_NISTP256_SHA256 => alg_id::ECDSA_P256,
             SignatureScheme::ECDSA_NISTP384_SHA384 => alg_id::ECDSA_P384,
             SignatureScheme::ECDSA_NISTP521_SHA512 => alg_id::ECDSA_P521,
+            ECDSA_BRAINPOOLP256R1TLS13_SHA256 => ECDSA_BRAINPOOLP256R1,
+            ECDSA_BRAINPOOLP384R1TLS13_SHA384 => ECDSA_BRAINPOOLP384R1,
+            ECDSA_BRAINPOOLP512R1TLS13_SHA512 => ECDSA_BRAINPOOLP512R1,
             _ => unreachable!("Unsupported scheme for ECDSA public_key_alg_id"),
         }
     }
 
     fn signature_alg_id(&self) -> AlgorithmIdentifier {
         match self.scheme {
-            SignatureScheme::ECDSA_NISTP256_SHA256 => alg_id::ECDSA_SHA256,
-            SignatureScheme::ECDSA_NISTP384_SHA384 => alg_id::ECDSA_SHA384,
-            SignatureScheme::ECDSA_NISTP521_SHA512 => alg_id::ECDSA_SHA512,
+            SignatureScheme::ECDSA_NISTP256_SHA256 | ECDSA_BRAINPOOLP256R1TLS13_SHA256 => {
+                alg_id::ECDSA_SHA256
+            }
+            SignatureScheme::ECDSA_NISTP384_SHA384 | ECDSA_BRAINPOOLP384R1TLS13_SHA384 => {
+                alg_id::ECDSA_SHA384
+            }
+            SignatureScheme::ECDSA_NISTP521_SHA512 | ECDSA_BRAINPOOLP512R1TLS13_SHA512 => {
+                alg_id::ECDSA_SHA512
+            }
             _ => unreachable!("Unsupported scheme for ECDSA signature_alg_id"),
         }
     }
@@ -86,6 +154,21 @@ impl SignatureVerificationAlgorithm for EcdsaVerifier {
                     66,
                     wc_HashType_WC_HASH_TYPE_SHA512,
                 ),
+                ECDSA_BRAINPOOLP256R1TLS13_SHA256 => (
+                    ecc_curve_id_ECC_BRAINPOOLP256R1,
+                    32,
+                    wc_HashType_WC_HASH_TYPE_SHA256,
+                ),
+                ECDSA_BRAINPOOLP384R1TLS13_SHA384 => (
+                    ecc_curve_id_ECC_BRAINPOOLP384R1,
+                    48,
+                    wc_HashType_WC_HASH_TYPE_SHA384,
+                ),
+                ECDSA_BRAINPOOLP512R1TLS13_SHA512 => (
+                    ecc_curve_id_ECC_BRAINPOOLP512R1,
+                    64,
+                    wc_HashType_WC_HASH_TYPE_SHA512,
+                ),
                 _ => return Err(InvalidSignature),
             };
 
@@ -96,6 +179,7 @@ impl SignatureVerificationAlgorithm for EcdsaVerifier {
              * For P-256, skip_len=32 means public_key[1..33] is "x" and [33..65] is "y".
              * For P-384, skip_len=48 means public_key[1..49] is "x" and [49..97] is "y".
              * For P-521, skip_len=66 means public_key[1..67] is "x" and [67..133] is "y".
+             * The brainpool curves use the same encoding, with the matching sizes.
              */
             ret = wc_ecc_import_unsigned(
                 ecc_key_object.as_ptr(),
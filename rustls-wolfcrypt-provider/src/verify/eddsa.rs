@@ -3,6 +3,7 @@ use crate::{
     types::*,
 };
 use core::mem;
+use core::ptr;
 use foreign_types::ForeignType;
 use rustls::pki_types::{AlgorithmIdentifier, InvalidSignature, SignatureVerificationAlgorithm};
 use webpki::alg_id;
@@ -59,3 +60,63 @@ impl SignatureVerificationAlgorithm for Ed25519 {
         }
     }
 }
+
+/// AlgorithmIdentifier for `id-Ed448` (RFC 8410), which webpki doesn't provide.
+///
+/// ```text
+/// # ed448
+/// OBJECT_IDENTIFIER { 1.3.101.113 }
+/// ```
+pub const ED448_ALG_ID: AlgorithmIdentifier =
+    AlgorithmIdentifier::from_slice(&[0x06, 0x03, 0x2b, 0x65, 0x71]);
+
+#[derive(Debug)]
+pub struct Ed448;
+
+impl SignatureVerificationAlgorithm for Ed448 {
+    fn public_key_alg_id(&self) -> AlgorithmIdentifier {
+        ED448_ALG_ID
+    }
+
+    fn signature_alg_id(&self) -> AlgorithmIdentifier {
+        ED448_ALG_ID
+    }
+
+    fn verify_signature(
+        &self,
+        public_key: &[u8],
+        message: &[u8],
+        signature: &[u8],
+    ) -> Result<(), InvalidSignature> {
+        unsafe {
+            let mut ed448_c_type: ed448_key = mem::zeroed();
+            let ed448_key_object = ED448KeyObject::from_ptr(&mut ed448_c_type);
+            let mut ret;
+            let mut stat: i32 = 0;
+
+            ed448_key_object.init();
+
+            ret = wc_ed448_import_public(
+                public_key.as_ptr(),
+                public_key.len() as word32,
+                ed448_key_object.as_ptr(),
+            );
+            check_if_zero(ret).map_err(|_| InvalidSignature)?;
+
+            // Pure Ed448 with an empty context, matching the signer.
+            ret = wc_ed448_verify_msg(
+                signature.as_ptr(),
+                signature.len() as word32,
+                message.as_ptr(),
+                message.len() as word32,
+                &mut stat,
+                ed448_key_object.as_ptr(),
+                ptr::null(),
+                0,
+            );
+            check_if_zero(ret).map_err(|_| InvalidSignature)?;
+
+            check_if_one(stat).map_err(|_| InvalidSignature)
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use crate::{
+    error::{check_if_one, check_if_zero},
+    types::*,
+};
+use core::mem;
+use foreign_types::ForeignType;
+use rustls::pki_types::{AlgorithmIdentifier, InvalidSignature, SignatureVerificationAlgorithm};
+use rustls::SignatureScheme;
+use wolfcrypt_rs::*;
+
+/// AlgorithmIdentifier for `id-ml-dsa-44` (parameters absent).
+///
+/// ```text
+/// # id-ml-dsa-44
+/// OBJECT_IDENTIFIER { 2.16.840.1.101.3.4.3.17 }
+/// ```
+pub const ML_DSA_44: AlgorithmIdentifier = AlgorithmIdentifier::from_slice(&[
+    0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x03, 0x11,
+]);
+
+/// AlgorithmIdentifier for `id-ml-dsa-65` (parameters absent).
+///
+/// ```text
+/// # id-ml-dsa-65
+/// OBJECT_IDENTIFIER { 2.16.840.1.101.3.4.3.18 }
+/// ```
+pub const ML_DSA_65: AlgorithmIdentifier = AlgorithmIdentifier::from_slice(&[
+    0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x03, 0x12,
+]);
+
+/// AlgorithmIdentifier for `id-ml-dsa-87` (parameters absent).
+///
+/// ```text
+/// # id-ml-dsa-87
+/// OBJECT_IDENTIFIER { 2.16.840.1.101.3.4.3.19 }
+/// ```
+pub const ML_DSA_87: AlgorithmIdentifier = AlgorithmIdentifier::from_slice(&[
+    0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x03, 0x13,
+]);
+
+/// A unified ML-DSA verifier for ML-DSA-44, ML-DSA-65 and ML-DSA-87.
+/// The same OID is used for both the public key and the signature.
+#[derive(Debug)]
+pub struct MlDsaVerifier {
+    scheme: SignatureScheme,
+}
+
+impl MlDsaVerifier {
+    /// Constructor for ML-DSA-44
+    pub const MLDSA44: Self = Self {
+        scheme: SignatureScheme::ML_DSA_44,
+    };
+
+    /// Constructor for ML-DSA-65
+    pub const MLDSA65: Self = Self {
+        scheme: SignatureScheme::ML_DSA_65,
+    };
+
+    /// Constructor for ML-DSA-87
+    pub const MLDSA87: Self = Self {
+        scheme: SignatureScheme::ML_DSA_87,
+    };
+
+    fn level(&self) -> u8 {
+        match self.scheme {
+            SignatureScheme::ML_DSA_44 => WC_ML_DSA_44 as u8,
+            SignatureScheme::ML_DSA_65 => WC_ML_DSA_65 as u8,
+            SignatureScheme::ML_DSA_87 => WC_ML_DSA_87 as u8,
+            _ => unreachable!("Unsupported scheme for ML-DSA"),
+        }
+    }
+}
+
+impl SignatureVerificationAlgorithm for MlDsaVerifier {
+    fn public_key_alg_id(&self) -> AlgorithmIdentifier {
+        self.signature_alg_id()
+    }
+
+    fn signature_alg_id(&self) -> AlgorithmIdentifier {
+        match self.scheme {
+            SignatureScheme::ML_DSA_44 => ML_DSA_44,
+            SignatureScheme::ML_DSA_65 => ML_DSA_65,
+            SignatureScheme::ML_DSA_87 => ML_DSA_87,
+            _ => unreachable!("Unsupported scheme for ML-DSA signature_alg_id"),
+        }
+    }
+
+    fn verify_signature(
+        &self,
+        public_key: &[u8],
+        message: &[u8],
+        signature: &[u8],
+    ) -> Result<(), InvalidSignature> {
+        unsafe {
+            let mut dilithium_c_type: dilithium_key = mem::zeroed();
+            let dilithium_key_object = DilithiumKeyObject::from_ptr(&mut dilithium_c_type);
+            let mut ret;
+            let mut stat: i32 = 0;
+
+            dilithium_key_object.init();
+
+            ret = wc_dilithium_set_level(dilithium_key_object.as_ptr(), self.level());
+            check_if_zero(ret).map_err(|_| InvalidSignature)?;
+
+            // The public key is the raw encoding from the SubjectPublicKeyInfo,
+            // its length is checked against the level we just set.
+            ret = wc_dilithium_import_public(
+                public_key.as_ptr(),
+                public_key.len() as word32,
+                dilithium_key_object.as_ptr(),
+            );
+            check_if_zero(ret).map_err(|_| InvalidSignature)?;
+
+            ret = wc_dilithium_verify_msg(
+                signature.as_ptr(),
+                signature.len() as word32,
+                message.as_ptr(),
+                message.len() as word32,
+                &mut stat,
+                dilithium_key_object.as_ptr(),
+            );
+            check_if_zero(ret).map_err(|_| InvalidSignature)?;
+
+            check_if_one(stat).map_err(|_| InvalidSignature)
+        }
+    }
+}
@@ -6,9 +6,10 @@ use rustls::SignatureScheme;
 use rustls_wolfcrypt_provider::error::*;
 use rustls_wolfcrypt_provider::types::*;
 use rustls_wolfcrypt_provider::{
-    TLS12_ECDHE_RSA_WITH_AES_128_GCM_SHA256, TLS12_ECDHE_RSA_WITH_AES_256_GCM_SHA384,
-    TLS12_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256, TLS13_AES_128_GCM_SHA256,
-    TLS13_AES_256_GCM_SHA384, TLS13_CHACHA20_POLY1305_SHA256,
+    TLS12_DHE_RSA_WITH_AES_128_GCM_SHA256, TLS12_DHE_RSA_WITH_AES_256_GCM_SHA384,
+    TLS12_DHE_RSA_WITH_CHACHA20_POLY1305_SHA256, TLS12_ECDHE_RSA_WITH_AES_128_GCM_SHA256,
+    TLS12_ECDHE_RSA_WITH_AES_256_GCM_SHA384, TLS12_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256,
+    TLS13_AES_128_GCM_SHA256, TLS13_AES_256_GCM_SHA384, TLS13_CHACHA20_POLY1305_SHA256,
 };
 use std::env;
 use std::fs::File;
@@ -84,7 +85,7 @@ fn start_wolfssl_server(current_dir_string: String, tls_version: &str) -> Child
 mod tests {
     use rustls::crypto::CryptoProvider;
     use rustls_pki_types::{
-        PrivateKeyDer, PrivatePkcs1KeyDer, PrivatePkcs8KeyDer, PrivateSec1KeyDer,
+        CertificateDer, PrivateKeyDer, PrivatePkcs1KeyDer, PrivatePkcs8KeyDer, PrivateSec1KeyDer,
     };
 
     use super::*;
@@ -99,6 +100,9 @@ mod tests {
             TLS12_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256,
             TLS12_ECDHE_RSA_WITH_AES_128_GCM_SHA256,
             TLS12_ECDHE_RSA_WITH_AES_256_GCM_SHA384,
+            TLS12_DHE_RSA_WITH_CHACHA20_POLY1305_SHA256,
+            TLS12_DHE_RSA_WITH_AES_128_GCM_SHA256,
+            TLS12_DHE_RSA_WITH_AES_256_GCM_SHA384,
         ];
 
         for cipher in ciphers {
@@ -130,14 +134,18 @@ mod tests {
 
             root_store.add_parsable_certificates(certs);
 
-            let config = rustls::ClientConfig::builder_with_provider(
-                rustls_wolfcrypt_provider::provider_with_specified_ciphers([cipher].to_vec())
-                    .into(),
-            )
-            .with_protocol_versions(&[&TLS12])
-            .unwrap()
-            .with_root_certificates(root_store)
-            .with_no_client_auth();
+            // The DHE_RSA suites need the FFDHE groups, which aren't offered by default.
+            let mut provider =
+                rustls_wolfcrypt_provider::provider_with_specified_ciphers([cipher].to_vec());
+            provider
+                .kx_groups
+                .extend(rustls_wolfcrypt_provider::kx::FFDHE_KX_GROUPS);
+
+            let config = rustls::ClientConfig::builder_with_provider(provider.into())
+                .with_protocol_versions(&[&TLS12])
+                .unwrap()
+                .with_root_certificates(root_store)
+                .with_no_client_auth();
 
             let server_name = "localhost".try_into().unwrap();
             let mut conn = rustls::ClientConnection::new(Arc::new(config), server_name).unwrap();
@@ -423,6 +431,25 @@ mod tests {
                 ecc_curve_id_ECC_SECP521R1,
                 66, // P521 key size
             ),
+            /*
+             * Same key sizes as the NIST curves: the scheme has to come
+             * from the curve id, not from the key size.
+             * */
+            (
+                rustls_wolfcrypt_provider::sign::ecdsa::ECDSA_BRAINPOOLP256R1TLS13_SHA256,
+                ecc_curve_id_ECC_BRAINPOOLP256R1,
+                32, // brainpoolP256r1 key size
+            ),
+            (
+                rustls_wolfcrypt_provider::sign::ecdsa::ECDSA_BRAINPOOLP384R1TLS13_SHA384,
+                ecc_curve_id_ECC_BRAINPOOLP384R1,
+                48, // brainpoolP384r1 key size
+            ),
+            (
+                rustls_wolfcrypt_provider::sign::ecdsa::ECDSA_BRAINPOOLP512R1TLS13_SHA512,
+                ecc_curve_id_ECC_BRAINPOOLP512R1,
+                64, // brainpoolP512r1 key size
+            ),
         ];
 
         for &(scheme, curve_id, key_size) in &test_configs {
@@ -572,6 +599,146 @@ mod tests {
             rustls_private_key.clone_key(),
             pub_key_raw.as_slice(),
         );
+
+        // An Ed25519 key must not claim it can produce Ed448 signatures.
+        let signing_key = wolfcrypt_default_provider
+            .key_provider
+            .load_private_key(rustls_private_key)
+            .unwrap();
+        assert!(signing_key
+            .choose_scheme(&[SignatureScheme::ED448])
+            .is_none());
+    }
+
+    #[test]
+    fn ed448_sign_and_verify() {
+        let wolfcrypt_default_provider = rustls_wolfcrypt_provider::provider();
+
+        // Initialize RNG and Ed448 key objects
+        let mut rng: WC_RNG = unsafe { mem::zeroed() };
+        let rng_object: WCRngObject = WCRngObject::new(&mut rng);
+        rng_object.init();
+
+        let mut key_c_type: ed448_key = unsafe { mem::zeroed() };
+        let key_object = ED448KeyObject::new(&mut key_c_type);
+        key_object.init();
+
+        let mut der_ed448_key: Vec<u8> = vec![0; 200];
+        let mut pub_key_raw: [u8; 57] = [0; 57];
+        let mut pub_key_raw_len: word32 = pub_key_raw.len() as word32;
+
+        let mut ret;
+
+        // Generate Ed448 key
+        ret = unsafe { wc_ed448_make_key(rng_object.as_ptr(), 57, key_object.as_ptr()) };
+        check_if_zero(ret).unwrap();
+
+        // Export public key
+        ret = unsafe {
+            wc_ed448_export_public(
+                key_object.as_ptr(),
+                pub_key_raw.as_mut_ptr(),
+                &mut pub_key_raw_len,
+            )
+        };
+        check_if_zero(ret).unwrap();
+
+        // Export private key in DER format
+        ret = unsafe {
+            wc_Ed448PrivateKeyToDer(
+                key_object.as_ptr(),
+                der_ed448_key.as_mut_ptr(),
+                der_ed448_key.len() as word32,
+            )
+        };
+        check_if_greater_than_zero(ret).unwrap();
+
+        der_ed448_key.resize(ret as usize, 0); // Trim to actual size
+        let rustls_pkcs8_der = PrivatePkcs8KeyDer::from(der_ed448_key.as_slice());
+        let rustls_private_key = PrivateKeyDer::from(rustls_pkcs8_der);
+
+        sign_and_verify(
+            &wolfcrypt_default_provider,
+            SignatureScheme::ED448,
+            rustls_private_key.clone_key(),
+            pub_key_raw.as_slice(),
+        );
+    }
+
+    /*
+     * Generates an ML-DSA key pair with wolfCrypt, returns the PKCS#8
+     * private key along with the raw public key.
+     * */
+    fn generate_mldsa_key(level: u32) -> (PrivateKeyDer<'static>, Vec<u8>) {
+        // Initialize RNG and ML-DSA key objects
+        let mut rng: WC_RNG = unsafe { mem::zeroed() };
+        let rng_object: WCRngObject = WCRngObject::new(&mut rng);
+        rng_object.init();
+
+        let mut key_c_type: dilithium_key = unsafe { mem::zeroed() };
+        let key_object = DilithiumKeyObject::new(&mut key_c_type);
+        key_object.init();
+
+        // Large enough for ML-DSA-87 private + public key.
+        let mut der_mldsa_key: Vec<u8> = vec![0; 8192];
+        let mut pub_key_raw: Vec<u8> = vec![0; 2592];
+        let mut pub_key_raw_len: word32 = pub_key_raw.len() as word32;
+
+        let mut ret;
+
+        ret = unsafe { wc_dilithium_set_level(key_object.as_ptr(), level as u8) };
+        check_if_zero(ret).unwrap();
+
+        // Generate ML-DSA key
+        ret = unsafe { wc_dilithium_make_key(key_object.as_ptr(), rng_object.as_ptr()) };
+        check_if_zero(ret).unwrap();
+
+        // Export public key
+        ret = unsafe {
+            wc_dilithium_export_public(
+                key_object.as_ptr(),
+                pub_key_raw.as_mut_ptr(),
+                &mut pub_key_raw_len,
+            )
+        };
+        check_if_zero(ret).unwrap();
+        pub_key_raw.truncate(pub_key_raw_len as usize);
+
+        // Export private key in (PKCS#8) DER format
+        ret = unsafe {
+            wc_Dilithium_PrivateKeyToDer(
+                key_object.as_ptr(),
+                der_mldsa_key.as_mut_ptr(),
+                der_mldsa_key.len() as word32,
+            )
+        };
+        check_if_greater_than_zero(ret).unwrap();
+
+        der_mldsa_key.resize(ret as usize, 0); // Trim to actual size
+
+        (PrivatePkcs8KeyDer::from(der_mldsa_key).into(), pub_key_raw)
+    }
+
+    #[test]
+    fn mldsa_sign_and_verify() {
+        let wolfcrypt_default_provider = rustls_wolfcrypt_provider::provider();
+
+        let test_configs = [
+            (SignatureScheme::ML_DSA_44, WC_ML_DSA_44),
+            (SignatureScheme::ML_DSA_65, WC_ML_DSA_65),
+            (SignatureScheme::ML_DSA_87, WC_ML_DSA_87),
+        ];
+
+        for &(scheme, level) in &test_configs {
+            let (rustls_private_key, pub_key_raw) = generate_mldsa_key(level);
+
+            sign_and_verify(
+                &wolfcrypt_default_provider,
+                scheme,
+                rustls_private_key,
+                pub_key_raw.as_slice(),
+            );
+        }
     }
 
     #[test]
@@ -787,4 +954,1030 @@ mod tests {
             .iter()
             .any(|alg| { alg.verify_signature(pub_key, data, &signature).is_ok() }));
     }
+
+    /*
+     * CA and "localhost" server certificate (signed by the CA), used for the
+     * in-memory handshakes between this provider and the ones shipped with rustls.
+     * */
+    struct TestPki {
+        ca_cert_der: CertificateDer<'static>,
+        server_cert_der: CertificateDer<'static>,
+        server_key_der: PrivateKeyDer<'static>,
+    }
+
+    impl TestPki {
+        fn new() -> Self {
+            let alg = &rcgen::PKCS_ECDSA_P256_SHA256;
+            let mut ca_params = rcgen::CertificateParams::new(Vec::new()).unwrap();
+            ca_params
+                .distinguished_name
+                .push(rcgen::DnType::CommonName, "Interop Test CA");
+            ca_params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+            ca_params.key_usages = vec![
+                rcgen::KeyUsagePurpose::KeyCertSign,
+                rcgen::KeyUsagePurpose::DigitalSignature,
+            ];
+            let ca_key = rcgen::KeyPair::generate_for(alg).unwrap();
+            let ca_cert = ca_params.self_signed(&ca_key).unwrap();
+
+            let mut server_params =
+                rcgen::CertificateParams::new(vec!["localhost".to_string()]).unwrap();
+            server_params.is_ca = rcgen::IsCa::NoCa;
+            server_params.extended_key_usages = vec![rcgen::ExtendedKeyUsagePurpose::ServerAuth];
+            let server_key = rcgen::KeyPair::generate_for(alg).unwrap();
+            let server_cert = server_params
+                .signed_by(&server_key, &ca_cert, &ca_key)
+                .unwrap();
+
+            Self {
+                ca_cert_der: ca_cert.into(),
+                server_cert_der: server_cert.into(),
+                server_key_der: PrivatePkcs8KeyDer::from(server_key.serialize_der()).into(),
+            }
+        }
+
+        /*
+         * Same as new(), with ML-DSA keys for both the CA and the server.
+         * rcgen can't make ML-DSA certificates without aws-lc-rs, so they're
+         * put together by hand and signed with this provider.
+         * */
+        fn new_mldsa(scheme: SignatureScheme, level: u32) -> Self {
+            let (ca_key_der, ca_pub_key) = generate_mldsa_key(level);
+            let (server_key_der, server_pub_key) = generate_mldsa_key(level);
+            let ca_signer = rustls_wolfcrypt_provider::provider()
+                .key_provider
+                .load_private_key(ca_key_der)
+                .unwrap()
+                .choose_scheme(&[scheme])
+                .unwrap();
+
+            // id-ml-dsa-44/65/87, 2.16.840.1.101.3.4.3.17 to 19.
+            let oid_last = match scheme {
+                SignatureScheme::ML_DSA_44 => 0x11,
+                SignatureScheme::ML_DSA_65 => 0x12,
+                _ => 0x13,
+            };
+            let alg_id = der(
+                0x30,
+                &[
+                    0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x03, oid_last,
+                ],
+            );
+
+            // basicConstraints (critical): cA.
+            let ca_extensions = [der(
+                0x30,
+                &[
+                    &[0x06, 0x03, 0x55, 0x1d, 0x13, 0x01, 0x01, 0xff][..],
+                    &der(0x04, &der(0x30, &[0x01, 0x01, 0xff]))[..],
+                ]
+                .concat(),
+            )];
+            // subjectAltName: dNSName "localhost", and
+            // extKeyUsage: id-kp-serverAuth.
+            let server_extensions = [
+                der(
+                    0x30,
+                    &[
+                        &[0x06, 0x03, 0x55, 0x1d, 0x11][..],
+                        &der(0x04, &der(0x30, &der(0x82, b"localhost")))[..],
+                    ]
+                    .concat(),
+                ),
+                der(
+                    0x30,
+                    &[
+                        &[0x06, 0x03, 0x55, 0x1d, 0x25][..],
+                        &der(
+                            0x04,
+                            &der(
+                                0x30,
+                                &[0x06, 0x08, 0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x01],
+                            ),
+                        )[..],
+                    ]
+                    .concat(),
+                ),
+            ];
+
+            Self {
+                ca_cert_der: mldsa_certificate(
+                    1,
+                    "ML-DSA Test CA",
+                    &ca_pub_key,
+                    &ca_extensions,
+                    &alg_id,
+                    ca_signer.as_ref(),
+                ),
+                server_cert_der: mldsa_certificate(
+                    2,
+                    "localhost",
+                    &server_pub_key,
+                    &server_extensions,
+                    &alg_id,
+                    ca_signer.as_ref(),
+                ),
+                server_key_der,
+            }
+        }
+
+        fn client_config(&self, provider: CryptoProvider) -> Arc<rustls::ClientConfig> {
+            self.client_config_for_versions(provider, &[&TLS13])
+        }
+
+        fn client_config_for_versions(
+            &self,
+            provider: CryptoProvider,
+            versions: &[&'static rustls::SupportedProtocolVersion],
+        ) -> Arc<rustls::ClientConfig> {
+            let mut root_store = rustls::RootCertStore::empty();
+            root_store.add(self.ca_cert_der.clone()).unwrap();
+
+            Arc::new(
+                rustls::ClientConfig::builder_with_provider(provider.into())
+                    .with_protocol_versions(versions)
+                    .unwrap()
+                    .with_root_certificates(root_store)
+                    .with_no_client_auth(),
+            )
+        }
+
+        fn server_config(&self, provider: CryptoProvider) -> Arc<rustls::ServerConfig> {
+            self.server_config_for_versions(provider, &[&TLS13])
+        }
+
+        fn server_config_for_versions(
+            &self,
+            provider: CryptoProvider,
+            versions: &[&'static rustls::SupportedProtocolVersion],
+        ) -> Arc<rustls::ServerConfig> {
+            Arc::new(
+                rustls::ServerConfig::builder_with_provider(provider.into())
+                    .with_protocol_versions(versions)
+                    .unwrap()
+                    .with_no_client_auth()
+                    .with_single_cert(
+                        vec![self.server_cert_der.clone()],
+                        self.server_key_der.clone_key(),
+                    )
+                    .unwrap(),
+            )
+        }
+    }
+
+    /*
+     * DER encoding of a single tag-length-value, enough to put the
+     * ML-DSA test certificates together.
+     * */
+    fn der(tag: u8, contents: &[u8]) -> Vec<u8> {
+        let mut out = vec![tag];
+        let len = contents.len().to_be_bytes();
+        let len = &len[len
+            .iter()
+            .position(|&byte| byte != 0)
+            .unwrap_or(len.len() - 1)..];
+
+        if contents.len() >= 0x80 {
+            out.push(0x80 | len.len() as u8);
+        }
+        out.extend_from_slice(len);
+        out.extend_from_slice(contents);
+
+        out
+    }
+
+    /*
+     * An X.509 v3 certificate for `subject`, issued by the ML-DSA test CA.
+     * */
+    fn mldsa_certificate(
+        serial: u8,
+        subject: &str,
+        pub_key: &[u8],
+        extensions: &[Vec<u8>],
+        alg_id: &[u8],
+        ca_signer: &dyn rustls::sign::Signer,
+    ) -> CertificateDer<'static> {
+        let name = |common_name: &str| {
+            der(
+                0x30,
+                &der(
+                    0x31,
+                    &der(
+                        0x30,
+                        &[
+                            &[0x06, 0x03, 0x55, 0x04, 0x03][..],
+                            &der(0x0c, common_name.as_bytes())[..],
+                        ]
+                        .concat(),
+                    ),
+                ),
+            )
+        };
+        let bit_string = |bytes: &[u8]| der(0x03, &[&[0x00][..], bytes].concat());
+
+        let tbs_certificate = der(
+            0x30,
+            &[
+                der(0xa0, &der(0x02, &[0x02])),
+                der(0x02, &[serial]),
+                alg_id.to_vec(),
+                name("ML-DSA Test CA"),
+                der(
+                    0x30,
+                    &[der(0x17, b"250101000000Z"), der(0x18, b"99991231235959Z")].concat(),
+                ),
+                name(subject),
+                der(0x30, &[alg_id, &bit_string(pub_key)[..]].concat()),
+                der(0xa3, &der(0x30, &extensions.concat())),
+            ]
+            .concat(),
+        );
+        let signature = ca_signer.sign(&tbs_certificate).unwrap();
+
+        CertificateDer::from(der(
+            0x30,
+            &[tbs_certificate, alg_id.to_vec(), bit_string(&signature)].concat(),
+        ))
+    }
+
+    /*
+     * Moves all the pending TLS records from one side to the other.
+     * */
+    fn transfer(from: &mut rustls::Connection, to: &mut rustls::Connection) {
+        let mut buf = Vec::new();
+        while from.wants_write() {
+            from.write_tls(&mut buf).unwrap();
+        }
+
+        let mut rd = buf.as_slice();
+        while !rd.is_empty() {
+            to.read_tls(&mut rd).unwrap();
+            to.process_new_packets().unwrap();
+        }
+    }
+
+    /*
+     * Runs a full handshake in memory, returns the client and server
+     * connections once both are done.
+     * */
+    fn connect_in_memory(
+        client_config: Arc<rustls::ClientConfig>,
+        server_config: Arc<rustls::ServerConfig>,
+    ) -> (rustls::Connection, rustls::Connection) {
+        let mut client: rustls::Connection =
+            rustls::ClientConnection::new(client_config, "localhost".try_into().unwrap())
+                .unwrap()
+                .into();
+        let mut server: rustls::Connection =
+            rustls::ServerConnection::new(server_config).unwrap().into();
+
+        while client.is_handshaking() || server.is_handshaking() {
+            transfer(&mut client, &mut server);
+            transfer(&mut server, &mut client);
+        }
+
+        (client, server)
+    }
+
+    /*
+     * Runs a full handshake in memory, then sends application data both ways
+     * to make sure both sides derived the same keys.
+     * Returns the negotiated key exchange group.
+     * */
+    fn handshake_in_memory(
+        client_config: Arc<rustls::ClientConfig>,
+        server_config: Arc<rustls::ServerConfig>,
+    ) -> rustls::NamedGroup {
+        let (mut client, mut server) = connect_in_memory(client_config, server_config);
+
+        assert_eq!(client.handshake_kind(), Some(rustls::HandshakeKind::Full));
+
+        client.writer().write_all(b"ping").unwrap();
+        transfer(&mut client, &mut server);
+        let mut received = [0u8; 4];
+        server.reader().read_exact(&mut received).unwrap();
+        assert_eq!(&received, b"ping");
+
+        server.writer().write_all(b"pong").unwrap();
+        transfer(&mut server, &mut client);
+        client.reader().read_exact(&mut received).unwrap();
+        assert_eq!(&received, b"pong");
+
+        let client_group = client.negotiated_key_exchange_group().unwrap().name();
+        let server_group = server.negotiated_key_exchange_group().unwrap().name();
+        assert_eq!(client_group, server_group);
+
+        client_group
+    }
+
+    fn with_kx_groups(provider: CryptoProvider, groups: &[rustls::NamedGroup]) -> CryptoProvider {
+        CryptoProvider {
+            kx_groups: provider
+                .kx_groups
+                .into_iter()
+                .filter(|group| groups.contains(&group.name()))
+                .collect(),
+            ..provider
+        }
+    }
+
+    #[test]
+    fn x25519mlkem768_client_against_aws_lc_rs_server() {
+        let pki = TestPki::new();
+        let server_provider = with_kx_groups(
+            rustls::crypto::aws_lc_rs::default_provider(),
+            &[rustls::NamedGroup::X25519MLKEM768],
+        );
+
+        let group = handshake_in_memory(
+            pki.client_config(rustls_wolfcrypt_provider::provider()),
+            pki.server_config(server_provider),
+        );
+
+        assert_eq!(group, rustls::NamedGroup::X25519MLKEM768);
+    }
+
+    #[test]
+    fn x25519mlkem768_server_against_aws_lc_rs_client() {
+        let pki = TestPki::new();
+        let client_provider = with_kx_groups(
+            rustls::crypto::aws_lc_rs::default_provider(),
+            &[rustls::NamedGroup::X25519MLKEM768],
+        );
+
+        let group = handshake_in_memory(
+            pki.client_config(client_provider),
+            pki.server_config(rustls_wolfcrypt_provider::provider()),
+        );
+
+        assert_eq!(group, rustls::NamedGroup::X25519MLKEM768);
+    }
+
+    #[test]
+    fn x25519mlkem768_between_wolfcrypt_client_and_server() {
+        let pki = TestPki::new();
+
+        let group = handshake_in_memory(
+            pki.client_config(rustls_wolfcrypt_provider::provider()),
+            pki.server_config(rustls_wolfcrypt_provider::provider()),
+        );
+
+        assert_eq!(group, rustls::NamedGroup::X25519MLKEM768);
+    }
+
+    /*
+     * ring doesn't support ML-KEM: since we also send the X25519 half of the
+     * hybrid share on its own, the handshake must fall back to X25519
+     * without a HelloRetryRequest.
+     * */
+    #[test]
+    fn x25519mlkem768_client_falls_back_against_ring_server() {
+        let pki = TestPki::new();
+
+        let group = handshake_in_memory(
+            pki.client_config(rustls_wolfcrypt_provider::provider()),
+            pki.server_config(rustls::crypto::ring::default_provider()),
+        );
+
+        assert_eq!(group, rustls::NamedGroup::X25519);
+    }
+
+    #[test]
+    fn x25519mlkem768_server_against_ring_client() {
+        let pki = TestPki::new();
+
+        let group = handshake_in_memory(
+            pki.client_config(rustls::crypto::ring::default_provider()),
+            pki.server_config(rustls_wolfcrypt_provider::provider()),
+        );
+
+        assert_eq!(group, rustls::NamedGroup::X25519);
+    }
+
+    /*
+     * CNSA 2.0 style configuration: no X25519 anywhere, only the NIST
+     * curve hybrids and the pure ML-KEM groups, selected explicitly
+     * through CryptoProvider::kx_groups.
+     * */
+    fn cnsa_provider(group: &'static dyn rustls::crypto::SupportedKxGroup) -> CryptoProvider {
+        CryptoProvider {
+            kx_groups: vec![group],
+            ..rustls_wolfcrypt_provider::provider()
+        }
+    }
+
+    #[test]
+    fn nist_hybrid_and_mlkem_groups_between_wolfcrypt_client_and_server() {
+        let pki = TestPki::new();
+        let groups: [&'static dyn rustls::crypto::SupportedKxGroup; 4] = [
+            &rustls_wolfcrypt_provider::kx::SECP256R1MLKEM768,
+            &rustls_wolfcrypt_provider::kx::SECP384R1MLKEM1024,
+            &rustls_wolfcrypt_provider::kx::MLKEM768,
+            &rustls_wolfcrypt_provider::kx::MLKEM1024,
+        ];
+
+        for group in groups {
+            let negotiated = handshake_in_memory(
+                pki.client_config(cnsa_provider(group)),
+                pki.server_config(cnsa_provider(group)),
+            );
+
+            assert_eq!(negotiated, group.name());
+        }
+    }
+
+    #[test]
+    fn mlkem768_client_against_aws_lc_rs_server() {
+        let pki = TestPki::new();
+        let server_provider = CryptoProvider {
+            kx_groups: vec![rustls::crypto::aws_lc_rs::kx_group::MLKEM768],
+            ..rustls::crypto::aws_lc_rs::default_provider()
+        };
+
+        let group = handshake_in_memory(
+            pki.client_config(cnsa_provider(&rustls_wolfcrypt_provider::kx::MLKEM768)),
+            pki.server_config(server_provider),
+        );
+
+        assert_eq!(group, rustls::NamedGroup::MLKEM768);
+    }
+
+    #[test]
+    fn mlkem768_server_against_aws_lc_rs_client() {
+        let pki = TestPki::new();
+        let client_provider = CryptoProvider {
+            kx_groups: vec![rustls::crypto::aws_lc_rs::kx_group::MLKEM768],
+            ..rustls::crypto::aws_lc_rs::default_provider()
+        };
+
+        let group = handshake_in_memory(
+            pki.client_config(client_provider),
+            pki.server_config(cnsa_provider(&rustls_wolfcrypt_provider::kx::MLKEM768)),
+        );
+
+        assert_eq!(group, rustls::NamedGroup::MLKEM768);
+    }
+
+    #[test]
+    fn x448_between_wolfcrypt_client_and_server() {
+        let pki = TestPki::new();
+        let provider = || {
+            with_kx_groups(
+                rustls_wolfcrypt_provider::provider(),
+                &[rustls::NamedGroup::X448],
+            )
+        };
+
+        let group =
+            handshake_in_memory(pki.client_config(provider()), pki.server_config(provider()));
+
+        assert_eq!(group, rustls::NamedGroup::X448);
+    }
+
+    /*
+     * Peers that refuse ECDHE altogether only offer the RFC 7919 groups.
+     * */
+    #[test]
+    fn ffdhe_between_wolfcrypt_client_and_server() {
+        let pki = TestPki::new();
+
+        let groups: [&'static dyn rustls::crypto::SupportedKxGroup; 3] = [
+            &rustls_wolfcrypt_provider::kx::FFDHE2048,
+            &rustls_wolfcrypt_provider::kx::FFDHE3072,
+            &rustls_wolfcrypt_provider::kx::FFDHE4096,
+        ];
+
+        for group in groups {
+            let provider = || CryptoProvider {
+                kx_groups: vec![group],
+                ..rustls_wolfcrypt_provider::provider()
+            };
+
+            let negotiated =
+                handshake_in_memory(pki.client_config(provider()), pki.server_config(provider()));
+
+            assert_eq!(negotiated, group.name());
+        }
+    }
+
+    #[test]
+    fn brainpool_between_wolfcrypt_client_and_server() {
+        let pki = TestPki::new();
+        let groups: [&'static dyn rustls::crypto::SupportedKxGroup; 3] = [
+            &rustls_wolfcrypt_provider::kx::BRAINPOOLP256R1TLS13,
+            &rustls_wolfcrypt_provider::kx::BRAINPOOLP384R1TLS13,
+            &rustls_wolfcrypt_provider::kx::BRAINPOOLP512R1TLS13,
+        ];
+
+        for group in groups {
+            let provider = || CryptoProvider {
+                kx_groups: vec![group],
+                ..rustls_wolfcrypt_provider::provider()
+            };
+
+            let negotiated =
+                handshake_in_memory(pki.client_config(provider()), pki.server_config(provider()));
+
+            assert_eq!(negotiated, group.name());
+        }
+    }
+
+    /*
+     * A certificate (and CertificateVerify) signed with ML-DSA: the schemes
+     * have to match the ones rustls decodes from the peer's signature_algorithms.
+     * */
+    #[test]
+    fn mldsa_certificate_between_wolfcrypt_client_and_server() {
+        for (scheme, level) in [
+            (SignatureScheme::ML_DSA_44, WC_ML_DSA_44),
+            (SignatureScheme::ML_DSA_65, WC_ML_DSA_65),
+            (SignatureScheme::ML_DSA_87, WC_ML_DSA_87),
+        ] {
+            let pki = TestPki::new_mldsa(scheme, level);
+
+            handshake_in_memory(
+                pki.client_config(rustls_wolfcrypt_provider::provider()),
+                pki.server_config(rustls_wolfcrypt_provider::provider()),
+            );
+        }
+    }
+
+    /*
+     * None of the other providers we test against implement CCM,
+     * so both ends are ours. CCM_8 isn't in the defaults, so this
+     * also checks that it can be opted into.
+     * */
+    #[test]
+    fn aes128ccm_between_wolfcrypt_client_and_server() {
+        let pki = TestPki::new();
+
+        assert!(!rustls_wolfcrypt_provider::provider()
+            .cipher_suites
+            .contains(&rustls_wolfcrypt_provider::TLS13_AES_128_CCM_8_SHA256));
+
+        for suite in [
+            rustls_wolfcrypt_provider::TLS13_AES_128_CCM_SHA256,
+            rustls_wolfcrypt_provider::TLS13_AES_128_CCM_8_SHA256,
+        ] {
+            let provider = || CryptoProvider {
+                cipher_suites: vec![suite],
+                ..rustls_wolfcrypt_provider::provider()
+            };
+
+            // Neither side has anything else to offer, so a completed
+            // handshake means `suite` was negotiated.
+            handshake_in_memory(pki.client_config(provider()), pki.server_config(provider()));
+        }
+    }
+
+    /*
+     * RFC 7251 only defines CCM with ECDSA, which is what TestPki issues.
+     * */
+    #[test]
+    fn tls12_aes_ccm_between_wolfcrypt_client_and_server() {
+        let pki = TestPki::new();
+
+        for suite in [
+            rustls_wolfcrypt_provider::TLS12_ECDHE_ECDSA_WITH_AES_128_CCM_8,
+            rustls_wolfcrypt_provider::TLS12_ECDHE_ECDSA_WITH_AES_256_CCM_8,
+        ] {
+            assert!(!rustls_wolfcrypt_provider::provider()
+                .cipher_suites
+                .contains(&suite));
+        }
+
+        for suite in [
+            rustls_wolfcrypt_provider::TLS12_ECDHE_ECDSA_WITH_AES_128_CCM,
+            rustls_wolfcrypt_provider::TLS12_ECDHE_ECDSA_WITH_AES_256_CCM,
+            rustls_wolfcrypt_provider::TLS12_ECDHE_ECDSA_WITH_AES_128_CCM_8,
+            rustls_wolfcrypt_provider::TLS12_ECDHE_ECDSA_WITH_AES_256_CCM_8,
+        ] {
+            let provider = || CryptoProvider {
+                cipher_suites: vec![suite],
+                ..rustls_wolfcrypt_provider::provider()
+            };
+
+            // As above, `suite` is all either side can negotiate.
+            handshake_in_memory(
+                pki.client_config_for_versions(provider(), &[&TLS12]),
+                pki.server_config_for_versions(provider(), &[&TLS12]),
+            );
+        }
+    }
+
+    #[cfg(feature = "integrity-only")]
+    #[test]
+    fn integrity_only_between_wolfcrypt_client_and_server() {
+        let pki = TestPki::new();
+
+        let suite = rustls_wolfcrypt_provider::TLS_SHA256_SHA256;
+        let provider = || CryptoProvider {
+            cipher_suites: vec![suite],
+            ..rustls_wolfcrypt_provider::provider()
+        };
+
+        let (mut client, mut server) =
+            connect_in_memory(pki.client_config(provider()), pki.server_config(provider()));
+
+        assert_eq!(client.negotiated_cipher_suite(), Some(suite));
+
+        // Application data travels in the clear.
+        client.writer().write_all(b"inspect me").unwrap();
+        let mut wire = Vec::new();
+        client.write_tls(&mut wire).unwrap();
+        assert!(wire.windows(10).any(|w| w == b"inspect me"));
+
+        let mut rd = wire.as_slice();
+        server.read_tls(&mut rd).unwrap();
+        server.process_new_packets().unwrap();
+        let mut received = [0u8; 10];
+        server.reader().read_exact(&mut received).unwrap();
+        assert_eq!(&received, b"inspect me");
+    }
+
+    /*
+     * Protects a packet with one side's keys and removes the
+     * protection with the other side's.
+     * */
+    fn quic_round_trip(from: &rustls::quic::DirectionalKeys, to: &rustls::quic::DirectionalKeys) {
+        let header = [0xc3, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x2a];
+        let pn_offset = header.len() - 4;
+        let plain = [0x42u8; 64];
+
+        let mut packet = header.to_vec();
+        let mut payload = plain;
+        let tag = from
+            .packet
+            .encrypt_in_place(42, &header, &mut payload)
+            .unwrap();
+        packet.extend_from_slice(&payload);
+        packet.extend_from_slice(tag.as_ref());
+
+        let sample = packet[pn_offset + 4..pn_offset + 4 + from.header.sample_len()].to_vec();
+        let (first, rest) = packet.split_first_mut().unwrap();
+        from.header
+            .encrypt_in_place(&sample, first, &mut rest[pn_offset - 1..pn_offset + 3])
+            .unwrap();
+        assert_ne!(&packet[..header.len()], &header);
+
+        let (first, rest) = packet.split_first_mut().unwrap();
+        to.header
+            .decrypt_in_place(&sample, first, &mut rest[pn_offset - 1..pn_offset + 3])
+            .unwrap();
+        assert_eq!(&packet[..header.len()], &header);
+
+        let (aad, payload) = packet.split_at_mut(header.len());
+        assert_eq!(
+            to.packet.decrypt_in_place(42, aad, payload).unwrap(),
+            &plain
+        );
+    }
+
+    #[test]
+    fn quic_packets_against_aws_lc_rs() {
+        use rustls::crypto::aws_lc_rs::cipher_suite as aws;
+        use rustls::quic::{Keys, Side, Version};
+
+        let dcid = [0x83, 0x94, 0xc8, 0xf0, 0x3e, 0x51, 0x57, 0x08];
+
+        for (ours, theirs) in [
+            (
+                rustls_wolfcrypt_provider::TLS13_AES_128_GCM_SHA256,
+                aws::TLS13_AES_128_GCM_SHA256,
+            ),
+            (
+                rustls_wolfcrypt_provider::TLS13_AES_256_GCM_SHA384,
+                aws::TLS13_AES_256_GCM_SHA384,
+            ),
+            (
+                rustls_wolfcrypt_provider::TLS13_CHACHA20_POLY1305_SHA256,
+                aws::TLS13_CHACHA20_POLY1305_SHA256,
+            ),
+        ] {
+            let ours = ours.tls13().unwrap();
+            let theirs = theirs.tls13().unwrap();
+
+            let client = Keys::initial(Version::V1, ours, ours.quic.unwrap(), &dcid, Side::Client);
+            let server = Keys::initial(
+                Version::V1,
+                theirs,
+                theirs.quic.unwrap(),
+                &dcid,
+                Side::Server,
+            );
+
+            quic_round_trip(&client.local, &server.remote);
+            quic_round_trip(&server.local, &client.remote);
+        }
+    }
+
+    /*
+     * The second connection has to be resumed from
+     * a ticket sealed by the first one.
+     * */
+    #[test]
+    fn session_tickets_resume_with_wolfcrypt_ticketer() {
+        use rustls_wolfcrypt_provider::ticketer::TicketCipher;
+
+        let pki = TestPki::new();
+
+        for cipher in [TicketCipher::Aes256Gcm, TicketCipher::ChaCha20Poly1305] {
+            for version in [&TLS12, &TLS13] {
+                let client_config = pki
+                    .client_config_for_versions(rustls_wolfcrypt_provider::provider(), &[version]);
+                let mut server_config =
+                    Arc::into_inner(pki.server_config_for_versions(
+                        rustls_wolfcrypt_provider::provider(),
+                        &[version],
+                    ))
+                    .unwrap();
+                server_config.ticketer =
+                    Arc::new(rustls::TicketRotator::new(60 * 60, cipher.generator()).unwrap());
+                server_config.session_storage = Arc::new(rustls::server::NoServerSessionStorage {});
+                let server_config = Arc::new(server_config);
+
+                handshake_in_memory(client_config.clone(), server_config.clone());
+
+                let (client, _) = connect_in_memory(client_config, server_config);
+                assert_eq!(
+                    client.handshake_kind(),
+                    Some(rustls::HandshakeKind::Resumed)
+                );
+            }
+        }
+    }
+
+    /*
+     * Whatever one side seals, the other has to open,
+     * for each suite both providers have.
+     * */
+    #[test]
+    fn hpke_against_aws_lc_rs() {
+        use rustls::crypto::hpke::Hpke;
+
+        let info = b"rustls-wolfcrypt-provider";
+        let aad = b"aad";
+        let plaintext = b"hello HPKE";
+
+        for ours in rustls_wolfcrypt_provider::hpke::ALL_SUPPORTED_SUITES {
+            let theirs: &dyn Hpke = *rustls::crypto::aws_lc_rs::hpke::ALL_SUPPORTED_SUITES
+                .iter()
+                .find(|theirs| theirs.suite() == ours.suite())
+                .unwrap();
+
+            for (sender, receiver) in [(*ours, theirs), (theirs, *ours)] {
+                let (public, private) = receiver.generate_key_pair().unwrap();
+
+                let (enc, ciphertext) = sender.seal(info, aad, plaintext, &public).unwrap();
+                assert_eq!(
+                    receiver
+                        .open(&enc, info, aad, &ciphertext, &private)
+                        .unwrap(),
+                    plaintext
+                );
+
+                let (enc, mut sealer) = sender.setup_sealer(info, &public).unwrap();
+                let mut opener = receiver.setup_opener(&enc, info, &private).unwrap();
+                for _ in 0..3 {
+                    let ciphertext = sealer.seal(aad, plaintext).unwrap();
+                    assert_eq!(opener.open(aad, &ciphertext).unwrap(), plaintext);
+                }
+            }
+        }
+    }
+
+    /*
+     * The server compresses its certificate chain, the client
+     * has to go through the decompressor to get it back.
+     * */
+    #[cfg(feature = "compress")]
+    #[test]
+    fn cert_compression_between_wolfcrypt_client_and_server() {
+        use rustls::compress::{CertDecompressor, DecompressionFailed};
+        use rustls::CertificateCompressionAlgorithm;
+        use rustls_wolfcrypt_provider::compress::{ZLIB_COMPRESSOR, ZLIB_DECOMPRESSOR};
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        #[derive(Debug)]
+        struct RecordingDecompressor(AtomicBool);
+
+        impl CertDecompressor for RecordingDecompressor {
+            fn decompress(
+                &self,
+                input: &[u8],
+                output: &mut [u8],
+            ) -> Result<(), DecompressionFailed> {
+                self.0.store(true, Ordering::SeqCst);
+                ZLIB_DECOMPRESSOR.decompress(input, output)
+            }
+
+            fn algorithm(&self) -> CertificateCompressionAlgorithm {
+                ZLIB_DECOMPRESSOR.algorithm()
+            }
+        }
+
+        static DECOMPRESSOR: RecordingDecompressor = RecordingDecompressor(AtomicBool::new(false));
+
+        let pki = TestPki::new();
+        let mut client_config =
+            Arc::into_inner(pki.client_config(rustls_wolfcrypt_provider::provider())).unwrap();
+        client_config.cert_decompressors = vec![&DECOMPRESSOR];
+        let mut server_config =
+            Arc::into_inner(pki.server_config(rustls_wolfcrypt_provider::provider())).unwrap();
+        server_config.cert_compressors = vec![ZLIB_COMPRESSOR];
+
+        handshake_in_memory(Arc::new(client_config), Arc::new(server_config));
+
+        assert!(DECOMPRESSOR.0.load(Ordering::SeqCst));
+    }
+
+    /*
+     * What kernel TLS would do with the extracted secrets:
+     * decrypt a record on its own, straight with wolfCrypt.
+     * */
+    fn decrypt_with_extracted_secrets(
+        version: rustls::ProtocolVersion,
+        secrets: &rustls::ConnectionTrafficSecrets,
+        seq: u64,
+        record: &[u8],
+    ) -> Vec<u8> {
+        use rustls::ConnectionTrafficSecrets;
+
+        const TAG_LENGTH: usize = 16;
+
+        let (key, iv, chacha) = match secrets {
+            ConnectionTrafficSecrets::Aes128Gcm { key, iv }
+            | ConnectionTrafficSecrets::Aes256Gcm { key, iv } => (key.as_ref(), iv.as_ref(), false),
+            ConnectionTrafficSecrets::Chacha20Poly1305 { key, iv } => {
+                (key.as_ref(), iv.as_ref(), true)
+            }
+            _ => panic!("unexpected secrets"),
+        };
+
+        let (header, payload) = record.split_at(5);
+
+        // The per record nonce: the iv XORed with the sequence number.
+        let mut nonce = [0u8; 12];
+        nonce.copy_from_slice(iv);
+        for (n, s) in nonce[4..].iter_mut().zip(seq.to_be_bytes()) {
+            *n ^= s;
+        }
+
+        let (aad, payload) = if version == rustls::ProtocolVersion::TLSv1_3 {
+            (header.to_vec(), payload)
+        } else {
+            // TLS 1.2 AES-GCM sends the explicit part of the nonce
+            // ahead of the cipher text (RFC 5288, 3).
+            let payload = match chacha {
+                true => payload,
+                false => {
+                    assert_eq!(&payload[..8], &nonce[4..]);
+                    &payload[8..]
+                }
+            };
+
+            let mut aad = seq.to_be_bytes().to_vec();
+            aad.extend_from_slice(&header[..3]);
+            aad.extend_from_slice(&((payload.len() - TAG_LENGTH) as u16).to_be_bytes());
+            (aad, payload)
+        };
+
+        let (cipher_text, tag) = payload.split_at(payload.len() - TAG_LENGTH);
+        let mut plain = vec![0u8; cipher_text.len()];
+
+        let ret = match chacha {
+            true => unsafe {
+                wc_ChaCha20Poly1305_Decrypt(
+                    key.as_ptr(),
+                    nonce.as_ptr(),
+                    aad.as_ptr(),
+                    aad.len() as word32,
+                    cipher_text.as_ptr(),
+                    cipher_text.len() as word32,
+                    tag.as_ptr(),
+                    plain.as_mut_ptr(),
+                )
+            },
+            false => unsafe {
+                let mut aes_c_type: Aes = mem::zeroed();
+                let aes_object = AesObject::from_ptr(&mut aes_c_type);
+
+                check_if_zero(wc_AesInit(
+                    aes_object.as_ptr(),
+                    std::ptr::null_mut(),
+                    INVALID_DEVID,
+                ))
+                .unwrap();
+                check_if_zero(wc_AesGcmSetKey(
+                    aes_object.as_ptr(),
+                    key.as_ptr(),
+                    key.len() as word32,
+                ))
+                .unwrap();
+
+                wc_AesGcmDecrypt(
+                    aes_object.as_ptr(),
+                    plain.as_mut_ptr(),
+                    cipher_text.as_ptr(),
+                    cipher_text.len() as word32,
+                    nonce.as_ptr(),
+                    nonce.len() as word32,
+                    tag.as_ptr(),
+                    tag.len() as word32,
+                    aad.as_ptr(),
+                    aad.len() as word32,
+                )
+            },
+        };
+        check_if_zero(ret).unwrap();
+
+        plain
+    }
+
+    #[test]
+    fn extracted_secrets_decrypt_records() {
+        let pki = TestPki::new();
+
+        for (suite, version) in [
+            (rustls_wolfcrypt_provider::TLS13_AES_128_GCM_SHA256, &TLS13),
+            (rustls_wolfcrypt_provider::TLS13_AES_256_GCM_SHA384, &TLS13),
+            (
+                rustls_wolfcrypt_provider::TLS13_CHACHA20_POLY1305_SHA256,
+                &TLS13,
+            ),
+            (
+                rustls_wolfcrypt_provider::TLS12_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256,
+                &TLS12,
+            ),
+            (
+                rustls_wolfcrypt_provider::TLS12_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384,
+                &TLS12,
+            ),
+            (
+                rustls_wolfcrypt_provider::TLS12_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256,
+                &TLS12,
+            ),
+        ] {
+            let provider = || CryptoProvider {
+                cipher_suites: vec![suite],
+                ..rustls_wolfcrypt_provider::provider()
+            };
+            let mut client_config =
+                Arc::into_inner(pki.client_config_for_versions(provider(), &[version])).unwrap();
+            client_config.enable_secret_extraction = true;
+            let mut server_config =
+                Arc::into_inner(pki.server_config_for_versions(provider(), &[version])).unwrap();
+            server_config.enable_secret_extraction = true;
+
+            let (mut client, mut server) =
+                connect_in_memory(Arc::new(client_config), Arc::new(server_config));
+
+            assert_eq!(client.negotiated_cipher_suite(), Some(suite));
+
+            // One record each way, kept out of the other side's hands.
+            let mut ping = Vec::new();
+            client.writer().write_all(b"ping").unwrap();
+            client.write_tls(&mut ping).unwrap();
+
+            let mut pong = Vec::new();
+            server.writer().write_all(b"pong").unwrap();
+            server.write_tls(&mut pong).unwrap();
+
+            let client_secrets = client.dangerous_extract_secrets().unwrap();
+            let server_secrets = server.dangerous_extract_secrets().unwrap();
+
+            // TLS 1.3 puts the content type after the data.
+            let expected = |data: &[u8]| {
+                let mut expected = data.to_vec();
+                if version == &TLS13 {
+                    expected.push(0x17);
+                }
+                expected
+            };
+
+            // The tx sequence numbers are the next ones to be used,
+            // the rx ones those of the records not read yet.
+            let (seq, secrets) = &client_secrets.tx;
+            assert_eq!(
+                decrypt_with_extracted_secrets(version.version, secrets, seq - 1, &ping),
+                expected(b"ping")
+            );
+            let (seq, secrets) = &server_secrets.rx;
+            assert_eq!(
+                decrypt_with_extracted_secrets(version.version, secrets, *seq, &ping),
+                expected(b"ping")
+            );
+
+            let (seq, secrets) = &server_secrets.tx;
+            assert_eq!(
+                decrypt_with_extracted_secrets(version.version, secrets, seq - 1, &pong),
+                expected(b"pong")
+            );
+            let (seq, secrets) = &client_secrets.rx;
+            assert_eq!(
+                decrypt_with_extracted_secrets(version.version, secrets, *seq, &pong),
+                expected(b"pong")
+            );
+        }
+    }
 }
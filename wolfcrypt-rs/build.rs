@@ -25,19 +25,34 @@ fn main() {
 ///
 /// This function:
 /// 1. Checks if WolfSSL needs to be set up
-/// 2. Sets up WolfSSL if necessary
+/// 2. Sets up WolfSSL if necessary, or builds it if this
+///    configuration (i.e. set of enabled features) wasn't built yet
 /// 3. Generates Rust bindings for the WolfSSL library
 ///
 /// Returns `Ok(())` if successful, or an error if any step fails.
 fn run_build() -> Result<()> {
+    let prefix = install_prefix();
+
     if fs::metadata(WOLFSSL_DIR).is_err() {
-        setup_wolfssl()?;
+        setup_wolfssl(&prefix)?;
+    } else if fs::metadata(prefix.join("lib").join("libwolfssl.a")).is_err() {
+        build_wolfssl(&prefix)?;
+        change_back_to_root()?;
     }
 
-    generate_bindings()?;
+    generate_bindings(&prefix)?;
     Ok(())
 }
 
+/// Returns where WolfSSL gets installed.
+///
+/// Cargo gives each set of enabled features its own `OUT_DIR`, so an
+/// accelerated build never replaces the portable library (or the other
+/// way around), and nothing outside of the target directory is touched.
+fn install_prefix() -> PathBuf {
+    PathBuf::from(env::var("OUT_DIR").unwrap()).join("wolfssl")
+}
+
 /// Generates Rust bindings for the WolfSSL library using bindgen.
 ///
 /// This function:
@@ -47,15 +62,18 @@ fn run_build() -> Result<()> {
 /// 4. Writes the bindings to a file
 ///
 /// Returns `Ok(())` if successful, or an error if binding generation fails.
-fn generate_bindings() -> Result<()> {
-    let wolfssl_lib_dir = Path::new("/opt/wolfssl-rs/lib/");
-    let wolfssl_include_dir = Path::new("/opt/wolfssl-rs/include/");
+fn generate_bindings(prefix: &Path) -> Result<()> {
+    let wolfssl_lib_dir = prefix.join("lib");
+    let wolfssl_include_dir = prefix.join("include");
 
     println!(
         "cargo:rustc-link-search={}",
         wolfssl_lib_dir.to_str().unwrap()
     );
     println!("cargo:rustc-link-lib=static=wolfssl");
+    if env::var_os("CARGO_FEATURE_ZLIB").is_some() {
+        println!("cargo:rustc-link-lib=z");
+    }
 
     let bindings = bindgen::Builder::default()
         .header("libraries.h")
@@ -85,11 +103,11 @@ fn generate_bindings() -> Result<()> {
 /// 5. Returns to the original directory
 ///
 /// Returns `Ok(())` if all steps complete successfully, or an error if any step fails.
-fn setup_wolfssl() -> Result<()> {
+fn setup_wolfssl(prefix: &Path) -> Result<()> {
     download_wolfssl()?;
     unzip_wolfssl()?;
     remove_zip()?;
-    build_wolfssl()?;
+    build_wolfssl(prefix)?;
     change_back_to_root()?;
     Ok(())
 }
@@ -154,33 +172,66 @@ fn remove_zip() -> Result<()> {
     Ok(())
 }
 
+/// Returns the options WolfSSL gets configured with.
+///
+/// On top of the base options, the `aesni` feature adds the AES-NI
+/// (and PCLMULQDQ) code paths, `intelasm` the AVX1/AVX2 ones and
+/// `zlib` the compression API, which links against the system zlib.
+/// wolfSSL checks the CPU with cpuid when it's first used and falls
+/// back to the portable C code if the instructions aren't there, so the
+/// resulting library still runs on any x86_64 machine.
+fn configure_options() -> Vec<&'static str> {
+    let mut options = vec![
+        "--enable-all",
+        "--enable-all-crypto",
+        "--enable-debug",
+        "--enable-kyber",
+        "--enable-experimental",
+        "--enable-dilithium",
+        "--enable-hpke",
+        // All the RFC 7919 groups (wc_DhSetNamedKey), with their q
+        // so that peer public values get the subgroup check.
+        "CPPFLAGS=-DHAVE_FFDHE_3072 -DHAVE_FFDHE_4096 -DHAVE_FFDHE_6144 -DHAVE_FFDHE_8192 -DHAVE_FFDHE_Q",
+        "--disable-shared",
+    ];
+
+    if env::var_os("CARGO_FEATURE_AESNI").is_some() {
+        options.push("--enable-aesni");
+    }
+    if env::var_os("CARGO_FEATURE_INTELASM").is_some() {
+        options.push("--enable-intelasm");
+    }
+    if env::var_os("CARGO_FEATURE_ZLIB").is_some() {
+        options.push("--with-libz");
+    }
+
+    options
+}
+
 /// Builds WolfSSL from source.
 ///
 /// This function:
 /// 1. Changes to the source directory
 /// 2. Runs autogen.sh to generate build files
 /// 3. Configures the build with specific options
-/// 4. Builds the library
-/// 5. Installs the library system-wide
+/// 4. Builds the library (from scratch, the source directory is shared
+///    by all the configurations)
+/// 5. Installs the library under `prefix`
 ///
 /// Returns `Ok(())` if all build steps succeed, or an error if any step fails.
-fn build_wolfssl() -> Result<()> {
+fn build_wolfssl(prefix: &Path) -> Result<()> {
+    let prefix_option = format!("--prefix={}", prefix.display());
+    let mut options: Vec<&str> = configure_options();
+    options.push(&prefix_option);
+
     env::set_current_dir(WOLFSSL_DIR)?;
     println!("Changed directory to {}.", WOLFSSL_DIR);
 
     run_command("./autogen.sh", &[])?;
-    run_command(
-        "./configure",
-        &[
-            "--enable-all",
-            "--enable-all-crypto",
-            "--enable-debug",
-            "--disable-shared",
-            "--prefix=/opt/wolfssl-rs/",
-        ],
-    )?;
+    run_command("./configure", &options)?;
+    run_command("make", &["clean"])?;
     run_command("make", &[])?;
-    run_command("sudo", &["make", "install"])?;
+    run_command("make", &["install"])?;
 
     Ok(())
 }